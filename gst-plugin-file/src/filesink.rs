@@ -10,7 +10,7 @@
 use std::fs::File;
 use url::Url;
 
-use std::io::Write;
+use std::io::{Write, Seek, SeekFrom};
 use std::convert::From;
 
 use gst_plugin::error::*;
@@ -24,7 +24,11 @@ use slog::Logger;
 #[derive(Debug)]
 enum StreamingState {
     Stopped,
-    Started { file: File, position: u64 },
+    Started {
+        file: File,
+        seekable: bool,
+        position: u64,
+    },
 }
 
 #[derive(Debug)]
@@ -91,8 +95,12 @@ impl Sink for FileSink {
 
         debug!(self.logger, "Opened file {:?}", file);
 
+        // Pipes and devices can't be seeked
+        let seekable = file.metadata().map(|m| m.is_file()).unwrap_or(false);
+
         self.streaming_state = StreamingState::Started {
             file: file,
+            seekable: seekable,
             position: 0,
         };
 
@@ -105,6 +113,46 @@ impl Sink for FileSink {
         Ok(())
     }
 
+    fn is_seekable(&self) -> bool {
+        match self.streaming_state {
+            StreamingState::Started { seekable, .. } => seekable,
+            StreamingState::Stopped => false,
+        }
+    }
+
+    fn seek(&mut self, offset: u64) -> Result<(), ErrorMessage> {
+        let logger = &self.logger;
+
+        let (file, position) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut file,
+                ref mut position,
+                ..
+            } => (file, position),
+            StreamingState::Stopped => {
+                return Err(error_msg!(SinkError::Failure, ["Not started yet"]));
+            }
+        };
+
+        if *position == offset {
+            return Ok(());
+        }
+
+        debug!(logger, "Seeking from {} to {}", position, offset);
+
+        try!(file.seek(SeekFrom::Start(offset))
+                 .or_else(|err| {
+                              error!(logger, "Failed to seek to {}: {:?}", offset, err);
+                              Err(error_msg!(SinkError::SeekFailed,
+                                             ["Failed to seek to {}: {}",
+                                              offset,
+                                              err.to_string()]))
+                          }));
+        *position = offset;
+
+        Ok(())
+    }
+
     fn render(&mut self, buffer: &Buffer) -> Result<(), FlowError> {
         // FIXME: Because we borrow streaming state mutably below
         let logger = self.logger.clone();
@@ -115,6 +163,7 @@ impl Sink for FileSink {
             StreamingState::Started {
                 ref mut file,
                 ref mut position,
+                ..
             } => (file, position),
             StreamingState::Stopped => {
                 return Err(FlowError::Error(error_msg!(SinkError::Failure, ["Not started yet"])));
@@ -15,6 +15,7 @@ extern crate slog;
 extern crate gst_plugin;
 
 use gst_plugin::plugin::*;
+use gst_plugin::caps::*;
 use gst_plugin::source::*;
 use gst_plugin::sink::*;
 
@@ -35,7 +36,12 @@ fn plugin_init(plugin: &Plugin) -> bool {
                         rank: 256 + 100,
                         create_instance: FileSrc::new_boxed,
                         protocols: vec!["file".into()],
+                        caps: Caps::new_any(),
                         push_only: false,
+                        blocksize: None,
+                        live: false,
+                        properties: Vec::new(),
+                        signals: Vec::new(),
                     });
 
     sink_register(plugin,
@@ -48,6 +54,11 @@ fn plugin_init(plugin: &Plugin) -> bool {
                       rank: 256 + 100,
                       create_instance: FileSink::new_boxed,
                       protocols: vec!["file".into()],
+                      caps: Caps::new_any(),
+                      sync: false,
+                      async_enabled: true,
+                      properties: Vec::new(),
+                      signals: Vec::new(),
                   });
 
     true
@@ -22,6 +22,8 @@ use gst_plugin::utils;
 use gst_plugin::utils::Element;
 use gst_plugin::log::*;
 use gst_plugin::caps::Caps;
+use gst_plugin::streams::*;
+use gst_plugin::tags::*;
 use gst_plugin::miniobject::*;
 use gst_plugin::value::Rational32;
 use gst_plugin::bytes::*;
@@ -53,6 +55,7 @@ struct StreamingState {
     last_position: Option<u64>,
 
     metadata: Option<Metadata>,
+    pending_tags: Option<GstRc<TagList>>,
 
     aac_sequence_header: Option<GstRc<Buffer>>,
     avc_sequence_header: Option<GstRc<Buffer>>,
@@ -68,6 +71,7 @@ impl StreamingState {
             got_all_streams: false,
             last_position: None,
             metadata: None,
+            pending_tags: None,
             aac_sequence_header: None,
             avc_sequence_header: None,
         }
@@ -144,6 +148,45 @@ impl AudioFormat {
         self.to_caps().map(|c| c.to_string())
     }
 
+    fn to_stream(&self) -> Option<Stream> {
+        self.to_caps().map(|caps| {
+            let stream = Stream::new("audio", Some(caps), TYPE_AUDIO, StreamFlags::empty());
+            stream.set_tags(Some(&self.to_tags()));
+            stream
+        })
+    }
+
+    fn to_tags(&self) -> GstRc<TagList> {
+        let codec = match self.format {
+            flavors::SoundFormat::MP3 |
+            flavors::SoundFormat::MP3_8KHZ => Some("MPEG-1 Layer 3 (MP3)"),
+            flavors::SoundFormat::PCM_NE |
+            flavors::SoundFormat::PCM_LE => Some("Uncompressed PCM audio"),
+            flavors::SoundFormat::ADPCM => Some("Shockwave ADPCM"),
+            flavors::SoundFormat::NELLYMOSER_16KHZ_MONO |
+            flavors::SoundFormat::NELLYMOSER_8KHZ_MONO |
+            flavors::SoundFormat::NELLYMOSER => Some("Nellymoser Asao"),
+            flavors::SoundFormat::PCM_ALAW => Some("A-Law"),
+            flavors::SoundFormat::PCM_ULAW => Some("Mu-Law"),
+            flavors::SoundFormat::AAC => Some("MPEG-4 AAC"),
+            flavors::SoundFormat::SPEEX => Some("Speex"),
+            flavors::SoundFormat::DEVICE_SPECIFIC => None,
+        };
+
+        let mut tags = TagList::new();
+        {
+            let tags = tags.get_mut().unwrap();
+            if let Some(codec) = codec {
+                tags.add::<AudioCodec>(codec, MergeMode::Replace);
+            }
+            if let Some(bitrate) = self.bitrate {
+                tags.add::<NominalBitrate>(bitrate, MergeMode::Replace);
+            }
+        }
+
+        tags
+    }
+
     fn to_caps(&self) -> Option<GstRc<Caps>> {
         let mut caps = match self.format {
             flavors::SoundFormat::MP3 |
@@ -322,6 +365,41 @@ impl VideoFormat {
         self.to_caps().map(|caps| caps.to_string())
     }
 
+    fn to_stream(&self) -> Option<Stream> {
+        self.to_caps().map(|caps| {
+            let stream = Stream::new("video", Some(caps), TYPE_VIDEO, StreamFlags::empty());
+            stream.set_tags(Some(&self.to_tags()));
+            stream
+        })
+    }
+
+    fn to_tags(&self) -> GstRc<TagList> {
+        let codec = match self.format {
+            flavors::CodecId::SORENSON_H263 => Some("Sorenson Spark Video"),
+            flavors::CodecId::SCREEN => Some("Flash Screen Video"),
+            flavors::CodecId::VP6 => Some("On2 VP6/Flash"),
+            flavors::CodecId::VP6A => Some("On2 VP6/Flash with alpha channel"),
+            flavors::CodecId::SCREEN2 => Some("Flash Screen Video 2"),
+            flavors::CodecId::H264 => Some("H.264 / AVC"),
+            flavors::CodecId::H263 => Some("H.263"),
+            flavors::CodecId::MPEG4Part2 => Some("MPEG-4 video"),
+            flavors::CodecId::JPEG => None,
+        };
+
+        let mut tags = TagList::new();
+        {
+            let tags = tags.get_mut().unwrap();
+            if let Some(codec) = codec {
+                tags.add::<VideoCodec>(codec, MergeMode::Replace);
+            }
+            if let Some(bitrate) = self.bitrate {
+                tags.add::<NominalBitrate>(bitrate, MergeMode::Replace);
+            }
+        }
+
+        tags
+    }
+
     fn to_caps(&self) -> Option<GstRc<Caps>> {
         let mut caps = match self.format {
             flavors::CodecId::SORENSON_H263 => {
@@ -419,6 +497,28 @@ struct Metadata {
 }
 
 impl Metadata {
+    fn to_tags(&self) -> GstRc<TagList> {
+        let mut tags = TagList::new();
+        {
+            let tags = tags.get_mut().unwrap();
+            tags.add::<ContainerFormat>("Flash", MergeMode::Replace);
+            if let Some(ref title) = self.title {
+                tags.add::<Title>(title.as_str(), MergeMode::Replace);
+            }
+            if let Some(ref creator) = self.creator {
+                tags.add::<Artist>(creator.as_str(), MergeMode::Replace);
+            }
+            if let Some(ref metadata_creator) = self.metadata_creator {
+                tags.add::<Encoder>(metadata_creator.as_str(), MergeMode::Replace);
+            }
+            if let Some(duration) = self.duration {
+                tags.add::<Duration>(duration, MergeMode::Replace);
+            }
+        }
+
+        tags
+    }
+
     fn new(script_data: &flavors::ScriptData) -> Metadata {
         assert_eq!(script_data.name, "onMetaData");
 
@@ -558,21 +658,23 @@ impl FlvDemux {
                     .as_mut()
                     .map(|v| v.update_with_metadata(&metadata))
                     .unwrap_or(false);
+                // Sent as global tags on the next call
+                streaming_state.pending_tags = Some(metadata.to_tags());
                 streaming_state.metadata = Some(metadata);
 
                 if audio_changed || video_changed {
                     let mut streams = Vec::new();
 
                     if audio_changed {
-                        if let Some(caps) =
-                            streaming_state.audio.as_ref().and_then(|a| a.to_caps()) {
-                            streams.push(Stream::new(AUDIO_STREAM_ID, caps, String::from("audio")));
+                        if let Some(stream) =
+                            streaming_state.audio.as_ref().and_then(|a| a.to_stream()) {
+                            streams.push((AUDIO_STREAM_ID, stream));
                         }
                     }
                     if video_changed {
-                        if let Some(caps) =
-                            streaming_state.video.as_ref().and_then(|v| v.to_caps()) {
-                            streams.push(Stream::new(VIDEO_STREAM_ID, caps, String::from("video")));
+                        if let Some(stream) =
+                            streaming_state.video.as_ref().and_then(|v| v.to_stream()) {
+                            streams.push((VIDEO_STREAM_ID, stream));
                         }
                     }
 
@@ -607,14 +709,12 @@ impl FlvDemux {
             debug!(logger, "Got new audio format: {:?}", new_audio_format);
             let new_stream = streaming_state.audio == None;
 
-            let caps = new_audio_format.to_caps();
-            if let Some(caps) = caps {
+            if let Some(stream) = new_audio_format.to_stream() {
                 streaming_state.audio = Some(new_audio_format);
-                let stream = Stream::new(AUDIO_STREAM_ID, caps, String::from("audio"));
                 if new_stream {
-                    return Ok(HandleBufferResult::StreamAdded(stream));
+                    return Ok(HandleBufferResult::StreamAdded(AUDIO_STREAM_ID, stream));
                 } else {
-                    return Ok(HandleBufferResult::StreamChanged(stream));
+                    return Ok(HandleBufferResult::StreamChanged(AUDIO_STREAM_ID, stream));
                 }
             } else {
                 streaming_state.audio = None;
@@ -757,14 +857,12 @@ impl FlvDemux {
 
             let new_stream = streaming_state.video == None;
 
-            let caps = new_video_format.to_caps();
-            if let Some(caps) = caps {
+            if let Some(stream) = new_video_format.to_stream() {
                 streaming_state.video = Some(new_video_format);
-                let stream = Stream::new(VIDEO_STREAM_ID, caps, String::from("video"));
                 if new_stream {
-                    return Ok(HandleBufferResult::StreamAdded(stream));
+                    return Ok(HandleBufferResult::StreamAdded(VIDEO_STREAM_ID, stream));
                 } else {
-                    return Ok(HandleBufferResult::StreamChanged(stream));
+                    return Ok(HandleBufferResult::StreamChanged(VIDEO_STREAM_ID, stream));
                 }
             } else {
                 streaming_state.video = None;
@@ -972,6 +1070,12 @@ impl FlvDemux {
                 Ok(HandleBufferResult::Again)
             }
             State::Streaming => {
+                if let Some(tags) = self.streaming_state
+                       .as_mut()
+                       .and_then(|streaming_state| streaming_state.pending_tags.take()) {
+                    return Ok(HandleBufferResult::GlobalTags(tags));
+                }
+
                 if self.adapter.get_available() < 16 {
                     return Ok(HandleBufferResult::NeedMoreData);
                 }
@@ -1077,6 +1181,14 @@ impl Demuxer for FlvDemux {
         unimplemented!();
     }
 
+    fn handle_segment(&mut self, start: u64, _stop: Option<u64>) -> Result<(), ErrorMessage> {
+        // Anything still queued up belongs to the previous upstream position
+        debug!(self.logger, "Upstream continues from offset {}", start);
+        self.adapter.clear();
+
+        Ok(())
+    }
+
     fn handle_buffer(&mut self,
                      buffer: Option<GstRc<Buffer>>)
                      -> Result<HandleBufferResult, FlowError> {
@@ -27,17 +27,19 @@ use flvdemux::FlvDemux;
 
 fn plugin_init(plugin: &Plugin) -> bool {
     demuxer_register(plugin,
-                     &DemuxerInfo {
-                          name: "rsflvdemux",
-                          long_name: "FLV Demuxer",
-                          description: "Demuxes FLV Streams",
-                          classification: "Codec/Demuxer",
-                          author: "Sebastian Dröge <sebastian@centricular.com>",
-                          rank: 256 + 100,
-                          create_instance: FlvDemux::new_boxed,
-                          input_caps: &Caps::new_simple("video/x-flv", &[]),
-                          output_caps: &Caps::new_any(),
-                      });
+                     DemuxerInfo {
+                         name: "rsflvdemux".into(),
+                         long_name: "FLV Demuxer".into(),
+                         description: "Demuxes FLV Streams".into(),
+                         classification: "Codec/Demuxer".into(),
+                         author: "Sebastian Dröge <sebastian@centricular.com>".into(),
+                         rank: 256 + 100,
+                         create_instance: FlvDemux::new_boxed,
+                         input_caps: Caps::new_simple("video/x-flv", &[]),
+                         output_caps: Caps::new_any(),
+                         properties: Vec::new(),
+                         signals: Vec::new(),
+                     });
 
     true
 }
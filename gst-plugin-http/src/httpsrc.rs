@@ -7,27 +7,48 @@
 // except according to those terms.
 
 use std::u64;
-use std::io::Read;
+use std::io::{self, Read};
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
 use url::Url;
 use reqwest::{Client, Response};
 use reqwest::header::{ContentLength, ContentRange, ContentRangeSpec, Range, ByteRangeSpec,
-                      AcceptRanges, RangeUnit};
+                      AcceptRanges, RangeUnit, UserAgent, ContentType};
 
 use gst_plugin::error::*;
 use gst_plugin::source::*;
 use gst_plugin::buffer::*;
+use gst_plugin::miniobject::*;
+use gst_plugin::caps::*;
 use gst_plugin::utils::*;
 use gst_plugin::log::*;
+use gst_plugin::value::*;
 
 use slog::Logger;
 
+const READ_CHUNK_SIZE: usize = 4096;
+// Number of chunks the reader thread reads ahead
+const READ_QUEUE_SIZE: usize = 16;
+
+#[derive(Debug)]
+enum ReaderMessage {
+    Data(Vec<u8>),
+    Eos,
+    Error(String),
+    // Wakes up fill() while it waits for data
+    Unlock,
+}
+
 #[derive(Debug)]
 enum StreamingState {
     Stopped,
     Started {
         uri: Url,
-        response: Response,
+        receiver: Receiver<ReaderMessage>,
         seekable: bool,
+        caps: Option<GstRc<Caps>>,
         position: u64,
         size: Option<u64>,
         start: u64,
@@ -35,11 +56,40 @@ enum StreamingState {
     },
 }
 
+pub const DEFAULT_USER_AGENT: &'static str = "GStreamer rshttpsrc";
+
+#[derive(Debug)]
+struct UnlockState {
+    flushing: AtomicBool,
+    // Sender of the current reader thread's channel
+    sender: Mutex<Option<SyncSender<ReaderMessage>>>,
+}
+
+#[derive(Debug)]
+struct HttpSrcUnlock(Arc<UnlockState>);
+
+impl Unlock for HttpSrcUnlock {
+    fn unlock(&self) {
+        self.0.flushing.store(true, Ordering::SeqCst);
+
+        // If the queue is full, fill() is not waiting and will see the flag
+        if let Some(ref sender) = *self.0.sender.lock().unwrap() {
+            let _ = sender.try_send(ReaderMessage::Unlock);
+        }
+    }
+
+    fn unlock_stop(&self) {
+        self.0.flushing.store(false, Ordering::SeqCst);
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpSrc {
     streaming_state: StreamingState,
     logger: Logger,
     client: Client,
+    user_agent: String,
+    unlock: Arc<UnlockState>,
 }
 
 impl HttpSrc {
@@ -52,6 +102,11 @@ impl HttpSrc {
                                                     "Rust http sink"),
                                  o!()),
             client: Client::new().unwrap(),
+            user_agent: DEFAULT_USER_AGENT.into(),
+            unlock: Arc::new(UnlockState {
+                                 flushing: AtomicBool::new(false),
+                                 sender: Mutex::new(None),
+                             }),
         }
     }
 
@@ -66,6 +121,8 @@ impl HttpSrc {
                   -> Result<StreamingState, ErrorMessage> {
         let mut req = self.client.get(uri.clone());
 
+        req = req.header(UserAgent(self.user_agent.clone()));
+
         match (start != 0, stop) {
             (false, None) => (),
             (true, None) => req = req.header(Range::Bytes(vec![ByteRangeSpec::AllFrom(start)])),
@@ -104,6 +161,17 @@ impl HttpSrc {
 
         let seekable = size.is_some() && accept_byte_ranges;
 
+        // Only the media type is used, parameters like the charset are
+        // dropped
+        let caps = response
+            .headers()
+            .get()
+            .and_then(|&ContentType(ref mime)| {
+                let mime = mime.to_string();
+                let media_type = mime.split(';').next().unwrap().trim().to_lowercase();
+                caps_from_media_type(&media_type)
+            });
+
         let position = if let Some(&ContentRange(ContentRangeSpec::Bytes {
                                                      range: Some((range_start, _)), ..
                                                  })) = response.headers().get() {
@@ -119,16 +187,84 @@ impl HttpSrc {
 
         debug!(self.logger, "Request successful: {:?}", response);
 
+        // Reading happens in a separate thread so that fill() can be
+        // interrupted by unlock() while waiting for the server
+        let (sender, receiver) = mpsc::sync_channel(READ_QUEUE_SIZE);
+        *self.unlock.sender.lock().unwrap() = Some(sender.clone());
+        let logger = self.logger.clone();
+        thread::spawn(move || read_response(logger, response, sender));
+
         Ok(StreamingState::Started {
                uri: uri,
-               response: response,
+               receiver: receiver,
                seekable: seekable,
+               caps: caps,
                position: 0,
                size: size,
                start: start,
                stop: stop,
            })
     }
+
+    // Dropping the receiver makes the reader thread of the current request
+    // stop after its current read without waiting for it here. Its
+    // connection is closed once the thread is finished
+    fn stop_streaming(&mut self) {
+        *self.unlock.sender.lock().unwrap() = None;
+        self.streaming_state = StreamingState::Stopped;
+    }
+}
+
+fn read_response(logger: Logger, mut response: Response, sender: SyncSender<ReaderMessage>) {
+    loop {
+        let mut data = vec![0; READ_CHUNK_SIZE];
+        let msg = match response.read(&mut data) {
+            Ok(0) => ReaderMessage::Eos,
+            Ok(size) => {
+                data.truncate(size);
+                ReaderMessage::Data(data)
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                error!(logger, "Failed to read: {:?}", err);
+                ReaderMessage::Error(err.to_string())
+            }
+        };
+
+        let done = match msg {
+            ReaderMessage::Data(..) => false,
+            _ => true,
+        };
+
+        // Fails once the request was replaced or the source stopped
+        if sender.send(msg).is_err() || done {
+            break;
+        }
+    }
+}
+
+// Unknown media types, e.g. application/octet-stream, are left to typefinding
+fn caps_from_media_type(media_type: &str) -> Option<GstRc<Caps>> {
+    match media_type {
+        "audio/mpeg" => Some(Caps::new_simple("audio/mpeg", &[("mpegversion", 1.into())])),
+        "audio/aac" | "audio/aacp" => {
+            Some(Caps::new_simple("audio/mpeg",
+                                  &[("mpegversion", 4.into()),
+                                    ("stream-format", "adts".into())]))
+        }
+        "audio/flac" | "audio/x-flac" => Some(Caps::new_simple("audio/x-flac", &[])),
+        "audio/wav" | "audio/wave" | "audio/x-wav" => Some(Caps::new_simple("audio/x-wav", &[])),
+        "application/ogg" | "audio/ogg" | "video/ogg" => {
+            Some(Caps::new_simple("application/ogg", &[]))
+        }
+        "audio/webm" | "video/webm" => Some(Caps::new_simple("video/webm", &[])),
+        "video/x-flv" => Some(Caps::new_simple("video/x-flv", &[])),
+        "audio/mp4" | "video/mp4" => {
+            Some(Caps::new_simple("video/quicktime", &[("variant", "iso".into())]))
+        }
+        "video/mp2t" => Some(Caps::new_simple("video/mpegts", &[("systemstream", true.into())])),
+        _ => None,
+    }
 }
 
 fn validate_uri(uri: &Url) -> Result<(), UriError> {
@@ -145,6 +281,29 @@ impl Source for HttpSrc {
         Box::new(validate_uri)
     }
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(HttpSrcUnlock(self.unlock.clone())))
+    }
+
+    fn set_property(&mut self, name: &str, value: &Value) {
+        match name {
+            "user-agent" => {
+                self.user_agent = value
+                    .try_get::<&str>()
+                    .unwrap_or(DEFAULT_USER_AGENT)
+                    .into()
+            }
+            _ => (),
+        }
+    }
+
+    fn get_property(&self, name: &str) -> Option<Value> {
+        match name {
+            "user-agent" => Some(Value::from(self.user_agent.as_str())),
+            _ => None,
+        }
+    }
+
     fn is_seekable(&self) -> bool {
         match self.streaming_state {
             StreamingState::Started { seekable, .. } => seekable,
@@ -159,15 +318,22 @@ impl Source for HttpSrc {
         }
     }
 
+    fn get_caps(&self) -> Option<GstRc<Caps>> {
+        match self.streaming_state {
+            StreamingState::Started { ref caps, .. } => caps.clone(),
+            _ => None,
+        }
+    }
+
     fn start(&mut self, uri: Url) -> Result<(), ErrorMessage> {
-        self.streaming_state = StreamingState::Stopped;
+        self.stop_streaming();
         self.streaming_state = try!(self.do_request(uri, 0, None));
 
         Ok(())
     }
 
     fn stop(&mut self) -> Result<(), ErrorMessage> {
-        self.streaming_state = StreamingState::Stopped;
+        self.stop_streaming();
 
         Ok(())
     }
@@ -189,63 +355,75 @@ impl Source for HttpSrc {
             return Ok(());
         }
 
-        self.streaming_state = StreamingState::Stopped;
+        self.stop_streaming();
         self.streaming_state = try!(self.do_request(uri, start, stop));
 
         Ok(())
     }
 
-    fn fill(&mut self, offset: u64, _: u32, buffer: &mut Buffer) -> Result<(), FlowError> {
-        let logger = self.logger.clone();
+    fn create(&mut self, offset: u64, _: u32) -> Option<Result<GstRc<Buffer>, FlowError>> {
+        let unlock = &self.unlock;
 
-        let (response, position) = match self.streaming_state {
+        let (receiver, position) = match self.streaming_state {
             StreamingState::Started {
-                ref mut response,
+                ref receiver,
                 ref mut position,
                 ..
-            } => (response, position),
+            } => (receiver, position),
             StreamingState::Stopped => {
-                return Err(FlowError::Error(error_msg!(SourceError::Failure, ["Not started yet"])));
+                return Some(Err(FlowError::Error(error_msg!(SourceError::Failure,
+                                                            ["Not started yet"]))));
             }
         };
 
         if *position != offset {
-            return Err(FlowError::Error(error_msg!(SourceError::SeekFailed,
-                                                   ["Got unexpected offset {}, expected {}",
-                                                    offset,
-                                                    position])));
+            return Some(Err(FlowError::Error(error_msg!(SourceError::SeekFailed,
+                                                        ["Got unexpected offset {}, expected {}",
+                                                         offset,
+                                                         position]))));
         }
 
-        let size = {
-            let mut map = match buffer.map_readwrite() {
-                None => {
-                    return Err(FlowError::Error(error_msg!(SourceError::Failure,
-                                                           ["Failed to map buffer"])));
-                }
-                Some(map) => map,
-            };
+        if unlock.flushing.load(Ordering::SeqCst) {
+            return Some(Err(FlowError::Flushing));
+        }
 
-            let data = map.as_mut_slice();
+        // The chunks from the reader thread are passed downstream as is
+        let data = loop {
+            match receiver.recv() {
+                Ok(ReaderMessage::Data(data)) => break data,
+                Ok(ReaderMessage::Eos) |
+                Err(..) => return Some(Err(FlowError::Eos)),
+                Ok(ReaderMessage::Error(err)) => {
+                    return Some(Err(FlowError::Error(error_msg!(SourceError::ReadFailed,
+                                                                ["Failed to read at {}: {}",
+                                                                 offset,
+                                                                 err]))));
+                }
+                // Left over from an earlier unlock() if not flushing anymore
+                Ok(ReaderMessage::Unlock) => {
+                    if unlock.flushing.load(Ordering::SeqCst) {
+                        return Some(Err(FlowError::Flushing));
+                    }
+                }
+            }
+        };
 
-            try!(response
-                     .read(data)
-                     .or_else(|err| {
-                                  error!(logger, "Failed to read: {:?}", err);
-                                  Err(FlowError::Error(error_msg!(SourceError::ReadFailed,
-                                                                  ["Failed to read at {}: {}",
-                                                                   offset,
-                                                                   err.to_string()])))
-                              }))
+        let size = data.len() as u64;
+        let mut buffer = match Buffer::from_vec(data) {
+            Some(buffer) => buffer,
+            None => {
+                return Some(Err(FlowError::Error(error_msg!(SourceError::Failure,
+                                                            ["Failed to wrap buffer"]))));
+            }
         };
 
-        if size == 0 {
-            return Err(FlowError::Eos);
+        {
+            let buffer = buffer.get_mut().unwrap();
+            buffer.set_offset(Some(offset));
+            buffer.set_offset_end(Some(offset + size));
         }
+        *position += size;
 
-        *position += size as u64;
-
-        buffer.set_size(size);
-
-        Ok(())
+        Some(Ok(buffer))
     }
 }
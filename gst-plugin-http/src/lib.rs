@@ -16,11 +16,13 @@ extern crate reqwest;
 extern crate slog;
 
 use gst_plugin::plugin::*;
+use gst_plugin::caps::*;
 use gst_plugin::source::*;
+use gst_plugin::properties::*;
 
 mod httpsrc;
 
-use httpsrc::HttpSrc;
+use httpsrc::{HttpSrc, DEFAULT_USER_AGENT};
 
 fn plugin_init(plugin: &Plugin) -> bool {
     source_register(plugin,
@@ -33,7 +35,21 @@ fn plugin_init(plugin: &Plugin) -> bool {
                         rank: 256 + 100,
                         create_instance: HttpSrc::new_boxed,
                         protocols: vec!["http".into(), "https".into()],
+                        caps: Caps::new_any(),
                         push_only: true,
+                        blocksize: None,
+                        live: false,
+                        properties: vec![Property {
+                                             name: "user-agent".into(),
+                                             nick: "User-Agent".into(),
+                                             blurb: "Value of the User-Agent HTTP request header"
+                                                 .into(),
+                                             property_type:
+                                                 PropertyType::String(Some(DEFAULT_USER_AGENT
+                                                                               .into())),
+                                             mutability: PropertyMutability::ReadWrite,
+                                         }],
+                        signals: Vec::new(),
                     });
 
     true
@@ -0,0 +1,234 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::iter::FromIterator;
+
+use miniobject::*;
+use buffer::*;
+
+use gst;
+
+#[repr(C)]
+pub struct BufferList(gst::GstBufferList);
+
+unsafe impl MiniObject for BufferList {
+    type PtrType = gst::GstBufferList;
+}
+
+impl BufferList {
+    pub fn new() -> GstRc<Self> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_buffer_list_new()) }
+    }
+
+    pub fn new_sized(size: usize) -> GstRc<Self> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_buffer_list_new_sized(size as u32)) }
+    }
+
+    pub fn insert(&mut self, idx: u32, buffer: GstRc<Buffer>) {
+        assert!(idx as usize <= self.len());
+
+        unsafe {
+            gst::gst_buffer_list_insert(self.as_mut_ptr(), idx as i32, buffer.into_ptr());
+        }
+    }
+
+    pub fn add(&mut self, buffer: GstRc<Buffer>) {
+        unsafe {
+            gst::gst_buffer_list_insert(self.as_mut_ptr(), -1, buffer.into_ptr());
+        }
+    }
+
+    pub fn remove(&mut self, idx: u32, len: u32) {
+        assert!(idx as usize + len as usize <= self.len());
+
+        unsafe {
+            gst::gst_buffer_list_remove(self.as_mut_ptr(), idx, len);
+        }
+    }
+
+    pub fn get(&self, idx: u32) -> Option<&Buffer> {
+        unsafe {
+            let ptr = gst::gst_buffer_list_get(self.as_mut_ptr(), idx);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Buffer::from_ptr(ptr))
+            }
+        }
+    }
+
+    pub fn get_owned(&self, idx: u32) -> Option<GstRc<Buffer>> {
+        self.get(idx)
+            .map(|buffer| unsafe { GstRc::from_unowned_ptr(buffer.as_ptr()) })
+    }
+
+    // New list with copies of all buffers instead of references
+    pub fn copy_deep(&self) -> GstRc<Self> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_buffer_list_copy_deep(self.as_ptr())) }
+    }
+
+    // Total size of all buffers
+    pub fn get_size(&self) -> usize {
+        self.iter().map(|buffer| buffer.get_size()).sum()
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { gst::gst_buffer_list_length(self.as_mut_ptr()) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter::new(self)
+    }
+}
+
+impl fmt::Debug for BufferList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl ToOwned for BufferList {
+    type Owned = GstRc<BufferList>;
+
+    fn to_owned(&self) -> GstRc<BufferList> {
+        unsafe { GstRc::from_unowned_ptr(self.as_ptr()) }
+    }
+}
+
+impl FromIterator<GstRc<Buffer>> for GstRc<BufferList> {
+    fn from_iter<T: IntoIterator<Item = GstRc<Buffer>>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let mut list = BufferList::new_sized(iter.size_hint().0);
+
+        {
+            let list = list.get_mut().unwrap();
+            for buffer in iter {
+                list.add(buffer);
+            }
+        }
+
+        list
+    }
+}
+
+unsafe impl Sync for BufferList {}
+unsafe impl Send for BufferList {}
+
+pub struct Iter<'a> {
+    list: &'a BufferList,
+    idx: u32,
+    size: u32,
+}
+
+impl<'a> Iter<'a> {
+    fn new(list: &'a BufferList) -> Iter<'a> {
+        Iter {
+            list: list,
+            idx: 0,
+            size: list.len() as u32,
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Buffer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.size {
+            return None;
+        }
+
+        let item = self.list.get(self.idx);
+        self.idx += 1;
+
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.idx == self.size {
+            return (0, Some(0));
+        }
+
+        let remaining = (self.size - self.idx) as usize;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.idx == self.size {
+            return None;
+        }
+
+        self.size -= 1;
+        self.list.get(self.size)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn init() {
+        unsafe {
+            gst::gst_init(ptr::null_mut(), ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_iter() {
+        init();
+
+        let mut list = BufferList::new();
+        {
+            let list = list.get_mut().unwrap();
+            list.add(Buffer::from_vec(vec![1, 2]).unwrap());
+            list.add(Buffer::from_vec(vec![3, 4, 5]).unwrap());
+        }
+
+        assert_eq!(list.len(), 2);
+        let sizes = list.iter().map(|b| b.get_size()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![2, 3]);
+        let sizes = list.iter().rev().map(|b| b.get_size()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![3, 2]);
+    }
+
+    #[test]
+    fn test_modify() {
+        init();
+
+        let mut list = (0..4)
+            .map(|i| Buffer::new_with_size(i + 1).unwrap())
+            .collect::<GstRc<BufferList>>();
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.get_size(), 10);
+
+        {
+            let list = list.get_mut().unwrap();
+            list.remove(1, 2);
+            list.insert(0, Buffer::new_with_size(5).unwrap());
+        }
+
+        let sizes = list.iter().map(|b| b.get_size()).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![5, 1, 4]);
+        assert_eq!(list.get(3), None);
+
+        let copy = list.copy_deep();
+        assert_eq!(copy.len(), 3);
+        assert!(unsafe { copy.get(0).unwrap().as_ptr() != list.get(0).unwrap().as_ptr() });
+    }
+}
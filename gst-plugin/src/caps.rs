@@ -9,9 +9,11 @@
 use std::ffi::CString;
 use std::ffi::CStr;
 use std::fmt;
+use std::ptr;
 use value::*;
 use miniobject::*;
 use structure::*;
+use capsfeatures::{CapsFeatures, OwnedCapsFeatures};
 
 use glib;
 use gst;
@@ -19,6 +21,23 @@ use gst;
 #[repr(C)]
 pub struct Caps(gst::GstCaps);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapsIntersectMode {
+    // Alternates between both caps, keeping the order of neither
+    ZigZag,
+    // Keeps the order of the first caps
+    First,
+}
+
+impl CapsIntersectMode {
+    fn to_native(&self) -> gst::GstCapsIntersectMode {
+        match *self {
+            CapsIntersectMode::ZigZag => gst::GST_CAPS_INTERSECT_ZIG_ZAG,
+            CapsIntersectMode::First => gst::GST_CAPS_INTERSECT_FIRST,
+        }
+    }
+}
+
 unsafe impl MiniObject for Caps {
     type PtrType = gst::GstCaps;
 }
@@ -82,6 +101,10 @@ impl Caps {
     }
 
     pub fn get_structure(&self, idx: u32) -> Option<&Structure> {
+        if idx >= self.get_size() {
+            return None;
+        }
+
         unsafe {
             let structure = gst::gst_caps_get_structure(self.as_ptr(), idx);
             if structure.is_null() {
@@ -93,6 +116,10 @@ impl Caps {
     }
 
     pub fn get_mut_structure(&mut self, idx: u32) -> Option<&mut Structure> {
+        if idx >= self.get_size() {
+            return None;
+        }
+
         unsafe {
             let structure = gst::gst_caps_get_structure(self.as_ptr(), idx);
             if structure.is_null() {
@@ -103,7 +130,174 @@ impl Caps {
         }
     }
 
-    // TODO: All kinds of caps operations
+    pub fn get_features(&self, idx: u32) -> Option<&CapsFeatures> {
+        if idx >= self.get_size() {
+            return None;
+        }
+
+        unsafe {
+            let features = gst::gst_caps_get_features(self.as_ptr(), idx);
+            if features.is_null() {
+                return None;
+            }
+
+            Some(CapsFeatures::from_borrowed_ptr(features as *const gst::GstCapsFeatures))
+        }
+    }
+
+    pub fn get_mut_features(&mut self, idx: u32) -> Option<&mut CapsFeatures> {
+        if idx >= self.get_size() {
+            return None;
+        }
+
+        unsafe {
+            let features = gst::gst_caps_get_features(self.as_ptr(), idx);
+            if features.is_null() {
+                return None;
+            }
+
+            Some(CapsFeatures::from_borrowed_mut_ptr(features as *mut gst::GstCapsFeatures))
+        }
+    }
+
+    // None resets the features to the default system memory features
+    pub fn set_features(&mut self, idx: u32, features: Option<OwnedCapsFeatures>) {
+        assert!(idx < self.get_size());
+
+        unsafe {
+            gst::gst_caps_set_features(self.as_mut_ptr(),
+                                       idx,
+                                       features
+                                           .map(|f| f.into_ptr())
+                                           .unwrap_or(ptr::null_mut()));
+        }
+    }
+
+    pub fn get_size(&self) -> u32 {
+        unsafe { gst::gst_caps_get_size(self.as_ptr()) }
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter {
+            caps: self,
+            idx: 0,
+            n_structures: self.get_size(),
+        }
+    }
+
+    pub fn iter_with_features(&self) -> IterFeatures {
+        IterFeatures { iter: self.iter() }
+    }
+
+    pub fn append_structure(&mut self, structure: OwnedStructure) {
+        unsafe {
+            gst::gst_caps_append_structure(self.as_mut_ptr(), structure.into_ptr());
+        }
+    }
+
+    pub fn append_structure_full(&mut self,
+                                 structure: OwnedStructure,
+                                 features: Option<OwnedCapsFeatures>) {
+        unsafe {
+            gst::gst_caps_append_structure_full(self.as_mut_ptr(),
+                                                structure.into_ptr(),
+                                                features
+                                                    .map(|f| f.into_ptr())
+                                                    .unwrap_or(ptr::null_mut()));
+        }
+    }
+
+    pub fn remove_structure(&mut self, idx: u32) {
+        assert!(idx < self.get_size());
+
+        unsafe {
+            gst::gst_caps_remove_structure(self.as_mut_ptr(), idx);
+        }
+    }
+
+    // Appends all structures of other, without removing duplicates
+    pub fn append(&mut self, other: GstRc<Caps>) {
+        unsafe {
+            gst::gst_caps_append(self.as_mut_ptr(), other.into_ptr());
+        }
+    }
+
+    // Only appends the structures of other that are not already expressed
+    // by caps
+    pub fn merge(caps: GstRc<Caps>, other: GstRc<Caps>) -> GstRc<Caps> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_caps_merge(caps.into_ptr(), other.into_ptr())) }
+    }
+
+    pub fn merge_structure(caps: GstRc<Caps>, structure: OwnedStructure) -> GstRc<Caps> {
+        unsafe {
+            GstRc::from_owned_ptr(gst::gst_caps_merge_structure(caps.into_ptr(),
+                                                                structure.into_ptr()))
+        }
+    }
+
+    pub fn intersect(&self, other: &Caps) -> GstRc<Caps> {
+        self.intersect_with_mode(other, CapsIntersectMode::ZigZag)
+    }
+
+    pub fn intersect_with_mode(&self, other: &Caps, mode: CapsIntersectMode) -> GstRc<Caps> {
+        unsafe {
+            GstRc::from_owned_ptr(gst::gst_caps_intersect_full(self.as_mut_ptr(),
+                                                               other.as_mut_ptr(),
+                                                               mode.to_native()))
+        }
+    }
+
+    pub fn can_intersect(&self, other: &Caps) -> bool {
+        unsafe { gst::gst_caps_can_intersect(self.as_ptr(), other.as_ptr()) == glib::GTRUE }
+    }
+
+    pub fn is_subset(&self, superset: &Caps) -> bool {
+        unsafe { gst::gst_caps_is_subset(self.as_ptr(), superset.as_ptr()) == glib::GTRUE }
+    }
+
+    pub fn is_subset_structure(&self, structure: &Structure) -> bool {
+        unsafe {
+            gst::gst_caps_is_subset_structure(self.as_ptr(),
+                                              structure as *const Structure as
+                                              *const gst::GstStructure) ==
+            glib::GTRUE
+        }
+    }
+
+    pub fn is_always_compatible(&self, other: &Caps) -> bool {
+        unsafe { gst::gst_caps_is_always_compatible(self.as_ptr(), other.as_ptr()) == glib::GTRUE }
+    }
+
+    pub fn is_any(&self) -> bool {
+        unsafe { gst::gst_caps_is_any(self.as_ptr()) == glib::GTRUE }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        unsafe { gst::gst_caps_is_empty(self.as_ptr()) == glib::GTRUE }
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        unsafe { gst::gst_caps_is_fixed(self.as_ptr()) == glib::GTRUE }
+    }
+
+    // Keeps only the first structure and fixates all its fields to the
+    // nearest fixed value
+    pub fn fixate(caps: GstRc<Caps>) -> GstRc<Caps> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_caps_fixate(caps.into_ptr())) }
+    }
+
+    pub fn simplify(caps: GstRc<Caps>) -> GstRc<Caps> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_caps_simplify(caps.into_ptr())) }
+    }
+
+    pub fn truncate(caps: GstRc<Caps>) -> GstRc<Caps> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_caps_truncate(caps.into_ptr())) }
+    }
+
+    // Expands all lists into separate structures
+    pub fn normalize(caps: GstRc<Caps>) -> GstRc<Caps> {
+        unsafe { GstRc::from_owned_ptr(gst::gst_caps_normalize(caps.into_ptr())) }
+    }
 }
 
 impl fmt::Debug for Caps {
@@ -131,6 +325,71 @@ impl ToOwned for Caps {
 unsafe impl Sync for Caps {}
 unsafe impl Send for Caps {}
 
+pub struct Iter<'a> {
+    caps: &'a Caps,
+    idx: u32,
+    n_structures: u32,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Structure;
+
+    fn next(&mut self) -> Option<&'a Structure> {
+        if self.idx >= self.n_structures {
+            return None;
+        }
+
+        let structure = self.caps.get_structure(self.idx);
+        self.idx += 1;
+
+        structure
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.idx == self.n_structures {
+            return (0, Some(0));
+        }
+
+        let remaining = (self.n_structures - self.idx) as usize;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.idx == self.n_structures {
+            return None;
+        }
+
+        self.n_structures -= 1;
+        self.caps.get_structure(self.n_structures)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+pub struct IterFeatures<'a> {
+    iter: Iter<'a>,
+}
+
+impl<'a> Iterator for IterFeatures<'a> {
+    type Item = (&'a Structure, &'a CapsFeatures);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.iter.idx;
+        self.iter
+            .next()
+            .map(|s| (s, self.iter.caps.get_features(idx).unwrap()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for IterFeatures<'a> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,4 +425,65 @@ mod tests {
                                          ("array", vec![1.into(), 2.into()].into())])
                            .as_ref());
     }
+
+    #[test]
+    fn test_operations() {
+        init();
+
+        let caps = Caps::from_string("foo/bar, int=(int)[1, 10]; foo/baz").unwrap();
+        assert_eq!(caps.get_size(), 2);
+        assert!(!caps.is_fixed());
+        assert!(!caps.is_any());
+        assert!(!caps.is_empty());
+
+        let filter = Caps::from_string("foo/bar, int=(int){5, 20}").unwrap();
+        assert!(caps.can_intersect(&filter));
+        let intersection = caps.intersect_with_mode(&filter, CapsIntersectMode::First);
+        assert_eq!(intersection.to_string(), "foo/bar, int=(int)5");
+        assert!(intersection.is_fixed());
+        assert!(intersection.is_subset(&caps));
+        assert!(!caps.is_subset(&intersection));
+
+        let names = caps.iter().map(|s| s.get_name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["foo/bar", "foo/baz"]);
+
+        let fixated = Caps::fixate(caps.clone());
+        assert_eq!(fixated.to_string(), "foo/bar, int=(int)1");
+
+        let mut caps = Caps::merge(caps, Caps::from_string("foo/baz; foo/qux").unwrap());
+        assert_eq!(caps.get_size(), 3);
+        {
+            let caps = caps.get_mut().unwrap();
+            caps.remove_structure(0);
+            caps.append_structure(OwnedStructure::new_empty("foo/quux"));
+        }
+        let names = caps.iter().rev().map(|s| s.get_name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["foo/quux", "foo/qux", "foo/baz"]);
+    }
+
+    #[test]
+    fn test_features() {
+        init();
+
+        let mut caps = Caps::new_empty();
+        {
+            let caps = caps.get_mut().unwrap();
+            caps.append_structure_full(OwnedStructure::new_empty("foo/bar"),
+                                       Some(OwnedCapsFeatures::new(&["memory:Foo"])));
+            caps.append_structure(OwnedStructure::new_empty("foo/bar"));
+        }
+        assert_eq!(caps.to_string(), "foo/bar(memory:Foo); foo/bar");
+
+        let features = caps.iter_with_features()
+            .map(|(_, f)| f.to_owned())
+            .collect::<Vec<_>>();
+        assert!(features[0].contains("memory:Foo"));
+        assert!(features[1].contains(::capsfeatures::CAPS_FEATURE_MEMORY_SYSTEM_MEMORY));
+        assert!(caps.get_features(2).is_none());
+        assert!(caps.get_structure(2).is_none());
+
+        caps.get_mut().unwrap().set_features(0, None);
+        assert_eq!(caps.to_string(), "foo/bar; foo/bar");
+        assert_eq!(Caps::simplify(caps).to_string(), "foo/bar");
+    }
 }
@@ -0,0 +1,263 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::mem;
+use std::ffi::{CStr, CString};
+use std::ops::{Deref, DerefMut};
+use std::borrow::{Borrow, ToOwned, BorrowMut};
+use std::marker::PhantomData;
+
+use glib;
+use gst;
+
+pub const CAPS_FEATURE_MEMORY_SYSTEM_MEMORY: &'static str = "memory:SystemMemory";
+
+pub struct OwnedCapsFeatures(*mut CapsFeatures, PhantomData<CapsFeatures>);
+
+impl OwnedCapsFeatures {
+    pub fn new_empty() -> OwnedCapsFeatures {
+        OwnedCapsFeatures(unsafe { gst::gst_caps_features_new_empty() as *mut CapsFeatures },
+                          PhantomData)
+    }
+
+    pub fn new_any() -> OwnedCapsFeatures {
+        OwnedCapsFeatures(unsafe { gst::gst_caps_features_new_any() as *mut CapsFeatures },
+                          PhantomData)
+    }
+
+    pub fn new(features: &[&str]) -> OwnedCapsFeatures {
+        let mut f = OwnedCapsFeatures::new_empty();
+
+        for feature in features {
+            f.add(feature);
+        }
+
+        f
+    }
+
+    pub fn from_string(s: &str) -> Option<OwnedCapsFeatures> {
+        unsafe {
+            let cstr = CString::new(s).unwrap();
+            let features = gst::gst_caps_features_from_string(cstr.as_ptr());
+            if features.is_null() {
+                None
+            } else {
+                Some(OwnedCapsFeatures(features as *mut CapsFeatures, PhantomData))
+            }
+        }
+    }
+
+    pub unsafe fn into_ptr(self) -> *mut gst::GstCapsFeatures {
+        let ptr = self.0 as *mut gst::GstCapsFeatures;
+        mem::forget(self);
+
+        ptr
+    }
+}
+
+impl Deref for OwnedCapsFeatures {
+    type Target = CapsFeatures;
+
+    fn deref(&self) -> &CapsFeatures {
+        unsafe { &*self.0 }
+    }
+}
+
+impl DerefMut for OwnedCapsFeatures {
+    fn deref_mut(&mut self) -> &mut CapsFeatures {
+        unsafe { &mut *self.0 }
+    }
+}
+
+impl AsRef<CapsFeatures> for OwnedCapsFeatures {
+    fn as_ref(&self) -> &CapsFeatures {
+        self.deref()
+    }
+}
+
+impl AsMut<CapsFeatures> for OwnedCapsFeatures {
+    fn as_mut(&mut self) -> &mut CapsFeatures {
+        self.deref_mut()
+    }
+}
+
+impl Clone for OwnedCapsFeatures {
+    fn clone(&self) -> Self {
+        self.as_ref().to_owned()
+    }
+}
+
+impl Drop for OwnedCapsFeatures {
+    fn drop(&mut self) {
+        unsafe { gst::gst_caps_features_free(&mut (*self.0).0) }
+    }
+}
+
+impl fmt::Debug for OwnedCapsFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string())
+    }
+}
+
+impl PartialEq for OwnedCapsFeatures {
+    fn eq(&self, other: &OwnedCapsFeatures) -> bool {
+        self.as_ref().eq(other)
+    }
+}
+
+impl PartialEq<CapsFeatures> for OwnedCapsFeatures {
+    fn eq(&self, other: &CapsFeatures) -> bool {
+        self.as_ref().eq(other)
+    }
+}
+
+impl Eq for OwnedCapsFeatures {}
+
+impl Borrow<CapsFeatures> for OwnedCapsFeatures {
+    fn borrow(&self) -> &CapsFeatures {
+        unsafe { &*self.0 }
+    }
+}
+
+impl BorrowMut<CapsFeatures> for OwnedCapsFeatures {
+    fn borrow_mut(&mut self) -> &mut CapsFeatures {
+        unsafe { &mut *self.0 }
+    }
+}
+
+impl ToOwned for CapsFeatures {
+    type Owned = OwnedCapsFeatures;
+
+    fn to_owned(&self) -> OwnedCapsFeatures {
+        OwnedCapsFeatures(unsafe { gst::gst_caps_features_copy(&self.0) as *mut CapsFeatures },
+                          PhantomData)
+    }
+}
+
+unsafe impl Send for OwnedCapsFeatures {}
+unsafe impl Sync for OwnedCapsFeatures {}
+
+#[repr(C)]
+pub struct CapsFeatures(gst::GstCapsFeatures);
+
+impl CapsFeatures {
+    pub unsafe fn from_borrowed_ptr<'a>(ptr: *const gst::GstCapsFeatures) -> &'a CapsFeatures {
+        assert!(!ptr.is_null());
+
+        &*(ptr as *mut CapsFeatures)
+    }
+
+    pub unsafe fn from_borrowed_mut_ptr<'a>(ptr: *mut gst::GstCapsFeatures)
+                                            -> &'a mut CapsFeatures {
+        assert!(!ptr.is_null());
+
+        &mut *(ptr as *mut CapsFeatures)
+    }
+
+    pub fn to_string(&self) -> String {
+        unsafe {
+            let ptr = gst::gst_caps_features_to_string(&self.0);
+            let s = CStr::from_ptr(ptr).to_str().unwrap().into();
+            glib::g_free(ptr as glib::gpointer);
+
+            s
+        }
+    }
+
+    pub fn is_any(&self) -> bool {
+        unsafe { gst::gst_caps_features_is_any(&self.0) == glib::GTRUE }
+    }
+
+    pub fn contains(&self, feature: &str) -> bool {
+        unsafe {
+            let cstr = CString::new(feature).unwrap();
+            gst::gst_caps_features_contains(&self.0, cstr.as_ptr()) == glib::GTRUE
+        }
+    }
+
+    pub fn add(&mut self, feature: &str) {
+        unsafe {
+            let cstr = CString::new(feature).unwrap();
+            gst::gst_caps_features_add(&mut self.0, cstr.as_ptr());
+        }
+    }
+
+    pub fn remove(&mut self, feature: &str) {
+        unsafe {
+            let cstr = CString::new(feature).unwrap();
+            gst::gst_caps_features_remove(&mut self.0, cstr.as_ptr());
+        }
+    }
+
+    pub fn get_size(&self) -> u32 {
+        unsafe { gst::gst_caps_features_get_size(&self.0) }
+    }
+
+    pub fn get_nth(&self, idx: u32) -> Option<&str> {
+        unsafe {
+            let feature = gst::gst_caps_features_get_nth(&self.0, idx);
+            if feature.is_null() {
+                return None;
+            }
+
+            Some(CStr::from_ptr(feature).to_str().unwrap())
+        }
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter {
+            features: self,
+            idx: 0,
+            n_features: self.get_size(),
+        }
+    }
+}
+
+impl fmt::Debug for CapsFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string())
+    }
+}
+
+impl PartialEq for CapsFeatures {
+    fn eq(&self, other: &CapsFeatures) -> bool {
+        (unsafe { gst::gst_caps_features_is_equal(&self.0, &other.0) } == glib::GTRUE)
+    }
+}
+
+impl Eq for CapsFeatures {}
+
+pub struct Iter<'a> {
+    features: &'a CapsFeatures,
+    idx: u32,
+    n_features: u32,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.idx >= self.n_features {
+            return None;
+        }
+
+        let feature = self.features.get_nth(self.idx);
+        self.idx += 1;
+
+        feature
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.n_features - self.idx) as usize;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
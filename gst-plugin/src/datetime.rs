@@ -0,0 +1,206 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::mem;
+use std::ffi::{CStr, CString};
+
+use value::*;
+
+use glib;
+use gst;
+
+// Date and time with possibly unset fields, e.g. only the year.
+// Time zone offsets are given in hours east of UTC
+pub struct DateTime(*mut gst::GstDateTime);
+
+impl DateTime {
+    pub fn new(tz_offset: f32,
+               year: i32,
+               month: i32,
+               day: i32,
+               hour: i32,
+               minute: i32,
+               seconds: f64)
+               -> Option<DateTime> {
+        unsafe {
+            DateTime::from_owned_ptr(gst::gst_date_time_new(tz_offset,
+                                                            year,
+                                                            month,
+                                                            day,
+                                                            hour,
+                                                            minute,
+                                                            seconds))
+        }
+    }
+
+    pub fn new_y(year: i32) -> Option<DateTime> {
+        unsafe { DateTime::from_owned_ptr(gst::gst_date_time_new_y(year)) }
+    }
+
+    pub fn new_ym(year: i32, month: i32) -> Option<DateTime> {
+        unsafe { DateTime::from_owned_ptr(gst::gst_date_time_new_ym(year, month)) }
+    }
+
+    pub fn new_ymd(year: i32, month: i32, day: i32) -> Option<DateTime> {
+        unsafe { DateTime::from_owned_ptr(gst::gst_date_time_new_ymd(year, month, day)) }
+    }
+
+    pub fn new_now_utc() -> DateTime {
+        unsafe { DateTime::from_owned_ptr(gst::gst_date_time_new_now_utc()).unwrap() }
+    }
+
+    pub fn from_iso8601_string(s: &str) -> Option<DateTime> {
+        unsafe {
+            let cstr = CString::new(s).unwrap();
+            DateTime::from_owned_ptr(gst::gst_date_time_new_from_iso8601_string(cstr.as_ptr()))
+        }
+    }
+
+    pub unsafe fn from_owned_ptr(ptr: *mut gst::GstDateTime) -> Option<DateTime> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(DateTime(ptr))
+        }
+    }
+
+    pub unsafe fn from_unowned_ptr(ptr: *mut gst::GstDateTime) -> Option<DateTime> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(DateTime(gst::gst_date_time_ref(ptr)))
+        }
+    }
+
+    pub unsafe fn as_ptr(&self) -> *mut gst::GstDateTime {
+        self.0
+    }
+
+    pub unsafe fn into_ptr(self) -> *mut gst::GstDateTime {
+        let ptr = self.0;
+        mem::forget(self);
+
+        ptr
+    }
+
+    pub fn to_iso8601_string(&self) -> String {
+        unsafe {
+            let ptr = gst::gst_date_time_to_iso8601_string(self.0);
+            let s = CStr::from_ptr(ptr).to_str().unwrap().into();
+            glib::g_free(ptr as glib::gpointer);
+
+            s
+        }
+    }
+
+    pub fn get_year(&self) -> i32 {
+        unsafe { gst::gst_date_time_get_year(self.0) }
+    }
+
+    pub fn get_month(&self) -> Option<i32> {
+        if self.has_month() {
+            Some(unsafe { gst::gst_date_time_get_month(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_day(&self) -> Option<i32> {
+        if self.has_day() {
+            Some(unsafe { gst::gst_date_time_get_day(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_hour(&self) -> Option<i32> {
+        if self.has_time() {
+            Some(unsafe { gst::gst_date_time_get_hour(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_minute(&self) -> Option<i32> {
+        if self.has_time() {
+            Some(unsafe { gst::gst_date_time_get_minute(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_second(&self) -> Option<i32> {
+        if self.has_second() {
+            Some(unsafe { gst::gst_date_time_get_second(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_microsecond(&self) -> Option<i32> {
+        if self.has_second() {
+            Some(unsafe { gst::gst_date_time_get_microsecond(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_time_zone_offset(&self) -> Option<f32> {
+        if self.has_time() {
+            Some(unsafe { gst::gst_date_time_get_time_zone_offset(self.0) })
+        } else {
+            None
+        }
+    }
+
+    pub fn has_month(&self) -> bool {
+        unsafe { gst::gst_date_time_has_month(self.0) == glib::GTRUE }
+    }
+
+    pub fn has_day(&self) -> bool {
+        unsafe { gst::gst_date_time_has_day(self.0) == glib::GTRUE }
+    }
+
+    pub fn has_time(&self) -> bool {
+        unsafe { gst::gst_date_time_has_time(self.0) == glib::GTRUE }
+    }
+
+    pub fn has_second(&self) -> bool {
+        unsafe { gst::gst_date_time_has_second(self.0) == glib::GTRUE }
+    }
+}
+
+impl Clone for DateTime {
+    fn clone(&self) -> Self {
+        unsafe { DateTime(gst::gst_date_time_ref(self.0)) }
+    }
+}
+
+impl Drop for DateTime {
+    fn drop(&mut self) {
+        unsafe {
+            gst::gst_date_time_unref(self.0);
+        }
+    }
+}
+
+impl fmt::Debug for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_iso8601_string())
+    }
+}
+
+impl PartialEq for DateTime {
+    fn eq(&self, other: &DateTime) -> bool {
+        Value::from(self) == Value::from(other)
+    }
+}
+
+unsafe impl Send for DateTime {}
+unsafe impl Sync for DateTime {}
@@ -7,15 +7,17 @@
 // except according to those terms.
 
 use libc::c_char;
-use std::os::raw::c_void;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::mem;
+use std::fmt;
 
 use std::panic::{self, AssertUnwindSafe};
 
-use std::sync::Mutex;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use std::u32;
 use std::u64;
 
 use slog::Logger;
@@ -23,109 +25,1354 @@ use slog::Logger;
 use utils::*;
 use error::*;
 use buffer::*;
+use bufferlist::BufferList;
 use miniobject::*;
 use log::*;
 use caps::Caps;
 use plugin::Plugin;
+use streams::*;
+use tags::TagList;
+use value::Value;
+use properties::*;
+use signals::*;
 
 use glib;
+use gobject;
 use gst;
+use gst_base;
+
+const PULL_BLOCKSIZE: u32 = 4096;
+
+#[derive(Debug)]
+pub enum DemuxerError {
+    StreamFailed,
+    UnknownStream,
+    DuplicateStream,
+    SeekFailed,
+}
+
+impl ToGError for DemuxerError {
+    fn to_gerror(&self) -> (u32, i32) {
+        match *self {
+            DemuxerError::StreamFailed => (gst_stream_error_domain(), 1),
+            DemuxerError::UnknownStream |
+            DemuxerError::DuplicateStream => (gst_stream_error_domain(), 9),
+            DemuxerError::SeekFailed => (gst_resource_error_domain(), 11),
+        }
+    }
+}
 
 pub type StreamIndex = u32;
 
-#[derive(Debug)]
-pub enum SeekResult {
-    TooEarly,
-    Ok(u64),
-    Eos,
-}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekResult {
+    TooEarly,
+    Ok(u64),
+    Eos,
+}
+
+#[derive(Debug)]
+pub enum HandleBufferResult {
+    NeedMoreData,
+    Again,
+    // Any data buffered by the implementation should be dropped, the next
+    // buffer passed to handle_buffer() starts at this offset
+    NeedDataFromOffset(u64),
+    StreamAdded(StreamIndex, Stream),
+    HaveAllStreams,
+    StreamChanged(StreamIndex, Stream),
+    StreamRemoved(StreamIndex),
+    // StreamsAdded(Vec<(StreamIndex, Stream)>), // Implies HaveAllStreams
+    StreamsChanged(Vec<(StreamIndex, Stream)>),
+    GlobalTags(GstRc<TagList>),
+    TagsForStream(StreamIndex, GstRc<TagList>),
+    // TODO need something to replace/add new streams
+    BufferForStream(StreamIndex, GstRc<Buffer>),
+    // Pushed downstream at once, e.g. for packetised formats
+    BufferListForStream(StreamIndex, GstRc<BufferList>),
+    Eos(Option<StreamIndex>),
+}
+
+pub trait Demuxer {
+    fn start(&mut self,
+             upstream_size: Option<u64>,
+             random_access: bool)
+             -> Result<(), ErrorMessage>;
+    fn stop(&mut self) -> Result<(), ErrorMessage>;
+
+    fn seek(&mut self, start: u64, stop: Option<u64>) -> Result<SeekResult, ErrorMessage>;
+
+    // Called for upstream BYTES segments, with the byte range that is going
+    // to follow. Most demuxers don't need to do anything here
+    fn handle_segment(&mut self,
+                      _start: u64,
+                      _stop: Option<u64>)
+                      -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn handle_buffer(&mut self,
+                     buffer: Option<GstRc<Buffer>>)
+                     -> Result<HandleBufferResult, FlowError>;
+    fn end_of_stream(&mut self) -> Result<(), ErrorMessage>;
+
+    // Called when the application selected a subset of the streams. Buffers
+    // for streams that are not selected are dropped by the base class
+    fn select_streams(&mut self, _streams: &[StreamIndex]) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn is_seekable(&self) -> bool;
+    fn get_position(&self) -> Option<u64>;
+    fn get_duration(&self) -> Option<u64>;
+
+    // Property changes are passed on once the demuxer is not busy anymore,
+    // e.g. after handle_buffer() returned. Values changed by the demuxer
+    // itself are only checked again after property changes and action
+    // signals
+    fn set_property(&mut self, _name: &str, _value: &Value) {}
+    fn get_property(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn handle_action_signal(&mut self, _name: &str, _args: &[Value]) -> Option<Value> {
+        None
+    }
+}
+
+struct SrcPad {
+    pad: *mut gst::GstPad,
+    stream: Stream,
+    selected: bool,
+}
+
+struct State {
+    upstream_size: Option<u64>,
+    offset: u64,
+    offset_seek_seqnum: Option<u32>,
+    skip_to: Option<u64>,
+    // The implementation has to be told about the current offset before
+    // the next buffer
+    resync_pending: bool,
+
+    srcpads: BTreeMap<StreamIndex, SrcPad>,
+    group_id: u32,
+    collection: Option<StreamCollection>,
+    global_tags: Option<GstRc<TagList>>,
+
+    upstream_segment: gst::GstSegment,
+
+    segment: gst::GstSegment,
+    segment_seqnum: u32,
+    segment_pending: bool,
+    segment_start_unknown: bool,
+
+    // Push mode seek that waits for upstream to accept the byte seek
+    seek_seqnum: Option<u32>,
+    seek_segment: gst::GstSegment,
+
+    flow_combiner: *mut gst_base::GstFlowCombiner,
+}
+
+impl State {
+    fn new() -> State {
+        unsafe {
+            let mut upstream_segment = mem::zeroed();
+            gst::gst_segment_init(&mut upstream_segment, gst::GST_FORMAT_BYTES);
+            let mut segment = mem::zeroed();
+            gst::gst_segment_init(&mut segment, gst::GST_FORMAT_TIME);
+            let mut seek_segment = mem::zeroed();
+            gst::gst_segment_init(&mut seek_segment, gst::GST_FORMAT_TIME);
+
+            State {
+                upstream_size: None,
+                offset: 0,
+                offset_seek_seqnum: None,
+                skip_to: None,
+                resync_pending: false,
+                srcpads: BTreeMap::new(),
+                group_id: 0,
+                collection: None,
+                global_tags: None,
+                upstream_segment: upstream_segment,
+                segment: segment,
+                segment_seqnum: 0,
+                segment_pending: false,
+                segment_start_unknown: false,
+                seek_seqnum: None,
+                seek_segment: seek_segment,
+                flow_combiner: gst_base::gst_flow_combiner_new(),
+            }
+        }
+    }
+
+    // Makes the segment of a seek the output segment
+    fn commit_seek(&mut self, seqnum: u32) {
+        unsafe {
+            gst::gst_segment_copy_into(&self.seek_segment, &mut self.segment);
+            gst_base::gst_flow_combiner_reset(self.flow_combiner);
+        }
+        self.segment_seqnum = seqnum;
+        self.segment_start_unknown = false;
+        self.segment_pending = true;
+    }
+}
+
+pub struct DemuxerWrapper {
+    raw: *mut gst::GstElement,
+    logger: Logger,
+    sinkpad: *mut gst::GstPad,
+    state: Mutex<State>,
+    demuxer: Mutex<Box<Demuxer>>,
+    // Kept outside the demuxer so that properties can be used while it is
+    // busy handling data
+    properties: PropertyStore,
+    panicked: AtomicBool,
+}
+
+impl DemuxerWrapper {
+    fn new(raw: *mut gst::GstElement,
+           sinkpad: *mut gst::GstPad,
+           properties: &[Property],
+           demuxer: Box<Demuxer>)
+           -> DemuxerWrapper {
+        let properties = PropertyStore::new(properties, |name| demuxer.get_property(name));
+
+        DemuxerWrapper {
+            raw: raw,
+            logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
+                                                    "rsdemux",
+                                                    0,
+                                                    "Rust demuxer base class"),
+                                 o!()),
+            sinkpad: sinkpad,
+            state: Mutex::new(State::new()),
+            demuxer: Mutex::new(demuxer),
+            properties: properties,
+            panicked: AtomicBool::new(false),
+        }
+    }
+
+    fn prepare(&self) {
+        let state = &mut self.state.lock().unwrap();
+
+        state.offset = 0;
+        state.offset_seek_seqnum = None;
+        state.skip_to = None;
+        state.resync_pending = false;
+        state.seek_seqnum = None;
+        unsafe {
+            gst::gst_segment_init(&mut state.upstream_segment, gst::GST_FORMAT_BYTES);
+            gst::gst_segment_init(&mut state.segment, gst::GST_FORMAT_TIME);
+            state.group_id = gst::gst_util_group_id_next();
+            state.segment_seqnum = gst::gst_util_seqnum_next();
+        }
+        state.segment_pending = false;
+        state.segment_start_unknown = false;
+        state.global_tags = None;
+    }
+
+    fn sink_activate(&self, pad: *mut gst::GstPad) -> bool {
+        let mode = unsafe {
+            let query = gst::gst_query_new_scheduling();
+            if gst::gst_pad_peer_query(pad, query) == glib::GFALSE {
+                gst::gst_mini_object_unref(query as *mut gst::GstMiniObject);
+                return false;
+            }
+
+            let pull = gst::gst_query_has_scheduling_mode_with_flags(query,
+                                                       gst::GST_PAD_MODE_PULL,
+                                                       gst::GST_SCHEDULING_FLAG_SEEKABLE) ==
+                       glib::GTRUE;
+            gst::gst_mini_object_unref(query as *mut gst::GstMiniObject);
+
+            if pull {
+                debug!(self.logger, "Activating in PULL mode");
+                gst::GST_PAD_MODE_PULL
+            } else {
+                debug!(self.logger, "Activating in PUSH mode");
+                gst::GST_PAD_MODE_PUSH
+            }
+        };
+
+        let upstream_size = unsafe {
+            let query = gst::gst_query_new_duration(gst::GST_FORMAT_BYTES);
+            let mut size = -1;
+            if gst::gst_pad_peer_query(pad, query) == glib::GTRUE {
+                gst::gst_query_parse_duration(query, ptr::null_mut(), &mut size);
+            }
+            gst::gst_mini_object_unref(query as *mut gst::GstMiniObject);
+
+            if size < 0 { None } else { Some(size as u64) }
+        };
+        self.state.lock().unwrap().upstream_size = upstream_size;
+
+        unsafe { gst::gst_pad_activate_mode(pad, mode, glib::GTRUE) == glib::GTRUE }
+    }
+
+    fn sink_activate_mode(&self, mode: gst::GstPadMode, active: bool) -> bool {
+        debug!(self.logger,
+               "{} pad in {:?} mode",
+               if active { "Activating" } else { "Deactivating" },
+               mode);
+
+        let pull = mode == gst::GST_PAD_MODE_PULL;
+
+        if active {
+            let upstream_size = self.state.lock().unwrap().upstream_size;
+            if !self.start(upstream_size, pull) {
+                return false;
+            }
+
+            if pull {
+                return self.start_task();
+            }
+        } else if pull {
+            unsafe {
+                gst::gst_pad_stop_task(self.sinkpad);
+            }
+        }
+
+        true
+    }
+
+    fn start_task(&self) -> bool {
+        unsafe {
+            gst::gst_pad_start_task(self.sinkpad,
+                                    Some(demuxer_loop),
+                                    self as *const DemuxerWrapper as glib::gpointer,
+                                    None) == glib::GTRUE
+        }
+    }
+
+    fn loop_fn(&self) {
+        let offset = self.state.lock().unwrap().offset;
+
+        trace!(self.logger, "Pulling buffer at offset {}", offset);
+
+        let mut buffer = ptr::null_mut();
+        let flow = unsafe {
+            gst::gst_pad_pull_range(self.sinkpad, offset, PULL_BLOCKSIZE, &mut buffer)
+        };
+
+        let flow = if flow == gst::GST_FLOW_OK {
+            let buffer: GstRc<Buffer> = unsafe { GstRc::from_owned_ptr(buffer) };
+            self.state.lock().unwrap().offset += buffer.get_size() as u64;
+
+            let flow = self.handle_buffer(buffer);
+            trace!(self.logger, "Handling buffer returned {:?}", flow);
+            flow
+        } else if flow == gst::GST_FLOW_EOS {
+            // Upstream is finished, let the implementation drain and EOS all streams
+            debug!(self.logger, "Got EOS at offset {}", offset);
+            self.end_of_stream();
+            self.all_streams_eos();
+            flow
+        } else {
+            debug!(self.logger, "Pulling failed: {:?}", flow);
+            flow
+        };
+
+        if flow == gst::GST_FLOW_OK {
+            return;
+        }
+
+        debug!(self.logger, "Pausing task: {:?}", flow);
+        unsafe {
+            gst::gst_pad_pause_task(self.sinkpad);
+        }
+
+        if flow == gst::GST_FLOW_EOS {
+            // Downstream might have returned EOS before all streams were
+            // finished
+            if !self.all_streams_have_eos() {
+                self.all_streams_eos();
+            }
+        } else if flow == gst::GST_FLOW_NOT_LINKED ||
+                  (flow as i32) < (gst::GST_FLOW_EOS as i32) {
+            self.post_message(&error_msg!(DemuxerError::StreamFailed,
+                                          ("Internal data stream error."),
+                                          ["streaming stopped, reason {:?}", flow]));
+            self.all_streams_eos();
+        }
+    }
+
+    fn sink_event(&self,
+                  pad: *mut gst::GstPad,
+                  parent: *mut gst::GstObject,
+                  event: *mut gst::GstEvent)
+                  -> bool {
+        let event_type = unsafe { (*event).type_ };
+
+        if event_type == gst::GST_EVENT_FLUSH_START || event_type == gst::GST_EVENT_FLUSH_STOP {
+            let seqnum = unsafe { gst::gst_event_get_seqnum(event) };
+
+            // Flushing because of our own seek for data from another offset must
+            // not affect anything downstream
+            if self.state.lock().unwrap().offset_seek_seqnum == Some(seqnum) {
+                debug!(self.logger, "Dropping flush event from offset seek");
+                unsafe {
+                    gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+                }
+                return true;
+            }
+        }
+
+        if event_type == gst::GST_EVENT_SEGMENT {
+            let res = unsafe {
+                let mut segment = ptr::null();
+                gst::gst_event_parse_segment(event, &mut segment);
+                self.handle_upstream_segment(&*segment, gst::gst_event_get_seqnum(event))
+            };
+
+            unsafe {
+                gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+            }
+
+            res
+        } else if event_type == gst::GST_EVENT_FLUSH_STOP {
+            {
+                // Flushing cleared the sticky segment on all our source pads
+                let state = &mut self.state.lock().unwrap();
+                unsafe {
+                    gst_base::gst_flow_combiner_reset(state.flow_combiner);
+                }
+                state.segment_pending = true;
+            }
+
+            unsafe { gst::gst_pad_event_default(pad, parent, event) == glib::GTRUE }
+        } else if event_type == gst::GST_EVENT_EOS {
+            debug!(self.logger, "Got EOS");
+            self.end_of_stream();
+
+            unsafe { gst::gst_pad_event_default(pad, parent, event) == glib::GTRUE }
+        } else {
+            unsafe { gst::gst_pad_event_default(pad, parent, event) == glib::GTRUE }
+        }
+    }
+
+    fn handle_upstream_segment(&self, segment: &gst::GstSegment, seqnum: u32) -> bool {
+        debug!(self.logger,
+               "Got upstream segment {}-{} in format {:?}",
+               segment.start,
+               segment.stop,
+               segment.format);
+
+        if segment.format == gst::GST_FORMAT_BYTES {
+            if !self.handle_segment(segment.start, segment.stop) {
+                return false;
+            }
+
+            let state = &mut self.state.lock().unwrap();
+            state.offset = segment.start;
+            state.skip_to = None;
+            state.resync_pending = false;
+
+            if state.offset_seek_seqnum == Some(seqnum) {
+                // Our own seek for data from another offset, the output
+                // segment stays the same
+                debug!(self.logger, "Upstream continues from offset {}", segment.start);
+                unsafe {
+                    gst::gst_segment_copy_into(segment, &mut state.upstream_segment);
+                }
+                return true;
+            }
+
+            // Upstream accepted our seek before the seek event returned
+            if state.seek_seqnum == Some(seqnum) {
+                debug!(self.logger, "Upstream seeked to {} for our seek", segment.start);
+                state.seek_seqnum = None;
+                state.commit_seek(seqnum);
+            }
+
+            // A segment caused by our own seek already has the right TIME segment
+            // configured. For anything else we only know the timestamps once the
+            // first buffer comes out again
+            if seqnum != state.segment_seqnum && segment.start != 0 {
+                debug!(self.logger, "Upstream seeked to {}", segment.start);
+                unsafe {
+                    gst::gst_segment_init(&mut state.segment, gst::GST_FORMAT_TIME);
+                }
+                state.segment_start_unknown = true;
+            }
+        } else if segment.format == gst::GST_FORMAT_TIME {
+            let state = &mut self.state.lock().unwrap();
+            unsafe {
+                gst::gst_segment_copy_into(segment, &mut state.segment);
+            }
+            state.segment_start_unknown = false;
+        } else {
+            warn!(self.logger, "Unsupported segment format {:?}", segment.format);
+            return false;
+        }
+
+        let state = &mut self.state.lock().unwrap();
+        unsafe {
+            gst::gst_segment_copy_into(segment, &mut state.upstream_segment);
+        }
+        state.segment_seqnum = seqnum;
+        state.segment_pending = true;
+
+        true
+    }
+
+    fn src_query(&self,
+                 pad: *mut gst::GstPad,
+                 parent: *mut gst::GstObject,
+                 query: *mut gst::GstQuery)
+                 -> bool {
+        let query_type = unsafe { (*query).type_ };
+
+        if query_type == gst::GST_QUERY_POSITION {
+            let mut format = gst::GST_FORMAT_UNDEFINED;
+            unsafe {
+                gst::gst_query_parse_position(query, &mut format, ptr::null_mut());
+            }
+            if format != gst::GST_FORMAT_TIME {
+                return false;
+            }
+
+            match self.get_position() {
+                Some(position) => {
+                    unsafe {
+                        gst::gst_query_set_position(query, format, position as i64);
+                    }
+                    true
+                }
+                None => false,
+            }
+        } else if query_type == gst::GST_QUERY_DURATION {
+            let mut format = gst::GST_FORMAT_UNDEFINED;
+            unsafe {
+                gst::gst_query_parse_duration(query, &mut format, ptr::null_mut());
+            }
+            if format != gst::GST_FORMAT_TIME {
+                return false;
+            }
+
+            match self.get_duration() {
+                Some(duration) => {
+                    unsafe {
+                        gst::gst_query_set_duration(query, format, duration as i64);
+                    }
+                    true
+                }
+                None => false,
+            }
+        } else if query_type == gst::GST_QUERY_SEEKING {
+            let mut format = gst::GST_FORMAT_UNDEFINED;
+            unsafe {
+                gst::gst_query_parse_seeking(query,
+                                             &mut format,
+                                             ptr::null_mut(),
+                                             ptr::null_mut(),
+                                             ptr::null_mut());
+            }
+            if format != gst::GST_FORMAT_TIME {
+                return false;
+            }
+
+            let duration = self.get_duration().map(|d| d as i64).unwrap_or(-1);
+            let seekable = if self.is_seekable() {
+                glib::GTRUE
+            } else {
+                glib::GFALSE
+            };
+            unsafe {
+                gst::gst_query_set_seeking(query, format, seekable, 0, duration);
+            }
+            true
+        } else {
+            unsafe { gst::gst_pad_query_default(pad, parent, query) == glib::GTRUE }
+        }
+    }
+
+    fn src_event(&self,
+                 pad: *mut gst::GstPad,
+                 parent: *mut gst::GstObject,
+                 event: *mut gst::GstEvent)
+                 -> bool {
+        let event_type = unsafe { (*event).type_ };
+
+        if event_type == gst::GST_EVENT_SEEK {
+            let res = self.handle_seek(event);
+            unsafe {
+                gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+            }
+            res
+        } else if event_type == gst::GST_EVENT_SELECT_STREAMS {
+            let (stream_ids, seqnum) = unsafe {
+                let mut list: *mut glib::GList = ptr::null_mut();
+                gst::gst_event_parse_select_streams(event, &mut list);
+
+                let mut stream_ids = Vec::new();
+                let mut item = list;
+                while !item.is_null() {
+                    let stream_id = CStr::from_ptr((*item).data as *const c_char);
+                    stream_ids.push(stream_id.to_string_lossy().into_owned());
+                    item = (*item).next;
+                }
+                glib::g_list_free_full(list, Some(glib::g_free));
+
+                (stream_ids, gst::gst_event_get_seqnum(event))
+            };
+
+            let res = self.handle_select_streams(&stream_ids, seqnum);
+            unsafe {
+                gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+            }
+            res
+        } else {
+            unsafe { gst::gst_pad_event_default(pad, parent, event) == glib::GTRUE }
+        }
+    }
+
+    fn handle_select_streams(&self, stream_ids: &[String], seqnum: u32) -> bool {
+        debug!(self.logger, "Selecting streams {:?}", stream_ids);
+
+        let indices = {
+            let state = &self.state.lock().unwrap();
+
+            let mut indices = Vec::with_capacity(stream_ids.len());
+            for stream_id in stream_ids {
+                match state
+                          .srcpads
+                          .iter()
+                          .find(|&(_, srcpad)| srcpad.stream.get_stream_id() == stream_id) {
+                    Some((index, _)) => indices.push(*index),
+                    None => {
+                        warn!(self.logger, "Can't select unknown stream {}", stream_id);
+                        return false;
+                    }
+                }
+            }
+
+            indices
+        };
+
+        if !self.select_streams(&indices) {
+            return false;
+        }
+
+        let (collection, selected) = {
+            let state = &mut self.state.lock().unwrap();
+
+            let mut selected = Vec::new();
+            for (index, srcpad) in state.srcpads.iter_mut() {
+                srcpad.selected = indices.contains(index);
+                if srcpad.selected {
+                    selected.push(srcpad.stream.clone());
+                }
+            }
+
+            (state.collection.clone(), selected)
+        };
+
+        if let Some(collection) = collection {
+            unsafe {
+                let msg =
+                    gst::gst_message_new_streams_selected(self.raw as *mut gst::GstObject,
+                                                          collection.as_ptr() as
+                                                          *mut gst::GstStreamCollection);
+                for stream in &selected {
+                    gst::gst_message_streams_selected_add(msg,
+                                                          stream.as_ptr() as *mut gst::GstStream);
+                }
+                gst::gst_message_set_seqnum(msg, seqnum);
+                gst::gst_element_post_message(self.raw, msg);
+            }
+        }
+
+        true
+    }
+
+    fn handle_seek(&self, event: *mut gst::GstEvent) -> bool {
+        let mut rate = 0.0;
+        let mut format = gst::GST_FORMAT_UNDEFINED;
+        let mut flags = gst::GstSeekFlags::empty();
+        let mut start_type = gst::GST_SEEK_TYPE_NONE;
+        let mut start = 0;
+        let mut stop_type = gst::GST_SEEK_TYPE_NONE;
+        let mut stop = 0;
+
+        let seqnum = unsafe {
+            gst::gst_event_parse_seek(event,
+                                      &mut rate,
+                                      &mut format,
+                                      &mut flags,
+                                      &mut start_type,
+                                      &mut start,
+                                      &mut stop_type,
+                                      &mut stop);
+            gst::gst_event_get_seqnum(event)
+        };
+
+        if format != gst::GST_FORMAT_TIME {
+            debug!(self.logger, "Can only seek in TIME format");
+            return false;
+        }
+
+        if rate < 0.0 {
+            debug!(self.logger, "Reverse playback not supported");
+            return false;
+        }
+
+        if !self.is_seekable() {
+            debug!(self.logger, "Not seekable");
+            return false;
+        }
+
+        let flush = flags.contains(gst::GST_SEEK_FLAG_FLUSH);
+        let pull = unsafe { (*self.sinkpad).mode } == gst::GST_PAD_MODE_PULL;
+
+        debug!(self.logger,
+               "Seeking with rate {} to {}-{} (flush {})",
+               rate,
+               start,
+               stop,
+               flush);
+
+        // Work on a copy so a failed seek leaves the configured segment alone
+        let mut seek_segment: gst::GstSegment = unsafe { mem::zeroed() };
+        unsafe {
+            gst::gst_segment_copy_into(&self.state.lock().unwrap().segment, &mut seek_segment);
+
+            let mut update = glib::GFALSE;
+            if gst::gst_segment_do_seek(&mut seek_segment,
+                                        rate,
+                                        format,
+                                        flags,
+                                        start_type,
+                                        start as u64,
+                                        stop_type,
+                                        stop as u64,
+                                        &mut update) == glib::GFALSE {
+                debug!(self.logger, "Invalid seek");
+                return false;
+            }
+        }
+
+        if pull {
+            if flush {
+                unsafe {
+                    let event = gst::gst_event_new_flush_start();
+                    gst::gst_event_set_seqnum(event, seqnum);
+                    self.push_flush_event(event);
+                }
+            } else {
+                unsafe {
+                    gst::gst_pad_pause_task(self.sinkpad);
+                }
+            }
+
+            unsafe {
+                glib::g_rec_mutex_lock(&mut (*self.sinkpad).stream_rec_lock);
+            }
+        }
+
+        let seek_stop = if seek_segment.stop == u64::MAX {
+            None
+        } else {
+            Some(seek_segment.stop)
+        };
+        let seek_result = self.seek(seek_segment.start, seek_stop);
+
+        if pull && flush {
+            unsafe {
+                let event = gst::gst_event_new_flush_stop(glib::GTRUE);
+                gst::gst_event_set_seqnum(event, seqnum);
+                self.push_flush_event(event);
+            }
+        }
+
+        let res = match seek_result {
+            Some(SeekResult::Ok(_)) |
+            Some(SeekResult::Eos) => true,
+            _ => false,
+        };
+
+        {
+            let state = &mut self.state.lock().unwrap();
+            unsafe {
+                gst::gst_segment_copy_into(&seek_segment, &mut state.seek_segment);
+            }
+        }
+
+        // In push mode the new segment only becomes valid once upstream
+        // accepted the seek to the new byte offset. Upstream might send the
+        // corresponding segment before the seek event returns already
+        let res = match seek_result {
+            Some(SeekResult::Ok(offset)) if !pull => {
+                debug!(self.logger, "Seeking upstream to offset {}", offset);
+
+                self.state.lock().unwrap().seek_seqnum = Some(seqnum);
+
+                let res = unsafe {
+                    let byte_seek = gst::gst_event_new_seek(1.0,
+                                                            gst::GST_FORMAT_BYTES,
+                                                            flags,
+                                                            gst::GST_SEEK_TYPE_SET,
+                                                            offset as i64,
+                                                            gst::GST_SEEK_TYPE_NONE,
+                                                            -1);
+                    gst::gst_event_set_seqnum(byte_seek, seqnum);
+
+                    gst::gst_pad_push_event(self.sinkpad, byte_seek) == glib::GTRUE
+                };
+
+                let state = &mut self.state.lock().unwrap();
+                if state.seek_seqnum.take().is_some() && res {
+                    state.commit_seek(seqnum);
+                }
+
+                res
+            }
+            _ => {
+                let state = &mut self.state.lock().unwrap();
+                if res {
+                    state.commit_seek(seqnum);
+                } else if pull && flush {
+                    // The flush-stop above cleared the sticky segment on all
+                    // source pads
+                    unsafe {
+                        gst_base::gst_flow_combiner_reset(state.flow_combiner);
+                    }
+                    state.segment_pending = true;
+                }
+
+                res
+            }
+        };
+
+        match seek_result {
+            Some(SeekResult::Ok(offset)) if res => {
+                debug!(self.logger, "Continuing from offset {}", offset);
+            }
+            Some(SeekResult::Eos) => {
+                debug!(self.logger, "Seeked after the end of the streams");
+                self.all_streams_eos();
+            }
+            Some(SeekResult::Ok(_)) if !pull => {
+                // The implementation was already moved to the new position but
+                // upstream continues from the current offset
+                debug!(self.logger, "Upstream seek failed");
+                self.state.lock().unwrap().resync_pending = true;
+            }
+            _ => {
+                debug!(self.logger, "Seek failed");
+            }
+        }
+
+        if pull {
+            if let Some(SeekResult::Ok(offset)) = seek_result {
+                self.state.lock().unwrap().offset = offset;
+            }
+
+            if seek_result != Some(SeekResult::Eos) {
+                self.start_task();
+            }
+
+            unsafe {
+                glib::g_rec_mutex_unlock(&mut (*self.sinkpad).stream_rec_lock);
+            }
+        }
+
+        res
+    }
+
+    // Sends a flush event upstream and downstream on all source pads
+    unsafe fn push_flush_event(&self, event: *mut gst::GstEvent) {
+        gst::gst_mini_object_ref(event as *mut gst::GstMiniObject);
+        gst::gst_pad_push_event(self.sinkpad, event);
+        self.push_src_event(event);
+    }
+
+    // Takes ownership of the event
+    fn push_src_event(&self, event: *mut gst::GstEvent) -> bool {
+        let srcpads: Vec<_> = self.state
+            .lock()
+            .unwrap()
+            .srcpads
+            .values()
+            .map(|srcpad| srcpad.pad)
+            .collect();
+
+        let mut res = false;
+        for pad in &srcpads {
+            unsafe {
+                gst::gst_mini_object_ref(event as *mut gst::GstMiniObject);
+                res |= gst::gst_pad_push_event(*pad, event) == glib::GTRUE;
+            }
+        }
+
+        unsafe {
+            gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+        }
+
+        res
+    }
+
+    fn new_tag_event(tags: &TagList, scope: gst::GstTagScope) -> *mut gst::GstEvent {
+        let mut tags = tags.to_owned();
+
+        unsafe {
+            gst::gst_tag_list_set_scope(tags.make_mut().as_mut_ptr(), scope);
+            gst::gst_event_new_tag(tags.into_ptr())
+        }
+    }
+
+    // Tags are sent right after the segment. Flushing clears them from the
+    // source pads, so they are sent again together with every new segment
+    fn push_pending_segment(&self, buffer: Option<&Buffer>) {
+        let (event, global_tags, stream_tags) = {
+            let state = &mut self.state.lock().unwrap();
+
+            if !state.segment_pending {
+                return;
+            }
+
+            // Start the segment from the first timestamp after an upstream seek
+            if state.segment_start_unknown {
+                if let Some(ts) = buffer.and_then(|buffer| buffer.get_dts().or(buffer.get_pts())) {
+                    state.segment.start = ts;
+                    state.segment.time = ts;
+                    state.segment.position = ts;
+                }
+                state.segment_start_unknown = false;
+            }
+
+            debug!(self.logger,
+                   "Pushing pending segment {}-{} (time {})",
+                   state.segment.start,
+                   state.segment.stop,
+                   state.segment.time);
+
+            state.segment_pending = false;
+            let event = unsafe {
+                let event = gst::gst_event_new_segment(&state.segment);
+                gst::gst_event_set_seqnum(event, state.segment_seqnum);
+                event
+            };
+
+            let stream_tags: Vec<_> = state
+                .srcpads
+                .values()
+                .filter_map(|srcpad| srcpad.stream.get_tags().map(|tags| (srcpad.pad, tags.to_owned())))
+                .collect();
+
+            (event, state.global_tags.clone(), stream_tags)
+        };
+
+        self.push_src_event(event);
+
+        if let Some(tags) = global_tags {
+            self.push_src_event(DemuxerWrapper::new_tag_event(&tags, gst::GST_TAG_SCOPE_GLOBAL));
+        }
+
+        for (pad, tags) in stream_tags {
+            unsafe {
+                gst::gst_pad_push_event(pad,
+                                        DemuxerWrapper::new_tag_event(&tags,
+                                                                      gst::GST_TAG_SCOPE_STREAM));
+            }
+        }
+    }
+
+    fn global_tags(&self, tags: GstRc<TagList>) {
+        debug!(self.logger, "Got global tags {:?}", tags);
+
+        let segment_pending = {
+            let state = &mut self.state.lock().unwrap();
+            state.global_tags = Some(tags.clone());
+            state.segment_pending
+        };
+
+        // Otherwise they are sent with the pending segment
+        if !segment_pending {
+            self.push_src_event(DemuxerWrapper::new_tag_event(&tags, gst::GST_TAG_SCOPE_GLOBAL));
+        }
+    }
+
+    fn stream_tags(&self, index: StreamIndex, tags: &TagList) -> Result<(), ErrorMessage> {
+        debug!(self.logger, "Got tags for stream {}: {:?}", index, tags);
+
+        let (pad, segment_pending) = {
+            let state = &self.state.lock().unwrap();
+            match state.srcpads.get(&index) {
+                Some(srcpad) => {
+                    srcpad.stream.set_tags(Some(tags));
+                    (srcpad.pad, state.segment_pending)
+                }
+                None => {
+                    return Err(error_msg!(DemuxerError::UnknownStream,
+                                          ["Unknown stream {}", index]))
+                }
+            }
+        };
+
+        // Otherwise they are sent with the pending segment
+        if !segment_pending {
+            unsafe {
+                gst::gst_pad_push_event(pad,
+                                        DemuxerWrapper::new_tag_event(tags,
+                                                                      gst::GST_TAG_SCOPE_STREAM));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_srcpad(&self, index: StreamIndex) -> Result<*mut gst::GstPad, ErrorMessage> {
+        match self.state.lock().unwrap().srcpads.get(&index) {
+            Some(srcpad) => Ok(srcpad.pad),
+            None => Err(error_msg!(DemuxerError::UnknownStream, ["Unknown stream {}", index])),
+        }
+    }
+
+    fn get_upstream_id(&self) -> Option<String> {
+        unsafe {
+            let upstream_id = gst::gst_pad_get_stream_id(self.sinkpad);
+            if upstream_id.is_null() {
+                return None;
+            }
+
+            let res = CStr::from_ptr(upstream_id).to_string_lossy().into_owned();
+            glib::g_free(upstream_id as glib::gpointer);
+
+            Some(res)
+        }
+    }
+
+    fn add_stream(&self, index: StreamIndex, stream: &Stream) -> Result<(), ErrorMessage> {
+        let pad = {
+            let state = &mut self.state.lock().unwrap();
+
+            if state.srcpads.contains_key(&index) {
+                return Err(error_msg!(DemuxerError::DuplicateStream,
+                                      ["Stream {} already exists", index]));
+            }
+
+            debug!(self.logger, "Adding stream {}: {:?}", index, stream);
+
+            unsafe {
+                let klass = (*(self.raw as *const gobject::GTypeInstance)).g_class as
+                            *mut gst::GstElementClass;
+                let templ_name = CString::new("src_%u").unwrap();
+                let templ = gst::gst_element_class_get_pad_template(klass, templ_name.as_ptr());
+
+                let name = CString::new(format!("src_{}", index)).unwrap();
+                let pad = gst::gst_pad_new_from_template(templ, name.as_ptr());
+
+                gst::gst_pad_set_query_function_full(pad,
+                                                     Some(demuxer_src_query),
+                                                     ptr::null_mut(),
+                                                     None);
+                gst::gst_pad_set_event_function_full(pad,
+                                                     Some(demuxer_src_event),
+                                                     ptr::null_mut(),
+                                                     None);
+
+                gst::gst_pad_set_active(pad, glib::GTRUE);
+
+                // The implementation only knows its own stream id, the stream
+                // we announce downstream carries the full one
+                let stream_id_cstr = CString::new(stream.get_stream_id()).unwrap();
+                let full_stream_id =
+                    gst::gst_pad_create_stream_id(pad, self.raw, stream_id_cstr.as_ptr());
+                let stream = {
+                    let full_stream = Stream::new(&CStr::from_ptr(full_stream_id)
+                                                       .to_string_lossy(),
+                                                  stream.get_caps().map(|caps| caps.to_owned()),
+                                                  stream.get_stream_type(),
+                                                  stream.get_stream_flags());
+                    full_stream.set_tags(stream.get_tags());
+                    full_stream
+                };
+
+                let event = gst::gst_event_new_stream_start(full_stream_id);
+                gst::gst_event_set_group_id(event, state.group_id);
+                gst::gst_event_set_stream(event, stream.as_ptr() as *mut gst::GstStream);
+                glib::g_free(full_stream_id as glib::gpointer);
+                gst::gst_pad_push_event(pad, event);
+
+                if let Some(caps) = stream.get_caps() {
+                    let event = gst::gst_event_new_caps(caps.as_ptr() as *mut gst::GstCaps);
+                    gst::gst_pad_push_event(pad, event);
+                }
+
+                let event = gst::gst_event_new_segment(&state.segment);
+                gst::gst_event_set_seqnum(event, state.segment_seqnum);
+                gst::gst_pad_push_event(pad, event);
+
+                if let Some(ref tags) = state.global_tags {
+                    gst::gst_pad_push_event(pad,
+                                            DemuxerWrapper::new_tag_event(tags,
+                                                                          gst::GST_TAG_SCOPE_GLOBAL));
+                }
+
+                if let Some(tags) = stream.get_tags() {
+                    gst::gst_pad_push_event(pad,
+                                            DemuxerWrapper::new_tag_event(tags,
+                                                                          gst::GST_TAG_SCOPE_STREAM));
+                }
+
+                gst_base::gst_flow_combiner_add_pad(state.flow_combiner, pad);
+                let selected = !stream.get_stream_flags().contains(FLAG_UNSELECT);
+                state
+                    .srcpads
+                    .insert(index,
+                            SrcPad {
+                                pad: pad,
+                                stream: stream,
+                                selected: selected,
+                            });
+
+                pad
+            }
+        };
+
+        unsafe {
+            gst::gst_element_add_pad(self.raw, pad);
+        }
+
+        Ok(())
+    }
+
+    fn added_all_streams(&self) {
+        let upstream_id = self.get_upstream_id().unwrap_or_default();
+
+        let collection = {
+            let state = &mut self.state.lock().unwrap();
+
+            let streams: Vec<Stream> = state
+                .srcpads
+                .values()
+                .map(|srcpad| srcpad.stream.clone())
+                .collect();
+            let collection = StreamCollection::new(&upstream_id, &streams);
+            state.collection = Some(collection.clone());
+
+            collection
+        };
+
+        debug!(self.logger,
+               "Posting stream collection with {} streams",
+               collection.len());
+
+        unsafe {
+            let collection_ptr = collection.as_ptr() as *mut gst::GstStreamCollection;
+
+            let msg = gst::gst_message_new_stream_collection(self.raw as *mut gst::GstObject,
+                                                             collection_ptr);
+            gst::gst_element_post_message(self.raw, msg);
+
+            self.push_src_event(gst::gst_event_new_stream_collection(collection_ptr));
+        }
+
+        debug!(self.logger, "No more pads");
+
+        unsafe {
+            gst::gst_element_no_more_pads(self.raw);
+            self.state.lock().unwrap().group_id = gst::gst_util_group_id_next();
+        }
+    }
+
+    fn stream_format_changed(&self, index: StreamIndex, stream: &Stream) -> Result<(), ErrorMessage> {
+        if let Some(caps) = stream.get_caps() {
+            let pad = {
+                let state = &self.state.lock().unwrap();
+                match state.srcpads.get(&index) {
+                    Some(srcpad) => {
+                        srcpad.stream.set_caps(Some(caps.to_owned()));
+                        srcpad.pad
+                    }
+                    None => {
+                        return Err(error_msg!(DemuxerError::UnknownStream,
+                                              ["Unknown stream {}", index]))
+                    }
+                }
+            };
+
+            debug!(self.logger, "Format changed for stream {}: {:?}", index, caps);
+
+            unsafe {
+                gst::gst_pad_push_event(pad,
+                                        gst::gst_event_new_caps(caps.as_ptr() as
+                                                                *mut gst::GstCaps));
+            }
+        }
+
+        if let Some(tags) = stream.get_tags() {
+            try!(self.stream_tags(index, tags));
+        }
+
+        Ok(())
+    }
+
+    fn new_eos_event(&self) -> *mut gst::GstEvent {
+        let segment_seqnum = self.state.lock().unwrap().segment_seqnum;
+
+        unsafe {
+            let event = gst::gst_event_new_eos();
+            gst::gst_event_set_seqnum(event, segment_seqnum);
+            event
+        }
+    }
+
+    fn stream_eos(&self, index: StreamIndex) -> Result<(), ErrorMessage> {
+        let pad = try!(self.get_srcpad(index));
+
+        debug!(self.logger, "EOS for stream {}", index);
+
+        self.push_pending_segment(None);
+
+        unsafe {
+            gst::gst_pad_push_event(pad, self.new_eos_event());
+        }
+
+        Ok(())
+    }
+
+    fn all_streams_eos(&self) {
+        debug!(self.logger, "EOS for all streams");
+
+        self.push_pending_segment(None);
+        self.push_src_event(self.new_eos_event());
+    }
+
+    // Whether EOS was already sent on every source pad
+    fn all_streams_have_eos(&self) -> bool {
+        let state = self.state.lock().unwrap();
+
+        state
+            .srcpads
+            .values()
+            .all(|srcpad| unsafe {
+                     let event = gst::gst_pad_get_sticky_event(srcpad.pad, gst::GST_EVENT_EOS, 0);
+                     if event.is_null() {
+                         false
+                     } else {
+                         gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+                         true
+                     }
+                 })
+    }
+
+    // Pushes data for a stream downstream after any pending segment, unless
+    // the stream is not selected
+    fn stream_push<T, G, F>(&self,
+                            index: StreamIndex,
+                            data: T,
+                            first_buffer: G,
+                            push: F)
+                            -> Result<gst::GstFlowReturn, ErrorMessage>
+        where T: fmt::Debug,
+              G: Fn(&T) -> Option<&Buffer>,
+              F: FnOnce(*mut gst::GstPad, T) -> gst::GstFlowReturn
+    {
+        let (pad, selected) = match self.state.lock().unwrap().srcpads.get(&index) {
+            Some(srcpad) => (srcpad.pad, srcpad.selected),
+            None => {
+                return Err(error_msg!(DemuxerError::UnknownStream, ["Unknown stream {}", index]))
+            }
+        };
 
-#[derive(Debug)]
-pub enum HandleBufferResult {
-    NeedMoreData,
-    Again,
-    // NeedDataFromOffset(u64),
-    StreamAdded(Stream),
-    HaveAllStreams,
-    StreamChanged(Stream),
-    // StreamsAdded(Vec<Stream>), // Implies HaveAllStreams
-    StreamsChanged(Vec<Stream>),
-    // TODO need something to replace/add new streams
-    // TODO should probably directly implement the GstStreams new world order
-    BufferForStream(StreamIndex, GstRc<Buffer>),
-    Eos(Option<StreamIndex>),
-}
+        if !selected {
+            trace!(self.logger, "Dropping {:?} for unselected stream {}", data, index);
+            return Ok(gst::GST_FLOW_OK);
+        }
 
-pub trait Demuxer {
-    fn start(&mut self,
-             upstream_size: Option<u64>,
-             random_access: bool)
-             -> Result<(), ErrorMessage>;
-    fn stop(&mut self) -> Result<(), ErrorMessage>;
+        self.push_pending_segment(first_buffer(&data));
 
-    fn seek(&mut self, start: u64, stop: Option<u64>) -> Result<SeekResult, ErrorMessage>;
-    fn handle_buffer(&mut self,
-                     buffer: Option<GstRc<Buffer>>)
-                     -> Result<HandleBufferResult, FlowError>;
-    fn end_of_stream(&mut self) -> Result<(), ErrorMessage>;
+        debug!(self.logger, "Pushing {:?} for pad {}", data, index);
+        let res = push(pad, data);
+        debug!(self.logger, "Pushing returned: {:?}", res);
 
-    fn is_seekable(&self) -> bool;
-    fn get_position(&self) -> Option<u64>;
-    fn get_duration(&self) -> Option<u64>;
-}
+        let res = {
+            let state = &self.state.lock().unwrap();
+            unsafe { gst_base::gst_flow_combiner_update_flow(state.flow_combiner, res) }
+        };
+        debug!(self.logger, "Combined return: {:?}", res);
 
-#[derive(Debug)]
-pub struct Stream {
-    pub index: StreamIndex,
-    pub caps: GstRc<Caps>,
-    pub stream_id: String,
-}
+        Ok(res)
+    }
+
+    fn stream_push_buffer(&self,
+                          index: StreamIndex,
+                          buffer: GstRc<Buffer>)
+                          -> Result<gst::GstFlowReturn, ErrorMessage> {
+        self.stream_push(index,
+                         buffer,
+                         |buffer| Some(&**buffer),
+                         |pad, buffer| unsafe {
+                             gst::gst_pad_push(pad, buffer.into_ptr() as *mut gst::GstBuffer)
+                         })
+    }
+
+    fn stream_push_buffer_list(&self,
+                               index: StreamIndex,
+                               list: GstRc<BufferList>)
+                               -> Result<gst::GstFlowReturn, ErrorMessage> {
+        self.stream_push(index,
+                         list,
+                         |list| list.get(0),
+                         |pad, list| unsafe { gst::gst_pad_push_list(pad, list.into_ptr()) })
+    }
+
+    fn remove_stream(&self, index: StreamIndex) -> Result<(), ErrorMessage> {
+        let pad = try!(self.get_srcpad(index));
+
+        debug!(self.logger, "Removing stream {}", index);
+
+        // Finish the stream before it goes away
+        self.push_pending_segment(None);
+        unsafe {
+            gst::gst_pad_push_event(pad, self.new_eos_event());
+        }
+
+        {
+            let state = &mut self.state.lock().unwrap();
+            state.srcpads.remove(&index);
+            unsafe {
+                gst_base::gst_flow_combiner_remove_pad(state.flow_combiner, pad);
+            }
+        }
 
-impl Stream {
-    pub fn new(index: StreamIndex, caps: GstRc<Caps>, stream_id: String) -> Stream {
-        Stream {
-            index: index,
-            caps: caps,
-            stream_id: stream_id,
+        unsafe {
+            gst::gst_pad_set_active(pad, glib::GFALSE);
+            gst::gst_element_remove_pad(self.raw, pad);
         }
+
+        Ok(())
     }
-}
 
-pub struct DemuxerWrapper {
-    raw: *mut gst::GstElement,
-    logger: Logger,
-    demuxer: Mutex<Box<Demuxer>>,
-    panicked: AtomicBool,
-}
+    fn remove_all_streams(&self) {
+        debug!(self.logger, "Removing all streams");
 
-impl DemuxerWrapper {
-    fn new(raw: *mut gst::GstElement, demuxer: Box<Demuxer>) -> DemuxerWrapper {
-        DemuxerWrapper {
-            raw: raw,
-            logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
-                                                    "rsdemux",
-                                                    0,
-                                                    "Rust demuxer base class"),
-                                 o!()),
-            demuxer: Mutex::new(demuxer),
-            panicked: AtomicBool::new(false),
+        let srcpads = {
+            let state = &mut self.state.lock().unwrap();
+            unsafe {
+                gst_base::gst_flow_combiner_clear(state.flow_combiner);
+            }
+            state.collection = None;
+            mem::replace(&mut state.srcpads, BTreeMap::new())
+        };
+
+        for srcpad in srcpads.values() {
+            unsafe {
+                gst::gst_element_remove_pad(self.raw, srcpad.pad);
+            }
         }
     }
 
-    fn start(&self, upstream_size: u64, random_access: bool) -> bool {
-        let demuxer = &mut self.demuxer.lock().unwrap();
+    fn start(&self, upstream_size: Option<u64>, random_access: bool) -> bool {
+        let demuxer = &mut self.lock_demuxer();
 
         debug!(self.logger,
-               "Starting with upstream size {} and random access {}",
+               "Starting with upstream size {:?} and random access {}",
                upstream_size,
                random_access);
 
-        let upstream_size = if upstream_size == u64::MAX {
-            None
-        } else {
-            Some(upstream_size)
-        };
-
         match demuxer.start(upstream_size, random_access) {
             Ok(..) => {
                 trace!(self.logger, "Successfully started");
@@ -140,7 +1387,7 @@ impl DemuxerWrapper {
 
     }
     fn stop(&self) -> bool {
-        let demuxer = &mut self.demuxer.lock().unwrap();
+        let demuxer = &mut self.lock_demuxer();
 
         debug!(self.logger, "Stopping");
 
@@ -158,7 +1405,7 @@ impl DemuxerWrapper {
     }
 
     fn is_seekable(&self) -> bool {
-        let demuxer = &self.demuxer.lock().unwrap();
+        let demuxer = &self.lock_demuxer();
 
         let seekable = demuxer.is_seekable();
         debug!(self.logger, "Seekable {}", seekable);
@@ -167,106 +1414,191 @@ impl DemuxerWrapper {
     }
 
 
-    fn get_position(&self, position: &mut u64) -> glib::gboolean {
-        let demuxer = &self.demuxer.lock().unwrap();
+    fn get_position(&self) -> Option<u64> {
+        let demuxer = &self.lock_demuxer();
 
-        match demuxer.get_position() {
-            None => {
-                trace!(self.logger, "Got no position");
-                *position = u64::MAX;
-                glib::GFALSE
-            }
-            Some(pos) => {
-                trace!(self.logger, "Returning position {}", pos);
-                *position = pos;
-                glib::GTRUE
-            }
-        }
+        let position = demuxer.get_position();
+        trace!(self.logger, "Returning position {:?}", position);
 
+        position
     }
 
-    fn get_duration(&self, duration: &mut u64) -> glib::gboolean {
-        let demuxer = &self.demuxer.lock().unwrap();
-
-        match demuxer.get_duration() {
-            None => {
-                trace!(self.logger, "Got no duration");
-                *duration = u64::MAX;
-                glib::GFALSE
-            }
-            Some(dur) => {
-                trace!(self.logger, "Returning duration {}", dur);
-                *duration = dur;
-                glib::GTRUE
-            }
-        }
-    }
+    fn get_duration(&self) -> Option<u64> {
+        let demuxer = &self.lock_demuxer();
 
-    fn seek(&self, start: u64, stop: u64, offset: &mut u64) -> bool {
-        extern "C" {
-            fn gst_rs_demuxer_stream_eos(raw: *mut gst::GstElement, index: u32);
-        };
+        let duration = demuxer.get_duration();
+        trace!(self.logger, "Returning duration {:?}", duration);
 
-        let stop = if stop == u64::MAX { None } else { Some(stop) };
+        duration
+    }
 
+    fn seek(&self, start: u64, stop: Option<u64>) -> Option<SeekResult> {
         debug!(self.logger, "Seeking to {:?}-{:?}", start, stop);
 
         let res = {
-            let mut demuxer = &mut self.demuxer.lock().unwrap();
+            let mut demuxer = &mut self.lock_demuxer();
 
             match demuxer.seek(start, stop) {
                 Ok(res) => res,
                 Err(ref msg) => {
                     error!(self.logger, "Failed to seek: {:?}", msg);
                     self.post_message(msg);
-                    return false;
+                    return None;
                 }
             }
         };
 
         match res {
-            SeekResult::TooEarly => {
-                debug!(self.logger, "Seeked too early");
+            SeekResult::TooEarly => debug!(self.logger, "Seeked too early"),
+            SeekResult::Ok(..) => trace!(self.logger, "Seeked successfully"),
+            // The caller sends EOS on all streams once it is done flushing
+            SeekResult::Eos => debug!(self.logger, "Seeked after EOS"),
+        }
+
+        Some(res)
+    }
+
+    fn select_streams(&self, streams: &[StreamIndex]) -> bool {
+        let demuxer = &mut self.lock_demuxer();
+
+        match demuxer.select_streams(streams) {
+            Ok(..) => {
+                trace!(self.logger, "Selected streams successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to select streams: {:?}", msg);
+                self.post_message(msg);
                 false
             }
-            SeekResult::Ok(off) => {
-                trace!(self.logger, "Seeked successfully");
-                *offset = off;
+        }
+    }
+
+    fn handle_segment(&self, start: u64, stop: u64) -> bool {
+        let stop = if stop == u64::MAX { None } else { Some(stop) };
+
+        let demuxer = &mut self.lock_demuxer();
+
+        match demuxer.handle_segment(start, stop) {
+            Ok(..) => {
+                trace!(self.logger, "Handled segment successfully");
                 true
             }
-            SeekResult::Eos => {
-                debug!(self.logger, "Seeked after EOS");
-                *offset = u64::MAX;
+            Err(ref msg) => {
+                error!(self.logger, "Failed to handle segment: {:?}", msg);
+                self.post_message(msg);
+                false
+            }
+        }
+    }
 
-                unsafe {
-                    gst_rs_demuxer_stream_eos(self.raw, u32::MAX);
+    fn sink_chain(&self, buffer: GstRc<Buffer>) -> gst::GstFlowReturn {
+        let (buffer, resync) = {
+            let state = &mut self.state.lock().unwrap();
+
+            let offset = state.offset;
+            let size = buffer.get_size() as u64;
+            state.offset += size;
+
+            let resync = if state.resync_pending {
+                state.resync_pending = false;
+                state.skip_to = None;
+                Some((offset, state.upstream_segment.stop))
+            } else {
+                None
+            };
+
+            let buffer = match state.skip_to {
+                None => buffer,
+                Some(skip_to) if skip_to >= offset + size => {
+                    trace!(self.logger, "Skipping buffer {:?} at offset {}", buffer, offset);
+                    return gst::GST_FLOW_OK;
+                }
+                Some(skip_to) => {
+                    state.skip_to = None;
+
+                    if skip_to <= offset {
+                        buffer
+                    } else {
+                        trace!(self.logger, "Skipping {} bytes", skip_to - offset);
+                        buffer.copy_region((skip_to - offset) as usize, None).unwrap()
+                    }
                 }
+            };
 
-                true
+            (buffer, resync)
+        };
+
+        if let Some((offset, stop)) = resync {
+            debug!(self.logger, "Continuing from offset {}", offset);
+            if !self.handle_segment(offset, stop) {
+                return gst::GST_FLOW_ERROR;
             }
         }
+
+        self.handle_buffer(buffer)
     }
 
-    fn handle_buffer(&self, buffer: GstRc<Buffer>) -> gst::GstFlowReturn {
-        extern "C" {
-            fn gst_rs_demuxer_stream_eos(raw: *mut gst::GstElement, index: u32);
-            fn gst_rs_demuxer_add_stream(raw: *mut gst::GstElement,
-                                         index: u32,
-                                         caps: *const gst::GstCaps,
-                                         stream_id: *const c_char);
-            fn gst_rs_demuxer_added_all_streams(raw: *mut gst::GstElement);
-            // fn gst_rs_demuxer_remove_all_streams(raw: *mut gst::GstElement);
-            fn gst_rs_demuxer_stream_format_changed(raw: *mut gst::GstElement,
-                                                    index: u32,
-                                                    caps: *const gst::GstCaps);
-            fn gst_rs_demuxer_stream_push_buffer(raw: *mut gst::GstElement,
-                                                 index: u32,
-                                                 buffer: *mut gst::GstBuffer)
-                                                 -> gst::GstFlowReturn;
+    fn need_data_from_offset(&self, offset: u64) -> gst::GstFlowReturn {
+        if unsafe { (*self.sinkpad).mode } == gst::GST_PAD_MODE_PULL {
+            debug!(self.logger, "Continuing to pull from offset {}", offset);
+            self.state.lock().unwrap().offset = offset;
+            return gst::GST_FLOW_OK;
+        }
+
+        debug!(self.logger, "Seeking upstream to offset {}", offset);
+
+        let seqnum = unsafe { gst::gst_util_seqnum_next() };
+        self.state.lock().unwrap().offset_seek_seqnum = Some(seqnum);
+
+        let res = unsafe {
+            let event = gst::gst_event_new_seek(1.0,
+                                                gst::GST_FORMAT_BYTES,
+                                                gst::GST_SEEK_FLAG_FLUSH |
+                                                gst::GST_SEEK_FLAG_ACCURATE,
+                                                gst::GST_SEEK_TYPE_SET,
+                                                offset as i64,
+                                                gst::GST_SEEK_TYPE_NONE,
+                                                -1);
+            gst::gst_event_set_seqnum(event, seqnum);
+
+            gst::gst_pad_push_event(self.sinkpad, event) == glib::GTRUE
+        };
+
+        if res {
+            return gst::GST_FLOW_OK;
+        }
+
+        // Upstream can't seek, we can only skip data until the offset if
+        // it is still ahead of us
+        let current_offset = {
+            let state = &mut self.state.lock().unwrap();
+            state.offset_seek_seqnum = None;
+
+            if offset >= state.offset {
+                debug!(self.logger,
+                       "Upstream not seekable, skipping {} bytes",
+                       offset - state.offset);
+                state.skip_to = Some(offset);
+                return gst::GST_FLOW_OK;
+            }
+
+            state.offset
         };
 
+        let msg = error_msg!(DemuxerError::SeekFailed,
+                             ["Upstream not seekable, can't go back to offset {} from {}",
+                              offset,
+                              current_offset]);
+        error!(self.logger, "Failed to get data from offset: {:?}", msg);
+        self.post_message(&msg);
+
+        gst::GST_FLOW_ERROR
+    }
+
+    fn handle_buffer(&self, buffer: GstRc<Buffer>) -> gst::GstFlowReturn {
         let mut res = {
-            let mut demuxer = &mut self.demuxer.lock().unwrap();
+            let mut demuxer = &mut self.lock_demuxer();
 
             trace!(self.logger, "Handling buffer {:?}", buffer);
 
@@ -292,50 +1624,64 @@ impl DemuxerWrapper {
                 HandleBufferResult::NeedMoreData => {
                     return gst::GST_FLOW_OK;
                 }
-                HandleBufferResult::StreamAdded(stream) => {
-                    let stream_id_cstr = CString::new(stream.stream_id.as_bytes()).unwrap();
-
-                    unsafe {
-                        gst_rs_demuxer_add_stream(self.raw,
-                                                  stream.index,
-                                                  stream.caps.as_ptr(),
-                                                  stream_id_cstr.as_ptr());
+                HandleBufferResult::NeedDataFromOffset(offset) => {
+                    return self.need_data_from_offset(offset);
+                }
+                HandleBufferResult::StreamAdded(index, stream) => {
+                    if let Err(ref msg) = self.add_stream(index, &stream) {
+                        return self.stream_error(msg);
+                    }
+                }
+                HandleBufferResult::HaveAllStreams => {
+                    self.added_all_streams();
+                }
+                HandleBufferResult::StreamChanged(index, stream) => {
+                    if let Err(ref msg) = self.stream_format_changed(index, &stream) {
+                        return self.stream_error(msg);
                     }
                 }
-                HandleBufferResult::HaveAllStreams => unsafe {
-                    gst_rs_demuxer_added_all_streams(self.raw);
-                },
-                HandleBufferResult::StreamChanged(stream) => unsafe {
-                    gst_rs_demuxer_stream_format_changed(self.raw,
-                                                         stream.index,
-                                                         stream.caps.as_ptr());
-                },
                 HandleBufferResult::StreamsChanged(streams) => {
-                    for stream in streams {
-                        unsafe {
-                            gst_rs_demuxer_stream_format_changed(self.raw,
-                                                                 stream.index,
-                                                                 stream.caps.as_ptr());
+                    for (index, stream) in streams {
+                        if let Err(ref msg) = self.stream_format_changed(index, &stream) {
+                            return self.stream_error(msg);
                         }
                     }
                 }
+                HandleBufferResult::GlobalTags(tags) => {
+                    self.global_tags(tags);
+                }
+                HandleBufferResult::TagsForStream(index, tags) => {
+                    if let Err(ref msg) = self.stream_tags(index, &tags) {
+                        return self.stream_error(msg);
+                    }
+                }
+                HandleBufferResult::StreamRemoved(index) => {
+                    if let Err(ref msg) = self.remove_stream(index) {
+                        return self.stream_error(msg);
+                    }
+                }
                 HandleBufferResult::BufferForStream(index, buffer) => {
-                    let flow_ret = unsafe {
-                        gst_rs_demuxer_stream_push_buffer(self.raw,
-                                                          index,
-                                                          buffer.into_ptr() as *mut gst::GstBuffer)
-                    };
-                    if flow_ret != gst::GST_FLOW_OK {
-                        return flow_ret;
+                    match self.stream_push_buffer(index, buffer) {
+                        Ok(flow_ret) if flow_ret != gst::GST_FLOW_OK => return flow_ret,
+                        Ok(..) => (),
+                        Err(ref msg) => return self.stream_error(msg),
                     }
                 }
-                HandleBufferResult::Eos(index) => {
-                    let index = index.unwrap_or(u32::MAX);
-
-                    unsafe {
-                        gst_rs_demuxer_stream_eos(self.raw, index);
+                HandleBufferResult::BufferListForStream(index, list) => {
+                    match self.stream_push_buffer_list(index, list) {
+                        Ok(flow_ret) if flow_ret != gst::GST_FLOW_OK => return flow_ret,
+                        Ok(..) => (),
+                        Err(ref msg) => return self.stream_error(msg),
                     }
-
+                }
+                HandleBufferResult::Eos(Some(index)) => {
+                    // The other streams continue
+                    if let Err(ref msg) = self.stream_eos(index) {
+                        return self.stream_error(msg);
+                    }
+                }
+                HandleBufferResult::Eos(None) => {
+                    self.all_streams_eos();
                     return gst::GST_FLOW_EOS;
                 }
                 HandleBufferResult::Again => {
@@ -346,7 +1692,7 @@ impl DemuxerWrapper {
             trace!(self.logger, "Calling again");
 
             res = {
-                let mut demuxer = &mut self.demuxer.lock().unwrap();
+                let mut demuxer = &mut self.lock_demuxer();
                 match demuxer.handle_buffer(None) {
                     Ok(res) => res,
                     Err(flow_error) => {
@@ -364,7 +1710,7 @@ impl DemuxerWrapper {
     }
 
     fn end_of_stream(&self) {
-        let mut demuxer = &mut self.demuxer.lock().unwrap();
+        let mut demuxer = &mut self.lock_demuxer();
 
         debug!(self.logger, "End of stream");
         match demuxer.end_of_stream() {
@@ -376,6 +1722,58 @@ impl DemuxerWrapper {
         }
     }
 
+    fn stream_error(&self, msg: &ErrorMessage) -> gst::GstFlowReturn {
+        error!(self.logger, "Failed handling streams: {:?}", msg);
+        self.post_message(msg);
+        gst::GST_FLOW_ERROR
+    }
+
+    fn set_property(&self, property: &Property, value: &Value) {
+        debug!(self.logger, "Setting property {} to {:?}", property.name, value);
+
+        self.properties.set(&property.name, value);
+
+        // Otherwise passed to the demuxer the next time it is locked
+        if let Ok(mut demuxer) = self.demuxer.try_lock() {
+            self.apply_properties(&mut demuxer);
+        }
+    }
+
+    fn get_property(&self, property: &Property) -> Option<Value> {
+        self.properties.get(&property.name)
+    }
+
+    // Locks the demuxer and passes it the property changes that happened
+    // while it was busy
+    fn lock_demuxer(&self) -> MutexGuard<Box<Demuxer>> {
+        let mut demuxer = self.demuxer.lock().unwrap();
+        self.apply_properties(&mut demuxer);
+        demuxer
+    }
+
+    fn apply_properties(&self, demuxer: &mut Box<Demuxer>) {
+        let pending = self.properties.take_pending();
+        if pending.is_empty() {
+            return;
+        }
+
+        for (name, value) in pending {
+            demuxer.set_property(&name, &value);
+        }
+        self.properties.update(|name| demuxer.get_property(name));
+    }
+
+    fn handle_action_signal(&self, signal: &Signal, args: &[Value]) -> Option<Value> {
+        let demuxer = &mut self.lock_demuxer();
+
+        debug!(self.logger, "Handling action signal {} with {:?}", signal.name, args);
+
+        let res = demuxer.handle_action_signal(&signal.name, args);
+        self.properties.update(|name| demuxer.get_property(name));
+
+        res
+    }
+
     fn post_message(&self, msg: &ErrorMessage) {
         unsafe {
             msg.post(self.raw);
@@ -383,28 +1781,23 @@ impl DemuxerWrapper {
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_new(demuxer: *mut gst::GstElement,
-                                     create_instance: fn(Element) -> Box<Demuxer>)
-                                     -> *mut DemuxerWrapper {
-    let instance = create_instance(Element::new(demuxer));
-    Box::into_raw(Box::new(DemuxerWrapper::new(demuxer, instance)))
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_drop(ptr: *mut DemuxerWrapper) {
-    let _ = Box::from_raw(ptr);
+impl Drop for DemuxerWrapper {
+    fn drop(&mut self) {
+        let state = &self.state.lock().unwrap();
+        unsafe {
+            gst_base::gst_flow_combiner_free(state.flow_combiner);
+        }
+    }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_start(ptr: *const DemuxerWrapper,
-                                       upstream_size: u64,
-                                       random_access: glib::gboolean)
-                                       -> glib::gboolean {
-    let wrap: &DemuxerWrapper = &*ptr;
+unsafe extern "C" fn demuxer_sink_activate(pad: *mut gst::GstPad,
+                                           parent: *mut gst::GstObject)
+                                           -> glib::gboolean {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
     panic_to_error!(wrap, glib::GFALSE, {
-        if wrap.start(upstream_size, random_access != glib::GFALSE) {
+        if wrap.sink_activate(pad) {
             glib::GTRUE
         } else {
             glib::GFALSE
@@ -412,12 +1805,16 @@ pub unsafe extern "C" fn demuxer_start(ptr: *const DemuxerWrapper,
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_stop(ptr: *const DemuxerWrapper) -> glib::gboolean {
-    let wrap: &DemuxerWrapper = &*ptr;
+unsafe extern "C" fn demuxer_sink_activate_mode(_pad: *mut gst::GstPad,
+                                                parent: *mut gst::GstObject,
+                                                mode: gst::GstPadMode,
+                                                active: glib::gboolean)
+                                                -> glib::gboolean {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
-    panic_to_error!(wrap, glib::GTRUE, {
-        if wrap.stop() {
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.sink_activate_mode(mode, active != glib::GFALSE) {
             glib::GTRUE
         } else {
             glib::GFALSE
@@ -425,12 +1822,30 @@ pub unsafe extern "C" fn demuxer_stop(ptr: *const DemuxerWrapper) -> glib::gbool
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_is_seekable(ptr: *const DemuxerWrapper) -> glib::gboolean {
-    let wrap: &DemuxerWrapper = &*ptr;
+unsafe extern "C" fn demuxer_sink_chain(_pad: *mut gst::GstPad,
+                                        parent: *mut gst::GstObject,
+                                        buffer: *mut gst::GstBuffer)
+                                        -> gst::GstFlowReturn {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        let buffer = GstRc::from_owned_ptr(buffer);
+        let res = wrap.sink_chain(buffer);
+        trace!(wrap.logger, "Handling buffer returned {:?}", res);
+        res
+    })
+}
+
+unsafe extern "C" fn demuxer_sink_event(pad: *mut gst::GstPad,
+                                        parent: *mut gst::GstObject,
+                                        event: *mut gst::GstEvent)
+                                        -> glib::gboolean {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
     panic_to_error!(wrap, glib::GFALSE, {
-        if wrap.is_seekable() {
+        if wrap.sink_event(pad, parent, event) {
             glib::GTRUE
         } else {
             glib::GFALSE
@@ -438,114 +1853,295 @@ pub unsafe extern "C" fn demuxer_is_seekable(ptr: *const DemuxerWrapper) -> glib
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_get_position(ptr: *const DemuxerWrapper,
-                                              position: *mut u64)
-                                              -> glib::gboolean {
-    let wrap: &DemuxerWrapper = &*ptr;
+unsafe extern "C" fn demuxer_src_query(pad: *mut gst::GstPad,
+                                       parent: *mut gst::GstObject,
+                                       query: *mut gst::GstQuery)
+                                       -> glib::gboolean {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
     panic_to_error!(wrap, glib::GFALSE, {
-        let position = &mut *position;
-        wrap.get_position(position)
+        if wrap.src_query(pad, parent, query) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_get_duration(ptr: *const DemuxerWrapper,
-                                              duration: *mut u64)
-                                              -> glib::gboolean {
-    let wrap: &DemuxerWrapper = &*ptr;
+unsafe extern "C" fn demuxer_src_event(pad: *mut gst::GstPad,
+                                       parent: *mut gst::GstObject,
+                                       event: *mut gst::GstEvent)
+                                       -> glib::gboolean {
+    let demuxer = &*(parent as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
     panic_to_error!(wrap, glib::GFALSE, {
-        let duration = &mut *duration;
-        wrap.get_duration(duration)
+        if wrap.src_event(pad, parent, event) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_seek(ptr: *mut DemuxerWrapper,
-                                      start: u64,
-                                      stop: u64,
-                                      offset: *mut u64)
-                                      -> glib::gboolean {
+unsafe extern "C" fn demuxer_loop(ptr: glib::gpointer) {
+    let wrap: &DemuxerWrapper = &*(ptr as *const DemuxerWrapper);
 
-    let wrap: &mut DemuxerWrapper = &mut *ptr;
+    // Don't keep the streaming thread spinning after a panic, the task is
+    // only restarted by a seek or a state change
+    if wrap.panicked.load(Ordering::Relaxed) {
+        gst::gst_pad_pause_task(wrap.sinkpad);
+    }
 
-    panic_to_error!(wrap, glib::GFALSE, {
-        let offset = &mut *offset;
+    panic_to_error!(wrap, (), {
+        wrap.loop_fn();
+    });
 
-        if wrap.seek(start, stop, offset) {
-            glib::GTRUE
-        } else {
-            glib::GFALSE
+    if wrap.panicked.load(Ordering::Relaxed) {
+        gst::gst_pad_pause_task(wrap.sinkpad);
+    }
+}
+
+unsafe extern "C" fn demuxer_change_state(element: *mut gst::GstElement,
+                                          transition: gst::GstStateChange)
+                                          -> gst::GstStateChangeReturn {
+    let demuxer = &*(element as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
+
+    panic_to_error!(wrap, gst::GST_STATE_CHANGE_FAILURE, {
+        debug!(wrap.logger, "Changing state {:?}", transition);
+
+        if transition == gst::GST_STATE_CHANGE_READY_TO_PAUSED {
+            wrap.prepare();
+        }
+
+        let demuxer_klass = &**(element as *const *const RsDemuxerClass);
+        let parent_klass = &*(demuxer_klass.parent_vtable as *const gst::GstElementClass);
+        let result = match parent_klass.change_state {
+            Some(change_state) => change_state(element, transition),
+            None => gst::GST_STATE_CHANGE_SUCCESS,
+        };
+
+        if result != gst::GST_STATE_CHANGE_FAILURE &&
+           transition == gst::GST_STATE_CHANGE_PAUSED_TO_READY {
+            // Ignore stop failures
+            wrap.stop();
+            wrap.remove_all_streams();
         }
+
+        result
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_handle_buffer(ptr: *mut DemuxerWrapper,
-                                               buffer: *mut gst::GstBuffer)
-                                               -> gst::GstFlowReturn {
-    let wrap: &mut DemuxerWrapper = &mut *ptr;
+pub struct DemuxerInfo {
+    pub name: String,
+    pub long_name: String,
+    pub description: String,
+    pub classification: String,
+    pub author: String,
+    pub rank: u32,
+    pub create_instance: fn(Element) -> Box<Demuxer>,
+    pub input_caps: GstRc<Caps>,
+    pub output_caps: GstRc<Caps>,
+    pub properties: Vec<Property>,
+    pub signals: Vec<Signal>,
+}
 
-    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
-        let buffer = GstRc::from_owned_ptr(buffer);
-        wrap.handle_buffer(buffer)
+#[repr(C)]
+struct RsDemuxer {
+    parent: gst::GstElement,
+    wrap: *mut DemuxerWrapper,
+    demuxer_info: *const DemuxerInfo,
+}
+
+#[repr(C)]
+struct RsDemuxerClass {
+    parent_class: gst::GstElementClass,
+    demuxer_info: *const DemuxerInfo,
+    parent_vtable: glib::gconstpointer,
+}
+
+unsafe extern "C" fn demuxer_finalize(obj: *mut gobject::GObject) {
+    let demuxer = &mut *(obj as *mut RsDemuxer);
+
+    drop(Box::from_raw(demuxer.wrap));
+
+    let demuxer_klass = &**(obj as *const *const RsDemuxerClass);
+    let parent_klass = &*(demuxer_klass.parent_vtable as *const gobject::GObjectClass);
+    parent_klass.finalize.map(|f| f(obj));
+}
+
+unsafe extern "C" fn demuxer_set_property(obj: *mut gobject::GObject,
+                                          id: u32,
+                                          value: *mut gobject::GValue,
+                                          _pspec: *mut gobject::GParamSpec) {
+    let demuxer = &*(obj as *const RsDemuxer);
+    let demuxer_info = &*demuxer.demuxer_info;
+    let property = &demuxer_info.properties[id as usize - 1];
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
+
+    panic_to_error!(wrap, (), {
+        wrap.set_property(property, &value_from_property_gvalue(property, value));
     })
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn demuxer_end_of_stream(ptr: *mut DemuxerWrapper) {
-    let wrap: &mut DemuxerWrapper = &mut *ptr;
+unsafe extern "C" fn demuxer_get_property(obj: *mut gobject::GObject,
+                                          id: u32,
+                                          value: *mut gobject::GValue,
+                                          pspec: *mut gobject::GParamSpec) {
+    let demuxer = &*(obj as *const RsDemuxer);
+    let demuxer_info = &*demuxer.demuxer_info;
+    let property = &demuxer_info.properties[id as usize - 1];
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
 
     panic_to_error!(wrap, (), {
-        wrap.end_of_stream();
+        match wrap.get_property(property) {
+            Some(ref v) if value_to_property_gvalue(property, v, value) => (),
+            Some(ref v) => {
+                error!(wrap.logger,
+                       "Invalid value {:?} for property {}",
+                       v,
+                       property.name);
+                gobject::g_param_value_set_default(pspec, value);
+            }
+            None => gobject::g_param_value_set_default(pspec, value),
+        }
     })
 }
 
-pub struct DemuxerInfo<'a> {
-    pub name: &'a str,
-    pub long_name: &'a str,
-    pub description: &'a str,
-    pub classification: &'a str,
-    pub author: &'a str,
-    pub rank: i32,
-    pub create_instance: fn(Element) -> Box<Demuxer>,
-    pub input_caps: &'a Caps,
-    pub output_caps: &'a Caps,
+unsafe fn demuxer_handle_action_signal(obj: *mut gobject::GObject,
+                                       signal: &Signal,
+                                       args: &[Value])
+                                       -> Option<Value> {
+    let demuxer = &*(obj as *const RsDemuxer);
+    let wrap: &DemuxerWrapper = &*demuxer.wrap;
+
+    panic_to_error!(wrap, None, {
+        wrap.handle_action_signal(signal, args)
+    })
+}
+
+unsafe extern "C" fn demuxer_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
+    let demuxer_klass = &mut *(klass as *mut RsDemuxerClass);
+    let demuxer_info = &*(klass_data as *const DemuxerInfo);
+
+    {
+        let gobject_klass = &mut demuxer_klass.parent_class.parent_class.parent_class;
+        gobject_klass.set_property = Some(demuxer_set_property);
+        gobject_klass.get_property = Some(demuxer_get_property);
+        gobject_klass.finalize = Some(demuxer_finalize);
+
+        install_properties(klass as *mut gobject::GObjectClass, 1, &demuxer_info.properties);
+        install_signals(klass, &demuxer_info.signals, demuxer_handle_action_signal);
+    }
+
+    {
+        let element_klass = &mut demuxer_klass.parent_class;
+        element_klass.change_state = Some(demuxer_change_state);
+
+        let longname_cstr = CString::new(demuxer_info.long_name.clone()).unwrap();
+        let classification_cstr = CString::new(demuxer_info.classification.clone()).unwrap();
+        let description_cstr = CString::new(demuxer_info.description.clone()).unwrap();
+        let author_cstr = CString::new(demuxer_info.author.clone()).unwrap();
+
+        gst::gst_element_class_set_static_metadata(element_klass,
+                                                   longname_cstr.into_raw(),
+                                                   classification_cstr.into_raw(),
+                                                   description_cstr.into_raw(),
+                                                   author_cstr.into_raw());
+
+        let templ_name = CString::new("sink").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SINK,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     demuxer_info.input_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+
+        let templ_name = CString::new("src_%u").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SRC,
+                                                     gst::GST_PAD_SOMETIMES,
+                                                     demuxer_info.output_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+    }
+
+    demuxer_klass.demuxer_info = demuxer_info;
+    demuxer_klass.parent_vtable = gobject::g_type_class_peek_parent(klass);
 }
 
-pub fn demuxer_register(plugin: &Plugin, demuxer_info: &DemuxerInfo) {
-    extern "C" {
-        fn gst_rs_demuxer_register(plugin: *const gst::GstPlugin,
-                                   name: *const c_char,
-                                   long_name: *const c_char,
-                                   description: *const c_char,
-                                   classification: *const c_char,
-                                   author: *const c_char,
-                                   rank: i32,
-                                   create_instance: *const c_void,
-                                   input_caps: *const gst::GstCaps,
-                                   output_caps: *const gst::GstCaps)
-                                   -> glib::gboolean;
-    }
-
-    let cname = CString::new(demuxer_info.name).unwrap();
-    let clong_name = CString::new(demuxer_info.long_name).unwrap();
-    let cdescription = CString::new(demuxer_info.description).unwrap();
-    let cclassification = CString::new(demuxer_info.classification).unwrap();
-    let cauthor = CString::new(demuxer_info.author).unwrap();
+unsafe extern "C" fn demuxer_init(instance: *mut gobject::GTypeInstance, klass: glib::gpointer) {
+    let demuxer = &mut *(instance as *mut RsDemuxer);
+    let demuxer_klass = &*(klass as *const RsDemuxerClass);
+    let demuxer_info = &*demuxer_klass.demuxer_info;
+
+    demuxer.demuxer_info = demuxer_info;
+
+    let element = &mut demuxer.parent as *mut gst::GstElement;
+
+    let templ_name = CString::new("sink").unwrap();
+    let templ = gst::gst_element_class_get_pad_template(klass as *mut gst::GstElementClass,
+                                                        templ_name.as_ptr());
+    let sinkpad = gst::gst_pad_new_from_template(templ, templ_name.as_ptr());
+
+    gst::gst_pad_set_activate_function_full(sinkpad,
+                                            Some(demuxer_sink_activate),
+                                            ptr::null_mut(),
+                                            None);
+    gst::gst_pad_set_activatemode_function_full(sinkpad,
+                                                Some(demuxer_sink_activate_mode),
+                                                ptr::null_mut(),
+                                                None);
+    gst::gst_pad_set_chain_function_full(sinkpad,
+                                         Some(demuxer_sink_chain),
+                                         ptr::null_mut(),
+                                         None);
+    gst::gst_pad_set_event_function_full(sinkpad,
+                                         Some(demuxer_sink_event),
+                                         ptr::null_mut(),
+                                         None);
+    gst::gst_element_add_pad(element, sinkpad);
+
+    let wrap = Box::new(DemuxerWrapper::new(element,
+                                            sinkpad,
+                                            &demuxer_info.properties,
+                                            (demuxer_info.create_instance)(Element::new(element))));
+    demuxer.wrap = Box::into_raw(wrap);
+}
 
+pub fn demuxer_register(plugin: &Plugin, demuxer_info: DemuxerInfo) {
     unsafe {
-        gst_rs_demuxer_register(plugin.as_ptr(),
-                                cname.as_ptr(),
-                                clong_name.as_ptr(),
-                                cdescription.as_ptr(),
-                                cclassification.as_ptr(),
-                                cauthor.as_ptr(),
-                                demuxer_info.rank,
-                                demuxer_info.create_instance as *const c_void,
-                                demuxer_info.input_caps.as_ptr(),
-                                demuxer_info.output_caps.as_ptr());
+        let parent_type = gst::gst_element_get_type();
+        let mut type_name = String::from("RsDemuxer-");
+        type_name.push_str(&demuxer_info.name);
+        let type_name_cstr = CString::new(type_name.into_bytes()).unwrap();
+
+        let name_cstr = CString::new(demuxer_info.name.clone().into_bytes()).unwrap();
+        let rank = demuxer_info.rank;
+
+        let demuxer_info = Box::new(demuxer_info);
+        let demuxer_info_ptr = Box::into_raw(demuxer_info) as glib::gpointer;
+
+        let type_info = gobject::GTypeInfo {
+            class_size: mem::size_of::<RsDemuxerClass>() as u16,
+            base_init: None,
+            base_finalize: None,
+            class_init: Some(demuxer_class_init),
+            class_finalize: None,
+            class_data: demuxer_info_ptr,
+            instance_size: mem::size_of::<RsDemuxer>() as u16,
+            n_preallocs: 0,
+            instance_init: Some(demuxer_init),
+            value_table: ptr::null(),
+        };
+
+        let type_ = gobject::g_type_register_static(parent_type,
+                                                    type_name_cstr.as_ptr(),
+                                                    &type_info,
+                                                    gobject::GTypeFlags::empty());
+
+        gst::gst_element_register(plugin.as_ptr(), name_cstr.as_ptr(), rank, type_);
     }
 }
@@ -69,6 +69,10 @@ pub fn gst_resource_error_domain() -> glib::GQuark {
     unsafe { gst::gst_resource_error_quark() }
 }
 
+pub fn gst_stream_error_domain() -> glib::GQuark {
+    unsafe { gst::gst_stream_error_quark() }
+}
+
 #[derive(Debug)]
 pub struct ErrorMessage {
     pub error_domain: u32,
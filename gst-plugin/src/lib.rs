@@ -17,6 +17,12 @@ extern crate slog;
 extern crate lazy_static;
 extern crate byteorder;
 extern crate num_rational;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
 #[macro_use]
 extern crate derivative;
 pub extern crate gobject_sys as gobject;
@@ -29,20 +35,32 @@ pub mod utils;
 #[macro_use]
 pub mod error;
 pub mod buffer;
+pub mod bufferlist;
+pub mod memory;
+pub mod meta;
 pub mod adapter;
 #[macro_use]
 pub mod plugin;
 pub mod source;
 pub mod sink;
 pub mod demuxer;
+pub mod muxer;
+pub mod parser;
+pub mod properties;
+pub mod signals;
+pub mod transform;
 pub mod log;
 pub mod value;
 pub mod caps;
+pub mod capsfeatures;
+pub mod datetime;
 pub mod bytes;
 pub mod tags;
 pub mod streams;
 pub mod miniobject;
 pub mod structure;
+#[cfg(feature = "serde")]
+pub mod structure_serde;
 
 pub mod ffi {
     pub use glib;
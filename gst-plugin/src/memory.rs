@@ -0,0 +1,373 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use buffer::*;
+use miniobject::*;
+
+use glib;
+use gst;
+
+#[repr(C)]
+pub struct Memory(gst::GstMemory);
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ReadMemoryMap<'a> {
+    memory: &'a Memory,
+    #[derivative(Debug="ignore")]
+    map_info: gst::GstMapInfo,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ReadWriteMemoryMap<'a> {
+    memory: &'a Memory,
+    #[derivative(Debug="ignore")]
+    map_info: gst::GstMapInfo,
+}
+
+unsafe impl MiniObject for Memory {
+    type PtrType = gst::GstMemory;
+}
+
+unsafe extern "C" fn owner_drop<T>(owner: glib::gpointer) {
+    let owner: Box<T> = Box::from_raw(owner as *mut T);
+    drop(owner);
+}
+
+impl Memory {
+    pub fn new_with_size(size: usize) -> Option<GstRc<Memory>> {
+        let raw = unsafe { gst::gst_allocator_alloc(ptr::null_mut(), size, ptr::null_mut()) };
+        if raw.is_null() {
+            None
+        } else {
+            Some(unsafe { GstRc::from_owned_ptr(raw) })
+        }
+    }
+
+    // Wraps the data of owner without copying, owner is dropped once the
+    // memory is freed. The memory is read-only
+    pub fn from_owned<T: AsRef<[u8]> + Send + 'static>(owner: T) -> GstRc<Memory> {
+        unsafe {
+            let owner = Box::new(owner);
+            let (data, size) = {
+                let slice = (*owner).as_ref();
+                (slice.as_ptr(), slice.len())
+            };
+            let user_data = Box::into_raw(owner);
+
+            GstRc::from_owned_ptr(gst::gst_memory_new_wrapped(gst::GST_MEMORY_FLAG_READONLY,
+                                                              data as glib::gpointer,
+                                                              size,
+                                                              0,
+                                                              size,
+                                                              user_data as glib::gpointer,
+                                                              Some(owner_drop::<T>)))
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.0.size
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.0.offset
+    }
+
+    pub fn get_maxsize(&self) -> usize {
+        self.0.maxsize
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.0.mini_object.flags & gst::GST_MEMORY_FLAG_READONLY.bits() != 0
+    }
+
+    // New memory sharing the given region of this one without copying
+    pub fn share(&self, offset: usize, size: Option<usize>) -> GstRc<Memory> {
+        unsafe {
+            GstRc::from_owned_ptr(gst::gst_memory_share(self.as_mut_ptr(),
+                                                        offset as isize,
+                                                        size.map(|s| s as isize).unwrap_or(-1)))
+        }
+    }
+
+    pub fn copy_part(&self, offset: usize, size: Option<usize>) -> Option<GstRc<Memory>> {
+        let raw = unsafe {
+            gst::gst_memory_copy(self.as_mut_ptr(),
+                                 offset as isize,
+                                 size.map(|s| s as isize).unwrap_or(-1))
+        };
+
+        if raw.is_null() {
+            None
+        } else {
+            Some(unsafe { GstRc::from_owned_ptr(raw) })
+        }
+    }
+
+    pub fn map_read(&self) -> Option<ReadMemoryMap> {
+        let mut map_info: gst::GstMapInfo = unsafe { mem::zeroed() };
+        let res =
+            unsafe { gst::gst_memory_map(self.as_mut_ptr(), &mut map_info, gst::GST_MAP_READ) };
+        if res == glib::GTRUE {
+            Some(ReadMemoryMap {
+                     memory: self,
+                     map_info: map_info,
+                 })
+        } else {
+            None
+        }
+    }
+
+    pub fn map_readwrite(&mut self) -> Option<ReadWriteMemoryMap> {
+        let mut map_info: gst::GstMapInfo = unsafe { mem::zeroed() };
+        let res = unsafe {
+            gst::gst_memory_map(self.as_mut_ptr(), &mut map_info, gst::GST_MAP_READWRITE)
+        };
+        if res == glib::GTRUE {
+            Some(ReadWriteMemoryMap {
+                     memory: self,
+                     map_info: map_info,
+                 })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Memory")
+            .field("ptr", &unsafe { self.as_ptr() })
+            .field("offset", &self.get_offset())
+            .field("size", &self.get_size())
+            .field("maxsize", &self.get_maxsize())
+            .finish()
+    }
+}
+
+impl ToOwned for Memory {
+    type Owned = GstRc<Memory>;
+
+    fn to_owned(&self) -> GstRc<Memory> {
+        unsafe { GstRc::from_unowned_ptr(self.as_ptr()) }
+    }
+}
+
+unsafe impl Sync for Memory {}
+unsafe impl Send for Memory {}
+
+impl<'a> ReadMemoryMap<'a> {
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.map_info.data as *const u8, self.map_info.size) }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.map_info.size
+    }
+
+    pub fn get_memory(&self) -> &Memory {
+        self.memory
+    }
+}
+
+impl<'a> Drop for ReadMemoryMap<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            gst::gst_memory_unmap(self.memory.as_mut_ptr(), &mut self.map_info);
+        }
+    }
+}
+
+impl<'a> ReadWriteMemoryMap<'a> {
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.map_info.data as *mut u8, self.map_info.size) }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.map_info.data as *const u8, self.map_info.size) }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.map_info.size
+    }
+
+    pub fn get_memory(&self) -> &Memory {
+        self.memory
+    }
+}
+
+impl<'a> Drop for ReadWriteMemoryMap<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            gst::gst_memory_unmap(self.memory.as_mut_ptr(), &mut self.map_info);
+        }
+    }
+}
+
+pub struct MemoryIter<'a> {
+    buffer: &'a Buffer,
+    idx: u32,
+    n_memory: u32,
+}
+
+impl<'a> Iterator for MemoryIter<'a> {
+    type Item = &'a Memory;
+
+    fn next(&mut self) -> Option<&'a Memory> {
+        if self.idx >= self.n_memory {
+            return None;
+        }
+
+        let memory = self.buffer.peek_memory(self.idx);
+        self.idx += 1;
+
+        memory
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.n_memory - self.idx) as usize;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for MemoryIter<'a> {}
+
+impl Buffer {
+    // Wraps the data of owner without copying, see Memory::from_owned
+    pub fn from_owned<T: AsRef<[u8]> + Send + 'static>(owner: T) -> GstRc<Buffer> {
+        let mut buffer = Buffer::new();
+        buffer
+            .get_mut()
+            .unwrap()
+            .append_memory(Memory::from_owned(owner));
+        buffer
+    }
+
+    pub fn n_memory(&self) -> u32 {
+        unsafe { gst::gst_buffer_n_memory(self.as_mut_ptr()) }
+    }
+
+    pub fn peek_memory(&self, idx: u32) -> Option<&Memory> {
+        if idx >= self.n_memory() {
+            return None;
+        }
+
+        unsafe { Some(Memory::from_ptr(gst::gst_buffer_peek_memory(self.as_mut_ptr(), idx))) }
+    }
+
+    pub fn iter_memories(&self) -> MemoryIter {
+        MemoryIter {
+            buffer: self,
+            idx: 0,
+            n_memory: self.n_memory(),
+        }
+    }
+
+    // Memories are merged if the buffer would contain more than the
+    // maximum number of memories afterwards
+    pub fn insert_memory(&mut self, idx: u32, memory: GstRc<Memory>) {
+        assert!(idx <= self.n_memory());
+
+        unsafe {
+            gst::gst_buffer_insert_memory(self.as_mut_ptr(), idx as i32, memory.into_ptr());
+        }
+    }
+
+    pub fn append_memory(&mut self, memory: GstRc<Memory>) {
+        unsafe {
+            gst::gst_buffer_insert_memory(self.as_mut_ptr(), -1, memory.into_ptr());
+        }
+    }
+
+    pub fn prepend_memory(&mut self, memory: GstRc<Memory>) {
+        self.insert_memory(0, memory);
+    }
+
+    pub fn replace_memory(&mut self, idx: u32, memory: GstRc<Memory>) {
+        assert!(idx < self.n_memory());
+
+        unsafe {
+            gst::gst_buffer_replace_memory_range(self.as_mut_ptr(), idx, 1, memory.into_ptr());
+        }
+    }
+
+    pub fn remove_memory(&mut self, idx: u32) {
+        assert!(idx < self.n_memory());
+
+        unsafe {
+            gst::gst_buffer_remove_memory_range(self.as_mut_ptr(), idx, 1);
+        }
+    }
+
+    pub fn remove_all_memory(&mut self) {
+        unsafe {
+            gst::gst_buffer_remove_memory_range(self.as_mut_ptr(), 0, -1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use std::sync::Arc;
+
+    fn init() {
+        unsafe {
+            gst::gst_init(ptr::null_mut(), ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_owned() {
+        init();
+
+        let data: Arc<[u8]> = Arc::new([3u8, 4, 5]);
+        let memory = Memory::from_owned(data.clone());
+        assert!(memory.is_readonly());
+        assert_eq!(memory.map_read().unwrap().as_slice().as_ptr(), data.as_ptr());
+        assert_eq!(Arc::strong_count(&data), 2);
+        drop(memory);
+        assert_eq!(Arc::strong_count(&data), 1);
+    }
+
+    #[test]
+    fn test_buffer_memories() {
+        init();
+
+        let mut buffer = Buffer::from_owned(vec![1u8, 2]);
+        {
+            let buffer = buffer.get_mut().unwrap();
+            buffer.append_memory(Memory::from_owned(vec![5u8, 6]));
+            buffer.insert_memory(1, Memory::from_owned([3u8, 4]));
+        }
+
+        assert_eq!(buffer.n_memory(), 3);
+        assert_eq!(buffer.map_read().unwrap().as_slice(), &[1, 2, 3, 4, 5, 6]);
+
+        {
+            let buffer = buffer.get_mut().unwrap();
+            buffer.replace_memory(0, Memory::from_owned(vec![0u8]));
+            buffer.remove_memory(2);
+        }
+
+        let sizes = buffer
+            .iter_memories()
+            .map(|m| m.get_size())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![1, 2]);
+        assert_eq!(buffer.map_read().unwrap().as_slice(), &[0, 3, 4]);
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+#[cfg(feature = "v1_14")]
+use std::u64;
+
+use buffer::*;
+use miniobject::*;
+use structure::*;
+#[cfg(feature = "v1_14")]
+use caps::Caps;
+
+use glib;
+use gobject;
+use gst;
+
+// Implemented by the Rust representation of a specific GstMeta. The type
+// must start with the GstMeta of that API
+pub unsafe trait MetaAPI: Sized {
+    fn get_meta_api() -> glib::GType;
+}
+
+#[repr(C)]
+pub struct Meta(gst::GstMeta);
+
+impl Meta {
+    pub fn get_api(&self) -> glib::GType {
+        unsafe { (*self.0.info).api }
+    }
+
+    pub fn downcast_ref<T: MetaAPI>(&self) -> Option<&T> {
+        if self.get_api() == T::get_meta_api() {
+            Some(unsafe { &*(self as *const Meta as *const T) })
+        } else {
+            None
+        }
+    }
+
+    pub fn downcast_mut<T: MetaAPI>(&mut self) -> Option<&mut T> {
+        if self.get_api() == T::get_meta_api() {
+            Some(unsafe { &mut *(self as *mut Meta as *mut T) })
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Debug for Meta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let api = unsafe { CStr::from_ptr(gobject::g_type_name(self.get_api())) };
+        f.debug_tuple("Meta").field(&api).finish()
+    }
+}
+
+pub struct MetaIter<'a> {
+    buffer: &'a Buffer,
+    state: glib::gpointer,
+}
+
+impl<'a> Iterator for MetaIter<'a> {
+    type Item = &'a Meta;
+
+    fn next(&mut self) -> Option<&'a Meta> {
+        unsafe {
+            let meta = gst::gst_buffer_iterate_meta(self.buffer.as_mut_ptr(), &mut self.state);
+            if meta.is_null() {
+                None
+            } else {
+                Some(&*(meta as *const Meta))
+            }
+        }
+    }
+}
+
+impl Buffer {
+    pub fn iter_meta(&self) -> MetaIter {
+        MetaIter {
+            buffer: self,
+            state: ptr::null_mut(),
+        }
+    }
+
+    // Returns the first meta of the given API
+    pub fn get_meta<T: MetaAPI>(&self) -> Option<&T> {
+        unsafe {
+            let meta = gst::gst_buffer_get_meta(self.as_mut_ptr(), T::get_meta_api());
+            if meta.is_null() {
+                None
+            } else {
+                Some(&*(meta as *const T))
+            }
+        }
+    }
+
+    pub fn get_meta_mut<T: MetaAPI>(&mut self) -> Option<&mut T> {
+        unsafe {
+            let meta = gst::gst_buffer_get_meta(self.as_mut_ptr(), T::get_meta_api());
+            if meta.is_null() {
+                None
+            } else {
+                Some(&mut *(meta as *mut T))
+            }
+        }
+    }
+
+    // Removes the first meta of the given API, returns false if there is
+    // none or it is locked
+    pub fn remove_meta<T: MetaAPI>(&mut self) -> bool {
+        unsafe {
+            let meta = gst::gst_buffer_get_meta(self.as_mut_ptr(), T::get_meta_api());
+            !meta.is_null() && gst::gst_buffer_remove_meta(self.as_mut_ptr(), meta) == glib::GTRUE
+        }
+    }
+
+    // Removes all metas for which func returns false
+    pub fn retain_meta<F: FnMut(&Meta) -> bool>(&mut self, mut func: F) {
+        let remove = self.iter_meta()
+            .filter(|meta| !func(meta))
+            .map(|meta| meta as *const Meta as *mut gst::GstMeta)
+            .collect::<Vec<_>>();
+
+        for meta in remove {
+            unsafe {
+                gst::gst_buffer_remove_meta(self.as_mut_ptr(), meta);
+            }
+        }
+    }
+}
+
+#[repr(C)]
+pub struct ProtectionMeta(gst::GstProtectionMeta);
+
+unsafe impl MetaAPI for ProtectionMeta {
+    fn get_meta_api() -> glib::GType {
+        unsafe { gst::gst_protection_meta_api_get_type() }
+    }
+}
+
+impl ProtectionMeta {
+    pub fn add(buffer: &mut Buffer, info: OwnedStructure) -> &mut ProtectionMeta {
+        unsafe {
+            let meta = gst::gst_buffer_add_protection_meta(buffer.as_mut_ptr(), info.into_ptr());
+            &mut *(meta as *mut ProtectionMeta)
+        }
+    }
+
+    pub fn get_info(&self) -> &Structure {
+        unsafe { Structure::from_borrowed_ptr(self.0.info) }
+    }
+
+    pub fn get_info_mut(&mut self) -> &mut Structure {
+        unsafe { Structure::from_borrowed_mut_ptr(self.0.info) }
+    }
+}
+
+impl fmt::Debug for ProtectionMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtectionMeta")
+            .field("info", &self.get_info())
+            .finish()
+    }
+}
+
+// Not in the bindings yet as it only exists since GStreamer 1.14
+#[cfg(feature = "v1_14")]
+#[repr(C)]
+struct GstReferenceTimestampMeta {
+    parent: gst::GstMeta,
+    reference: *mut gst::GstCaps,
+    timestamp: gst::GstClockTime,
+    duration: gst::GstClockTime,
+}
+
+#[cfg(feature = "v1_14")]
+extern "C" {
+    fn gst_reference_timestamp_meta_api_get_type() -> glib::GType;
+    fn gst_buffer_add_reference_timestamp_meta(buffer: *mut gst::GstBuffer,
+                                               reference: *mut gst::GstCaps,
+                                               timestamp: gst::GstClockTime,
+                                               duration: gst::GstClockTime)
+                                               -> *mut GstReferenceTimestampMeta;
+}
+
+#[cfg(feature = "v1_14")]
+#[repr(C)]
+pub struct ReferenceTimestampMeta(GstReferenceTimestampMeta);
+
+#[cfg(feature = "v1_14")]
+unsafe impl MetaAPI for ReferenceTimestampMeta {
+    fn get_meta_api() -> glib::GType {
+        unsafe { gst_reference_timestamp_meta_api_get_type() }
+    }
+}
+
+#[cfg(feature = "v1_14")]
+impl ReferenceTimestampMeta {
+    pub fn add<'a>(buffer: &'a mut Buffer,
+                   reference: &Caps,
+                   timestamp: u64,
+                   duration: Option<u64>)
+                   -> &'a mut ReferenceTimestampMeta {
+        unsafe {
+            let meta = gst_buffer_add_reference_timestamp_meta(buffer.as_mut_ptr(),
+                                                               reference.as_mut_ptr(),
+                                                               timestamp,
+                                                               duration.unwrap_or(u64::MAX));
+            &mut *(meta as *mut ReferenceTimestampMeta)
+        }
+    }
+
+    pub fn get_reference(&self) -> &Caps {
+        unsafe { Caps::from_ptr(self.0.reference) }
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.0.timestamp
+    }
+
+    pub fn get_duration(&self) -> Option<u64> {
+        if self.0.duration == u64::MAX {
+            None
+        } else {
+            Some(self.0.duration)
+        }
+    }
+}
+
+#[cfg(feature = "v1_14")]
+impl fmt::Debug for ReferenceTimestampMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReferenceTimestampMeta")
+            .field("reference", &self.get_reference())
+            .field("timestamp", &self.get_timestamp())
+            .field("duration", &self.get_duration())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaTransform<'a> {
+    // Copy of the buffer, or of a region of it given as offset and size
+    Copy(Option<(usize, usize)>),
+    // Any other transformation, by name
+    Other(&'a str),
+}
+
+// Metas carrying arbitrary Rust values, added with RustMeta::add
+pub trait CustomMeta: Send + Sync + Sized + 'static {
+    // Registered as the meta implementation name, the API gets an "API"
+    // suffix. Must be unique
+    fn get_name() -> &'static str;
+
+    // The value for the meta on the transformed buffer, None drops it
+    fn transform(&self, _transform: MetaTransform) -> Option<Self> {
+        None
+    }
+}
+
+#[repr(C)]
+pub struct RustMeta<T: CustomMeta> {
+    parent: gst::GstMeta,
+    value: T,
+}
+
+lazy_static! {
+    // Registered GstMetaInfo per CustomMeta type
+    static ref CUSTOM_META_INFOS: Mutex<HashMap<TypeId, usize>> = Mutex::new(HashMap::new());
+}
+
+unsafe extern "C" fn rust_meta_init<T: CustomMeta>(meta: *mut gst::GstMeta,
+                                                   params: glib::gpointer,
+                                                   _buffer: *mut gst::GstBuffer)
+                                                   -> glib::gboolean {
+    // Nothing must unwind into GStreamer
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        if params.is_null() {
+            return glib::GFALSE;
+        }
+
+        let value = match (*(params as *mut Option<T>)).take() {
+            Some(value) => value,
+            None => return glib::GFALSE,
+        };
+
+        let meta = meta as *mut RustMeta<T>;
+        ptr::write(&mut (*meta).value, value);
+
+        glib::GTRUE
+    }));
+
+    res.unwrap_or(glib::GFALSE)
+}
+
+unsafe extern "C" fn rust_meta_free<T: CustomMeta>(meta: *mut gst::GstMeta,
+                                                   _buffer: *mut gst::GstBuffer) {
+    let meta = meta as *mut RustMeta<T>;
+    ptr::drop_in_place(&mut (*meta).value);
+}
+
+unsafe extern "C" fn rust_meta_transform<T: CustomMeta>(dest: *mut gst::GstBuffer,
+                                                        meta: *mut gst::GstMeta,
+                                                        _buffer: *mut gst::GstBuffer,
+                                                        type_: glib::GQuark,
+                                                        data: glib::gpointer)
+                                                        -> glib::gboolean {
+    // Panics in CustomMeta::transform() must not unwind into GStreamer
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let meta = &*(meta as *const RustMeta<T>);
+        let type_name = CStr::from_ptr(glib::g_quark_to_string(type_)).to_string_lossy();
+
+        let transform = if type_name == "gst-copy" {
+            let copy = &*(data as *const gst::GstMetaTransformCopy);
+            if copy.region == glib::GTRUE {
+                MetaTransform::Copy(Some((copy.offset, copy.size)))
+            } else {
+                MetaTransform::Copy(None)
+            }
+        } else {
+            MetaTransform::Other(&*type_name)
+        };
+
+        if let Some(value) = meta.value.transform(transform) {
+            RustMeta::add(Buffer::from_mut_ptr(dest), value);
+        }
+
+        glib::GTRUE
+    }));
+
+    res.unwrap_or(glib::GFALSE)
+}
+
+impl<T: CustomMeta> RustMeta<T> {
+    fn get_info() -> *const gst::GstMetaInfo {
+        let mut infos = CUSTOM_META_INFOS.lock().unwrap();
+
+        let info = infos
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| unsafe {
+                let api_name_cstr = CString::new(format!("{}API", T::get_name())).unwrap();
+                let impl_name_cstr = CString::new(T::get_name()).unwrap();
+                let mut tags = [ptr::null()];
+
+                let api = gst::gst_meta_api_type_register(api_name_cstr.as_ptr(),
+                                                          tags.as_mut_ptr());
+                gst::gst_meta_register(api,
+                                       impl_name_cstr.as_ptr(),
+                                       mem::size_of::<RustMeta<T>>(),
+                                       Some(rust_meta_init::<T>),
+                                       Some(rust_meta_free::<T>),
+                                       Some(rust_meta_transform::<T>)) as
+                usize
+            });
+
+        *info as *const gst::GstMetaInfo
+    }
+
+    pub fn add(buffer: &mut Buffer, value: T) -> &mut RustMeta<T> {
+        let mut value = Some(value);
+
+        unsafe {
+            let meta = gst::gst_buffer_add_meta(buffer.as_mut_ptr(),
+                                                RustMeta::<T>::get_info(),
+                                                &mut value as *mut Option<T> as
+                                                glib::gpointer);
+            assert!(!meta.is_null(), "Failed to add meta");
+
+            &mut *(meta as *mut RustMeta<T>)
+        }
+    }
+}
+
+unsafe impl<T: CustomMeta> MetaAPI for RustMeta<T> {
+    fn get_meta_api() -> glib::GType {
+        unsafe { (*RustMeta::<T>::get_info()).api }
+    }
+}
+
+impl<T: CustomMeta> Deref for RustMeta<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: CustomMeta> DerefMut for RustMeta<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: CustomMeta + fmt::Debug> fmt::Debug for RustMeta<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("RustMeta").field(&self.value).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn init() {
+        unsafe {
+            gst::gst_init(ptr::null_mut(), ptr::null_mut());
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct TestMeta(Vec<u8>, Option<(usize, usize)>);
+
+    impl CustomMeta for TestMeta {
+        fn get_name() -> &'static str {
+            "RsTestMeta"
+        }
+
+        fn transform(&self, transform: MetaTransform) -> Option<TestMeta> {
+            match transform {
+                MetaTransform::Copy(region) => Some(TestMeta(self.0.clone(), region)),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom() {
+        init();
+
+        let mut buffer = Buffer::from_vec(vec![1, 2, 3, 4]).unwrap();
+        RustMeta::add(buffer.get_mut().unwrap(), TestMeta(vec![5, 6], None));
+
+        assert_eq!(buffer.get_meta::<RustMeta<TestMeta>>().map(|m| &**m),
+                   Some(&TestMeta(vec![5, 6], None)));
+        assert_eq!(buffer.iter_meta().count(), 1);
+
+        let copy = buffer.copy_region(1, Some(2)).unwrap();
+        assert_eq!(copy.get_meta::<RustMeta<TestMeta>>().map(|m| &**m),
+                   Some(&TestMeta(vec![5, 6], Some((1, 2)))));
+
+        assert!(buffer.get_mut().unwrap().remove_meta::<RustMeta<TestMeta>>());
+        assert!(buffer.get_meta::<RustMeta<TestMeta>>().is_none());
+    }
+
+    #[test]
+    fn test_protection() {
+        init();
+
+        let mut buffer = Buffer::new();
+        ProtectionMeta::add(buffer.get_mut().unwrap(),
+                            OwnedStructure::new_empty("application/x-cenc"));
+
+        assert_eq!(buffer
+                       .get_meta::<ProtectionMeta>()
+                       .map(|m| m.get_info().get_name()),
+                   Some("application/x-cenc"));
+
+        buffer.get_mut().unwrap().retain_meta(|_| false);
+        assert_eq!(buffer.iter_meta().count(), 0);
+    }
+}
@@ -0,0 +1,991 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use libc::c_char;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::mem;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use slog::Logger;
+
+use utils::*;
+use error::*;
+use buffer::*;
+use miniobject::*;
+use log::*;
+use caps::Caps;
+use plugin::Plugin;
+
+use glib;
+use gobject;
+use gst;
+use gst_base;
+
+pub type PadIndex = u32;
+
+#[derive(Debug)]
+pub enum MuxerError {
+    Failure,
+    MuxFailed,
+}
+
+impl ToGError for MuxerError {
+    fn to_gerror(&self) -> (u32, i32) {
+        match *self {
+            MuxerError::Failure => (gst_library_error_domain(), 1),
+            MuxerError::MuxFailed => (gst_stream_error_domain(), 10),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MuxerOutput {
+    // Output caps, must be set before the first buffer
+    Caps(GstRc<Caps>),
+    Buffer(GstRc<Buffer>),
+    // Following buffers are written starting at this byte offset, e.g. to
+    // rewrite headers at EOS. Ignored together with these buffers if
+    // downstream is not seekable
+    Seek(u64),
+}
+
+pub trait Muxer {
+    fn start(&mut self) -> Result<(), ErrorMessage>;
+    fn stop(&mut self) -> Result<(), ErrorMessage>;
+
+    fn set_caps(&mut self, index: PadIndex, caps: &Caps) -> Result<(), ErrorMessage>;
+
+    fn release_pad(&mut self, _index: PadIndex) {}
+
+    // Called with the running time of the next queued buffer of every pad
+    // that is not EOS yet, returns the pad whose buffer should be muxed next.
+    // None is an error
+    fn choose_next(&mut self, candidates: &[(PadIndex, Option<u64>)]) -> Option<PadIndex> {
+        // Buffers without timestamp go first
+        candidates
+            .iter()
+            .min_by_key(|&&(_, running_time)| running_time)
+            .map(|&(index, _)| index)
+    }
+
+    fn handle_buffer(&mut self,
+                     index: PadIndex,
+                     buffer: GstRc<Buffer>)
+                     -> Result<Vec<MuxerOutput>, FlowError>;
+    fn end_of_stream(&mut self) -> Result<Vec<MuxerOutput>, FlowError>;
+}
+
+struct State {
+    sinkpads: BTreeMap<PadIndex, *mut gst::GstPad>,
+    next_pad_index: PadIndex,
+
+    stream_start_pending: bool,
+    have_caps: bool,
+    segment_pending: bool,
+    downstream_seekable: Option<bool>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            sinkpads: BTreeMap::new(),
+            next_pad_index: 0,
+            stream_start_pending: true,
+            have_caps: false,
+            segment_pending: true,
+            downstream_seekable: None,
+        }
+    }
+}
+
+#[repr(C)]
+struct RsCollectData {
+    parent: gst_base::GstCollectData,
+    index: PadIndex,
+}
+
+pub struct MuxerWrapper {
+    raw: *mut gst::GstElement,
+    logger: Logger,
+    srcpad: *mut gst::GstPad,
+    collect: *mut gst_base::GstCollectPads,
+    state: Mutex<State>,
+    muxer: Mutex<Box<Muxer>>,
+    panicked: AtomicBool,
+}
+
+impl MuxerWrapper {
+    fn new(raw: *mut gst::GstElement,
+           srcpad: *mut gst::GstPad,
+           collect: *mut gst_base::GstCollectPads,
+           muxer: Box<Muxer>)
+           -> MuxerWrapper {
+        MuxerWrapper {
+            raw: raw,
+            logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
+                                                    "rsmux",
+                                                    0,
+                                                    "Rust muxer base class"),
+                                 o!()),
+            srcpad: srcpad,
+            collect: collect,
+            state: Mutex::new(State::new()),
+            muxer: Mutex::new(muxer),
+            panicked: AtomicBool::new(false),
+        }
+    }
+
+    fn start(&self) -> bool {
+        {
+            let state = &mut self.state.lock().unwrap();
+            state.stream_start_pending = true;
+            state.have_caps = false;
+            state.segment_pending = true;
+            state.downstream_seekable = None;
+        }
+
+        let muxer = &mut self.muxer.lock().unwrap();
+
+        debug!(self.logger, "Starting");
+
+        match muxer.start() {
+            Ok(..) => {
+                trace!(self.logger, "Started successfully");
+                unsafe {
+                    gst_base::gst_collect_pads_start(self.collect);
+                }
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to start: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn stop(&self) -> bool {
+        let muxer = &mut self.muxer.lock().unwrap();
+
+        debug!(self.logger, "Stopping");
+
+        match muxer.stop() {
+            Ok(..) => {
+                trace!(self.logger, "Stopped successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to stop: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn request_new_pad(&self,
+                       templ: *mut gst::GstPadTemplate,
+                       name: Option<&str>)
+                       -> *mut gst::GstPad {
+        let pad = {
+            let state = &mut self.state.lock().unwrap();
+
+            let index = match name.and_then(|name| if name.starts_with("sink_") {
+                                                name[5..].parse::<PadIndex>().ok()
+                                            } else {
+                                                None
+                                            }) {
+                Some(index) => {
+                    if state.sinkpads.contains_key(&index) {
+                        error!(self.logger, "Pad {} already exists", index);
+                        return ptr::null_mut();
+                    }
+                    index
+                }
+                None => state.next_pad_index,
+            };
+
+            if index >= state.next_pad_index {
+                state.next_pad_index = index + 1;
+            }
+
+            debug!(self.logger, "Requesting pad {}", index);
+
+            unsafe {
+                let name = CString::new(format!("sink_{}", index)).unwrap();
+                let pad = gst::gst_pad_new_from_template(templ, name.as_ptr());
+
+                let data = gst_base::gst_collect_pads_add_pad(self.collect,
+                                                              pad,
+                                                              mem::size_of::<RsCollectData>() as
+                                                              u32,
+                                                              None,
+                                                              glib::GTRUE) as
+                           *mut RsCollectData;
+                (*data).index = index;
+
+                state.sinkpads.insert(index, pad);
+
+                pad
+            }
+        };
+
+        unsafe {
+            gst::gst_element_add_pad(self.raw, pad);
+        }
+
+        pad
+    }
+
+    fn release_pad(&self, pad: *mut gst::GstPad) {
+        let index = {
+            let state = &mut self.state.lock().unwrap();
+
+            let index = match state.sinkpads.iter().find(|&(_, p)| *p == pad) {
+                Some((index, _)) => *index,
+                None => return,
+            };
+            state.sinkpads.remove(&index);
+
+            index
+        };
+
+        debug!(self.logger, "Releasing pad {}", index);
+
+        unsafe {
+            gst_base::gst_collect_pads_remove_pad(self.collect, pad);
+        }
+
+        self.muxer.lock().unwrap().release_pad(index);
+
+        unsafe {
+            gst::gst_element_remove_pad(self.raw, pad);
+        }
+    }
+
+    fn sink_event(&self, data: *mut RsCollectData, event: *mut gst::GstEvent) -> bool {
+        let event_type = unsafe { (*event).type_ };
+        let index = unsafe { (*data).index };
+
+        trace!(self.logger,
+               "Handling event {:?} on pad {}",
+               event_type,
+               index);
+
+        match event_type {
+            gst::GST_EVENT_CAPS => {
+                let caps = unsafe {
+                    let mut caps = ptr::null_mut();
+                    gst::gst_event_parse_caps(event, &mut caps);
+                    Caps::from_ptr(caps)
+                };
+
+                debug!(self.logger, "Got caps {:?} on pad {}", caps, index);
+
+                let res = self.muxer.lock().unwrap().set_caps(index, caps);
+                match res {
+                    Ok(..) => unsafe {
+                        gst_base::gst_collect_pads_event_default(self.collect,
+                                                                 data as
+                                                                 *mut gst_base::GstCollectData,
+                                                                 event,
+                                                                 glib::GTRUE) ==
+                        glib::GTRUE
+                    },
+                    Err(ref msg) => {
+                        error!(self.logger, "Failed to set caps: {:?}", msg);
+                        unsafe {
+                            gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+                        }
+                        self.post_message(msg);
+                        false
+                    }
+                }
+            }
+            // Flushing is forwarded, all serialized events would end up
+            // in the middle of our own output and are dropped
+            gst::GST_EVENT_FLUSH_START => unsafe {
+                gst_base::gst_collect_pads_event_default(self.collect,
+                                                         data as *mut gst_base::GstCollectData,
+                                                         event,
+                                                         glib::GFALSE) ==
+                glib::GTRUE
+            },
+            gst::GST_EVENT_FLUSH_STOP => {
+                {
+                    // Flushing cleared the sticky events downstream
+                    let state = &mut self.state.lock().unwrap();
+                    state.stream_start_pending = true;
+                    state.segment_pending = true;
+                }
+
+                unsafe {
+                    gst_base::gst_collect_pads_event_default(self.collect,
+                                                             data as
+                                                             *mut gst_base::GstCollectData,
+                                                             event,
+                                                             glib::GFALSE) ==
+                    glib::GTRUE
+                }
+            }
+            _ => unsafe {
+                gst_base::gst_collect_pads_event_default(self.collect,
+                                                         data as *mut gst_base::GstCollectData,
+                                                         event,
+                                                         glib::GTRUE) ==
+                glib::GTRUE
+            },
+        }
+    }
+
+    fn src_event(&self,
+                 pad: *mut gst::GstPad,
+                 parent: *mut gst::GstObject,
+                 event: *mut gst::GstEvent)
+                 -> bool {
+        unsafe {
+            match (*event).type_ {
+                gst::GST_EVENT_SEEK => {
+                    debug!(self.logger, "Seeking is not supported");
+                    gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+                    false
+                }
+                _ => gst::gst_pad_event_default(pad, parent, event) == glib::GTRUE,
+            }
+        }
+    }
+
+    fn collect_data(&self) -> Vec<*mut RsCollectData> {
+        let mut res = Vec::new();
+
+        // Only called with the collect pads stream lock
+        unsafe {
+            let mut walk = (*self.collect).data;
+            while !walk.is_null() {
+                res.push((*walk).data as *mut RsCollectData);
+                walk = (*walk).next;
+            }
+        }
+
+        res
+    }
+
+    fn collected(&self) -> gst::GstFlowReturn {
+        let collect_data = self.collect_data();
+
+        let mut candidates = Vec::with_capacity(collect_data.len());
+        for data in &collect_data {
+            let buffer = unsafe {
+                let buffer = gst_base::gst_collect_pads_peek(self.collect,
+                                                             *data as
+                                                             *mut gst_base::GstCollectData);
+                if buffer.is_null() {
+                    // EOS on this pad
+                    continue;
+                }
+                GstRc::<Buffer>::from_owned_ptr(buffer)
+            };
+
+            // Timestamps were converted to running time by the clip function
+            let running_time = buffer.get_dts().or_else(|| buffer.get_pts());
+            candidates.push((unsafe { (**data).index }, running_time));
+        }
+
+        if candidates.is_empty() {
+            debug!(self.logger, "All pads are EOS");
+            return self.end_of_stream();
+        }
+
+        let res = {
+            let muxer = &mut self.muxer.lock().unwrap();
+
+            let index = match muxer.choose_next(&candidates) {
+                Some(index) => index,
+                None => {
+                    error!(self.logger, "Chose no pad");
+                    self.post_message(&error_msg!(MuxerError::Failure, ["Chose no pad"]));
+                    return gst::GST_FLOW_ERROR;
+                }
+            };
+            let data = match collect_data
+                      .iter()
+                      .find(|data| unsafe { (***data).index } == index) {
+                Some(data) => *data,
+                None => {
+                    error!(self.logger, "Chose invalid pad {}", index);
+                    self.post_message(&error_msg!(MuxerError::Failure,
+                                                  ["Chose invalid pad {}", index]));
+                    return gst::GST_FLOW_ERROR;
+                }
+            };
+
+            let buffer = unsafe {
+                let buffer = gst_base::gst_collect_pads_pop(self.collect,
+                                                            data as *mut gst_base::GstCollectData);
+                if buffer.is_null() {
+                    error!(self.logger, "Chose pad {} without buffer", index);
+                    self.post_message(&error_msg!(MuxerError::Failure,
+                                                  ["Chose pad {} without buffer", index]));
+                    return gst::GST_FLOW_ERROR;
+                }
+                GstRc::<Buffer>::from_owned_ptr(buffer)
+            };
+
+            trace!(self.logger, "Muxing buffer {:?} from pad {}", buffer, index);
+
+            muxer.handle_buffer(index, buffer)
+        };
+
+        match res {
+            Ok(outputs) => self.push_outputs(outputs),
+            Err(flow_error) => self.handle_flow_error(flow_error),
+        }
+    }
+
+    fn end_of_stream(&self) -> gst::GstFlowReturn {
+        let res = self.muxer.lock().unwrap().end_of_stream();
+
+        let flow_ret = match res {
+            Ok(outputs) => self.push_outputs(outputs),
+            Err(flow_error) => return self.handle_flow_error(flow_error),
+        };
+
+        if flow_ret != gst::GST_FLOW_OK {
+            return flow_ret;
+        }
+
+        unsafe {
+            gst::gst_pad_push_event(self.srcpad, gst::gst_event_new_eos());
+        }
+
+        gst::GST_FLOW_EOS
+    }
+
+    fn push_outputs(&self, outputs: Vec<MuxerOutput>) -> gst::GstFlowReturn {
+        let mut skipping = false;
+
+        for output in outputs {
+            match output {
+                MuxerOutput::Caps(caps) => {
+                    debug!(self.logger, "Setting output caps {:?}", caps);
+
+                    self.push_stream_start();
+                    unsafe {
+                        gst::gst_pad_push_event(self.srcpad,
+                                                gst::gst_event_new_caps(caps.as_mut_ptr()));
+                    }
+                    self.state.lock().unwrap().have_caps = true;
+                }
+                MuxerOutput::Seek(offset) => {
+                    skipping = !self.is_downstream_seekable();
+                    if skipping {
+                        warn!(self.logger,
+                              "Downstream is not seekable, can't write at offset {}",
+                              offset);
+                        continue;
+                    }
+
+                    debug!(self.logger, "Writing at offset {}", offset);
+                    self.push_segment(offset);
+                }
+                MuxerOutput::Buffer(buffer) => {
+                    if skipping {
+                        continue;
+                    }
+
+                    let (have_caps, segment_pending) = {
+                        let state = &self.state.lock().unwrap();
+                        (state.have_caps, state.segment_pending)
+                    };
+
+                    if !have_caps {
+                        return self.handle_flow_error(FlowError::NotNegotiated(error_msg!(
+                            MuxerError::Failure,
+                            ["No output caps set before the first buffer"])));
+                    }
+
+                    if segment_pending {
+                        self.push_segment(0);
+                    }
+
+                    trace!(self.logger, "Pushing buffer {:?}", buffer);
+
+                    let flow_ret = unsafe { gst::gst_pad_push(self.srcpad, buffer.into_ptr()) };
+                    if flow_ret != gst::GST_FLOW_OK {
+                        debug!(self.logger, "Failed to push buffer: {:?}", flow_ret);
+                        return flow_ret;
+                    }
+                }
+            }
+        }
+
+        gst::GST_FLOW_OK
+    }
+
+    fn push_stream_start(&self) {
+        {
+            let state = &mut self.state.lock().unwrap();
+            if !state.stream_start_pending {
+                return;
+            }
+            state.stream_start_pending = false;
+        }
+
+        unsafe {
+            let stream_id = gst::gst_pad_create_stream_id(self.srcpad, self.raw, ptr::null());
+            let event = gst::gst_event_new_stream_start(stream_id);
+            glib::g_free(stream_id as glib::gpointer);
+            gst::gst_pad_push_event(self.srcpad, event);
+        }
+    }
+
+    fn push_segment(&self, offset: u64) {
+        self.state.lock().unwrap().segment_pending = false;
+
+        unsafe {
+            let mut segment = mem::zeroed();
+            gst::gst_segment_init(&mut segment, gst::GST_FORMAT_BYTES);
+            segment.start = offset;
+            segment.position = offset;
+            segment.time = offset;
+
+            gst::gst_pad_push_event(self.srcpad, gst::gst_event_new_segment(&segment));
+        }
+    }
+
+    fn is_downstream_seekable(&self) -> bool {
+        if let Some(seekable) = self.state.lock().unwrap().downstream_seekable {
+            return seekable;
+        }
+
+        let seekable = unsafe {
+            let query = gst::gst_query_new_seeking(gst::GST_FORMAT_BYTES);
+
+            let mut seekable = glib::GFALSE;
+            if gst::gst_pad_peer_query(self.srcpad, query) == glib::GTRUE {
+                gst::gst_query_parse_seeking(query,
+                                             ptr::null_mut(),
+                                             &mut seekable,
+                                             ptr::null_mut(),
+                                             ptr::null_mut());
+            }
+            gst::gst_mini_object_unref(query as *mut gst::GstMiniObject);
+
+            seekable == glib::GTRUE
+        };
+
+        debug!(self.logger, "Downstream seekable: {}", seekable);
+        self.state.lock().unwrap().downstream_seekable = Some(seekable);
+
+        seekable
+    }
+
+    fn handle_flow_error(&self, flow_error: FlowError) -> gst::GstFlowReturn {
+        error!(self.logger, "Failed to mux: {:?}", flow_error);
+        match flow_error {
+            FlowError::NotNegotiated(ref msg) |
+            FlowError::Error(ref msg) => self.post_message(msg),
+            _ => (),
+        }
+        flow_error.to_native()
+    }
+
+    fn post_message(&self, msg: &ErrorMessage) {
+        unsafe {
+            msg.post(self.raw);
+        }
+    }
+}
+
+impl Drop for MuxerWrapper {
+    fn drop(&mut self) {
+        unsafe {
+            gst::gst_object_unref(self.collect as *mut gst::GstObject);
+        }
+    }
+}
+
+unsafe extern "C" fn muxer_collected(_pads: *mut gst_base::GstCollectPads,
+                                     user_data: glib::gpointer)
+                                     -> gst::GstFlowReturn {
+    let wrap: &MuxerWrapper = &*(user_data as *const MuxerWrapper);
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        wrap.collected()
+    })
+}
+
+unsafe extern "C" fn muxer_sink_event(_pads: *mut gst_base::GstCollectPads,
+                                      data: *mut gst_base::GstCollectData,
+                                      event: *mut gst::GstEvent,
+                                      user_data: glib::gpointer)
+                                      -> glib::gboolean {
+    let wrap: &MuxerWrapper = &*(user_data as *const MuxerWrapper);
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.sink_event(data as *mut RsCollectData, event) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn muxer_src_event(pad: *mut gst::GstPad,
+                                     parent: *mut gst::GstObject,
+                                     event: *mut gst::GstEvent)
+                                     -> glib::gboolean {
+    let muxer = &*(parent as *const RsMuxer);
+    let wrap: &MuxerWrapper = &*muxer.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.src_event(pad, parent, event) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn muxer_request_new_pad(element: *mut gst::GstElement,
+                                           templ: *mut gst::GstPadTemplate,
+                                           name: *const c_char,
+                                           _caps: *const gst::GstCaps)
+                                           -> *mut gst::GstPad {
+    let muxer = &*(element as *const RsMuxer);
+    let wrap: &MuxerWrapper = &*muxer.wrap;
+
+    panic_to_error!(wrap, ptr::null_mut(), {
+        let name = if name.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(name).to_str().unwrap())
+        };
+
+        wrap.request_new_pad(templ, name)
+    })
+}
+
+unsafe extern "C" fn muxer_release_pad(element: *mut gst::GstElement, pad: *mut gst::GstPad) {
+    let muxer = &*(element as *const RsMuxer);
+    let wrap: &MuxerWrapper = &*muxer.wrap;
+
+    panic_to_error!(wrap, (), {
+        wrap.release_pad(pad);
+    })
+}
+
+unsafe extern "C" fn muxer_change_state(element: *mut gst::GstElement,
+                                        transition: gst::GstStateChange)
+                                        -> gst::GstStateChangeReturn {
+    let muxer = &*(element as *const RsMuxer);
+    let wrap: &MuxerWrapper = &*muxer.wrap;
+
+    panic_to_error!(wrap, gst::GST_STATE_CHANGE_FAILURE, {
+        debug!(wrap.logger, "Changing state {:?}", transition);
+
+        match transition {
+            gst::GST_STATE_CHANGE_READY_TO_PAUSED => {
+                if !wrap.start() {
+                    return gst::GST_STATE_CHANGE_FAILURE;
+                }
+            }
+            // Unblocks the streaming threads before the pads are deactivated
+            gst::GST_STATE_CHANGE_PAUSED_TO_READY => {
+                gst_base::gst_collect_pads_stop(wrap.collect);
+            }
+            _ => (),
+        }
+
+        let muxer_klass = &**(element as *const *const RsMuxerClass);
+        let parent_klass = &*(muxer_klass.parent_vtable as *const gst::GstElementClass);
+        let result = match parent_klass.change_state {
+            Some(change_state) => change_state(element, transition),
+            None => gst::GST_STATE_CHANGE_SUCCESS,
+        };
+
+        if result != gst::GST_STATE_CHANGE_FAILURE &&
+           transition == gst::GST_STATE_CHANGE_PAUSED_TO_READY {
+            // Ignore stop failures
+            wrap.stop();
+        }
+
+        result
+    })
+}
+
+pub struct MuxerInfo {
+    pub name: String,
+    pub long_name: String,
+    pub description: String,
+    pub classification: String,
+    pub author: String,
+    pub rank: u32,
+    pub create_instance: fn(Element) -> Box<Muxer>,
+    pub input_caps: GstRc<Caps>,
+    pub output_caps: GstRc<Caps>,
+}
+
+#[repr(C)]
+struct RsMuxer {
+    parent: gst::GstElement,
+    wrap: *mut MuxerWrapper,
+    muxer_info: *const MuxerInfo,
+}
+
+#[repr(C)]
+struct RsMuxerClass {
+    parent_class: gst::GstElementClass,
+    muxer_info: *const MuxerInfo,
+    parent_vtable: glib::gconstpointer,
+}
+
+unsafe extern "C" fn muxer_finalize(obj: *mut gobject::GObject) {
+    let muxer = &mut *(obj as *mut RsMuxer);
+
+    drop(Box::from_raw(muxer.wrap));
+
+    let muxer_klass = &**(obj as *const *const RsMuxerClass);
+    let parent_klass = &*(muxer_klass.parent_vtable as *const gobject::GObjectClass);
+    parent_klass.finalize.map(|f| f(obj));
+}
+
+unsafe extern "C" fn muxer_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
+    let muxer_klass = &mut *(klass as *mut RsMuxerClass);
+    let muxer_info = &*(klass_data as *const MuxerInfo);
+
+    {
+        let gobject_klass = &mut muxer_klass.parent_class.parent_class.parent_class;
+        gobject_klass.finalize = Some(muxer_finalize);
+    }
+
+    {
+        let element_klass = &mut muxer_klass.parent_class;
+        element_klass.change_state = Some(muxer_change_state);
+        element_klass.request_new_pad = Some(muxer_request_new_pad);
+        element_klass.release_pad = Some(muxer_release_pad);
+
+        let longname_cstr = CString::new(muxer_info.long_name.clone()).unwrap();
+        let classification_cstr = CString::new(muxer_info.classification.clone()).unwrap();
+        let description_cstr = CString::new(muxer_info.description.clone()).unwrap();
+        let author_cstr = CString::new(muxer_info.author.clone()).unwrap();
+
+        gst::gst_element_class_set_static_metadata(element_klass,
+                                                   longname_cstr.into_raw(),
+                                                   classification_cstr.into_raw(),
+                                                   description_cstr.into_raw(),
+                                                   author_cstr.into_raw());
+
+        let templ_name = CString::new("sink_%u").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SINK,
+                                                     gst::GST_PAD_REQUEST,
+                                                     muxer_info.input_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+
+        let templ_name = CString::new("src").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SRC,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     muxer_info.output_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+    }
+
+    muxer_klass.muxer_info = muxer_info;
+    muxer_klass.parent_vtable = gobject::g_type_class_peek_parent(klass);
+}
+
+unsafe extern "C" fn muxer_init(instance: *mut gobject::GTypeInstance, klass: glib::gpointer) {
+    let muxer = &mut *(instance as *mut RsMuxer);
+    let muxer_klass = &*(klass as *const RsMuxerClass);
+    let muxer_info = &*muxer_klass.muxer_info;
+
+    muxer.muxer_info = muxer_info;
+
+    let element = &mut muxer.parent as *mut gst::GstElement;
+
+    let templ_name = CString::new("src").unwrap();
+    let templ = gst::gst_element_class_get_pad_template(klass as *mut gst::GstElementClass,
+                                                        templ_name.as_ptr());
+    let srcpad = gst::gst_pad_new_from_template(templ, templ_name.as_ptr());
+
+    gst::gst_pad_set_event_function_full(srcpad, Some(muxer_src_event), ptr::null_mut(), None);
+    gst::gst_element_add_pad(element, srcpad);
+
+    let collect = gst_base::gst_collect_pads_new();
+
+    let wrap = Box::new(MuxerWrapper::new(element,
+                                          srcpad,
+                                          collect,
+                                          (muxer_info.create_instance)(Element::new(element))));
+    muxer.wrap = Box::into_raw(wrap);
+
+    gst_base::gst_collect_pads_set_function(collect,
+                                            Some(muxer_collected),
+                                            muxer.wrap as glib::gpointer);
+    gst_base::gst_collect_pads_set_event_function(collect,
+                                                  Some(muxer_sink_event),
+                                                  muxer.wrap as glib::gpointer);
+    gst_base::gst_collect_pads_set_clip_function(collect,
+                                                 Some(gst_base::gst_collect_pads_clip_running_time),
+                                                 ptr::null_mut());
+}
+
+pub fn muxer_register(plugin: &Plugin, muxer_info: MuxerInfo) {
+    unsafe {
+        let parent_type = gst::gst_element_get_type();
+        let mut type_name = String::from("RsMuxer-");
+        type_name.push_str(&muxer_info.name);
+        let type_name_cstr = CString::new(type_name.into_bytes()).unwrap();
+
+        let name_cstr = CString::new(muxer_info.name.clone().into_bytes()).unwrap();
+        let rank = muxer_info.rank;
+
+        let muxer_info = Box::new(muxer_info);
+        let muxer_info_ptr = Box::into_raw(muxer_info) as glib::gpointer;
+
+        let type_info = gobject::GTypeInfo {
+            class_size: mem::size_of::<RsMuxerClass>() as u16,
+            base_init: None,
+            base_finalize: None,
+            class_init: Some(muxer_class_init),
+            class_finalize: None,
+            class_data: muxer_info_ptr,
+            instance_size: mem::size_of::<RsMuxer>() as u16,
+            n_preallocs: 0,
+            instance_init: Some(muxer_init),
+            value_table: ptr::null(),
+        };
+
+        let type_ = gobject::g_type_register_static(parent_type,
+                                                    type_name_cstr.as_ptr(),
+                                                    &type_info,
+                                                    gobject::GTypeFlags::empty());
+
+        gst::gst_element_register(plugin.as_ptr(), name_cstr.as_ptr(), rank, type_);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use gst;
+
+    struct TestMuxer;
+
+    impl Muxer for TestMuxer {
+        fn start(&mut self) -> Result<(), ErrorMessage> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), ErrorMessage> {
+            Ok(())
+        }
+
+        fn set_caps(&mut self, _index: PadIndex, _caps: &Caps) -> Result<(), ErrorMessage> {
+            Ok(())
+        }
+
+        fn handle_buffer(&mut self,
+                         _index: PadIndex,
+                         _buffer: GstRc<Buffer>)
+                         -> Result<Vec<MuxerOutput>, FlowError> {
+            Ok(Vec::new())
+        }
+
+        fn end_of_stream(&mut self) -> Result<Vec<MuxerOutput>, FlowError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn init() {
+        unsafe {
+            gst::gst_init(ptr::null_mut(), ptr::null_mut());
+        }
+    }
+
+    // Wrapper around a bin with an active but unlinked source pad
+    fn new_wrapper() -> MuxerWrapper {
+        unsafe {
+            let element = gst::gst_bin_new(ptr::null()) as *mut gst::GstElement;
+            let srcpad = gst::gst_pad_new(b"src\0".as_ptr() as *const c_char, gst::GST_PAD_SRC);
+            gst::gst_pad_set_active(srcpad, glib::GTRUE);
+            gst::gst_element_add_pad(element, srcpad);
+
+            MuxerWrapper::new(element,
+                              srcpad,
+                              gst_base::gst_collect_pads_new(),
+                              Box::new(TestMuxer))
+        }
+    }
+
+    #[test]
+    fn test_choose_next() {
+        let mut muxer = TestMuxer;
+
+        assert_eq!(muxer.choose_next(&[(0, Some(20)), (1, Some(10)), (2, Some(30))]),
+                   Some(1));
+        assert_eq!(muxer.choose_next(&[(0, Some(20)), (1, None), (2, Some(10))]),
+                   Some(1));
+        assert_eq!(muxer.choose_next(&[(3, Some(10))]), Some(3));
+        assert_eq!(muxer.choose_next(&[]), None);
+    }
+
+    #[test]
+    fn test_push_outputs_without_caps() {
+        init();
+
+        let wrap = new_wrapper();
+        let outputs = vec![MuxerOutput::Buffer(Buffer::new_with_size(10).unwrap())];
+
+        assert_eq!(wrap.push_outputs(outputs), gst::GST_FLOW_NOT_NEGOTIATED);
+    }
+
+    #[test]
+    fn test_push_outputs() {
+        init();
+
+        let wrap = new_wrapper();
+        let caps = Caps::new_simple("foo/bar", &[]);
+        let outputs = vec![MuxerOutput::Caps(caps.clone()),
+                           MuxerOutput::Buffer(Buffer::new_with_size(10).unwrap())];
+
+        // Nothing is linked downstream but all sticky events are stored on
+        // the source pad before the buffer
+        assert_eq!(wrap.push_outputs(outputs), gst::GST_FLOW_NOT_LINKED);
+
+        {
+            let state = wrap.state.lock().unwrap();
+            assert!(state.have_caps);
+            assert!(!state.stream_start_pending);
+            assert!(!state.segment_pending);
+        }
+
+        unsafe {
+            let current_caps =
+                GstRc::<Caps>::from_owned_ptr(gst::gst_pad_get_current_caps(wrap.srcpad));
+            assert_eq!(current_caps.as_ref(), caps.as_ref());
+
+            for event_type in &[gst::GST_EVENT_STREAM_START, gst::GST_EVENT_SEGMENT] {
+                let event = gst::gst_pad_get_sticky_event(wrap.srcpad, *event_type, 0);
+                assert!(!event.is_null());
+                gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+            }
+        }
+    }
+}
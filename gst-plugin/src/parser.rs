@@ -0,0 +1,510 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ffi::CString;
+use std::ptr;
+use std::mem;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use num_rational::Rational32;
+
+use slog::Logger;
+
+use utils::*;
+use error::*;
+use buffer::*;
+use miniobject::*;
+use log::*;
+use plugin::Plugin;
+use caps::*;
+
+use glib;
+use gobject;
+use gst;
+use gst_base;
+
+#[derive(Debug)]
+pub enum ParserError {
+    Failure,
+    ParseFailed,
+}
+
+impl ToGError for ParserError {
+    fn to_gerror(&self) -> (u32, i32) {
+        match *self {
+            ParserError::Failure => (gst_library_error_domain(), 1),
+            ParserError::ParseFailed => (gst_stream_error_domain(), 11),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckFrameResult {
+    // A valid frame of the given size starts at the beginning of the data
+    Valid(usize),
+    // More data is needed, optionally at least the given number of bytes
+    NeedMoreData(Option<u32>),
+    // Skip the given number of bytes, e.g. to resync
+    Skip(usize),
+}
+
+// A frame that was found by check_valid_frame() and is about to be pushed
+// downstream
+pub struct Frame {
+    parse: *mut gst_base::GstBaseParse,
+    frame: *mut gst_base::GstBaseParseFrame,
+}
+
+impl Frame {
+    pub fn get_buffer(&self) -> &Buffer {
+        unsafe { Buffer::from_ptr((*self.frame).buffer) }
+    }
+
+    pub fn get_mut_buffer(&mut self) -> &mut Buffer {
+        unsafe { <Buffer as MiniObject>::from_mut_ptr((*self.frame).buffer) }
+    }
+
+    pub fn get_offset(&self) -> u64 {
+        unsafe { (*self.frame).offset }
+    }
+
+    pub fn set_pts(&mut self, pts: Option<u64>) {
+        self.get_mut_buffer().set_pts(pts);
+    }
+
+    pub fn set_dts(&mut self, dts: Option<u64>) {
+        self.get_mut_buffer().set_dts(dts);
+    }
+
+    pub fn set_duration(&mut self, duration: Option<u64>) {
+        self.get_mut_buffer().set_duration(duration);
+    }
+
+    pub fn set_src_caps(&mut self, caps: &Caps) {
+        unsafe {
+            gst::gst_pad_push_event((*self.parse).srcpad,
+                                    gst::gst_event_new_caps(caps.as_mut_ptr()));
+        }
+    }
+
+    // Allows the base class to interpolate timestamps and durations
+    pub fn set_frame_rate(&mut self, frame_rate: Rational32, lead_in: u32, lead_out: u32) {
+        unsafe {
+            gst_base::gst_base_parse_set_frame_rate(self.parse,
+                                                    *frame_rate.numer() as u32,
+                                                    *frame_rate.denom() as u32,
+                                                    lead_in,
+                                                    lead_out);
+        }
+    }
+
+    pub fn set_stream_duration(&mut self, duration: Option<u64>) {
+        unsafe {
+            gst_base::gst_base_parse_set_duration(self.parse,
+                                                  gst::GST_FORMAT_TIME,
+                                                  duration.map(|d| d as i64).unwrap_or(-1),
+                                                  0);
+        }
+    }
+
+    pub fn set_average_bitrate(&mut self, bitrate: u32) {
+        unsafe {
+            gst_base::gst_base_parse_set_average_bitrate(self.parse, bitrate);
+        }
+    }
+}
+
+pub struct ParserWrapper {
+    raw: *mut gst::GstElement,
+    logger: Logger,
+    parser: Mutex<Box<Parser>>,
+    panicked: AtomicBool,
+}
+
+pub trait Parser {
+    fn start(&mut self) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn set_sink_caps(&mut self, _caps: &Caps) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // data contains all currently available bytes, draining is set at EOS
+    // when no more data will follow
+    fn check_valid_frame(&mut self, data: &[u8], draining: bool) -> CheckFrameResult;
+
+    fn handle_frame(&mut self, frame: &mut Frame) -> Result<(), FlowError>;
+}
+
+impl ParserWrapper {
+    fn new(raw: *mut gst::GstElement, parser: Box<Parser>) -> ParserWrapper {
+        ParserWrapper {
+            raw: raw,
+            logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
+                                                    "rsparse",
+                                                    0,
+                                                    "Rust parser base class"),
+                                 o!()),
+            parser: Mutex::new(parser),
+            panicked: AtomicBool::new(false),
+        }
+    }
+
+    fn start(&self) -> bool {
+        let parser = &mut self.parser.lock().unwrap();
+
+        debug!(self.logger, "Starting");
+
+        match parser.start() {
+            Ok(..) => {
+                trace!(self.logger, "Started successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to start: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn stop(&self) -> bool {
+        let parser = &mut self.parser.lock().unwrap();
+
+        debug!(self.logger, "Stopping");
+
+        match parser.stop() {
+            Ok(..) => {
+                trace!(self.logger, "Stopped successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to stop: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn set_sink_caps(&self, caps: &Caps) -> bool {
+        let parser = &mut self.parser.lock().unwrap();
+
+        debug!(self.logger, "Setting sink caps {:?}", caps);
+
+        match parser.set_sink_caps(caps) {
+            Ok(..) => true,
+            Err(ref msg) => {
+                error!(self.logger, "Failed to set sink caps: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn handle_frame(&self,
+                    parse: *mut gst_base::GstBaseParse,
+                    frame: *mut gst_base::GstBaseParseFrame,
+                    skipsize: &mut i32)
+                    -> gst::GstFlowReturn {
+        let mut parser = self.parser.lock().unwrap();
+
+        let draining = unsafe {
+            (*parse).flags & (gst_base::GST_BASE_PARSE_FLAG_DRAINING as u32) != 0
+        };
+
+        let (res, available) = {
+            let buffer = unsafe { Buffer::from_ptr((*frame).buffer) };
+            let map = match buffer.map_read() {
+                None => {
+                    error!(self.logger, "Failed to map buffer");
+                    self.post_message(&error_msg!(ParserError::Failure,
+                                                  ["Failed to map buffer"]));
+                    return gst::GST_FLOW_ERROR;
+                }
+                Some(map) => map,
+            };
+
+            let data = map.as_slice();
+            (parser.check_valid_frame(data, draining), data.len())
+        };
+
+        trace!(self.logger,
+               "Checked frame at offset {}: {:?}",
+               unsafe { (*frame).offset },
+               res);
+
+        match res {
+            CheckFrameResult::NeedMoreData(min_size) => {
+                if let Some(min_size) = min_size {
+                    unsafe {
+                        gst_base::gst_base_parse_set_min_frame_size(parse, min_size);
+                    }
+                }
+                *skipsize = 0;
+                gst::GST_FLOW_OK
+            }
+            CheckFrameResult::Skip(size) => {
+                debug!(self.logger, "Skipping {} bytes", size);
+                *skipsize = size as i32;
+                gst::GST_FLOW_OK
+            }
+            CheckFrameResult::Valid(size) if size > available => {
+                error!(self.logger,
+                       "Frame size {} larger than the {} available bytes",
+                       size,
+                       available);
+                self.post_message(&error_msg!(ParserError::ParseFailed,
+                                              ["Frame size {} larger than the {} available bytes",
+                                               size,
+                                               available]));
+                gst::GST_FLOW_ERROR
+            }
+            CheckFrameResult::Valid(size) => {
+                let mut frame_wrapper = Frame {
+                    parse: parse,
+                    frame: frame,
+                };
+
+                let res = parser.handle_frame(&mut frame_wrapper);
+
+                // Finishing the frame pushes it downstream
+                drop(parser);
+
+                match res {
+                    Ok(()) => unsafe {
+                        gst_base::gst_base_parse_finish_frame(parse, frame, size as i32)
+                    },
+                    Err(flow_error) => {
+                        error!(self.logger, "Failed to handle frame: {:?}", flow_error);
+                        match flow_error {
+                            FlowError::NotNegotiated(ref msg) |
+                            FlowError::Error(ref msg) => self.post_message(msg),
+                            _ => (),
+                        }
+                        flow_error.to_native()
+                    }
+                }
+            }
+        }
+    }
+
+    fn post_message(&self, msg: &ErrorMessage) {
+        unsafe {
+            msg.post(self.raw);
+        }
+    }
+}
+
+unsafe extern "C" fn parser_start(ptr: *mut gst_base::GstBaseParse) -> glib::gboolean {
+    let parser = &*(ptr as *const RsParser);
+    let wrap: &ParserWrapper = &*parser.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.start() {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn parser_stop(ptr: *mut gst_base::GstBaseParse) -> glib::gboolean {
+    let parser = &*(ptr as *const RsParser);
+    let wrap: &ParserWrapper = &*parser.wrap;
+
+    panic_to_error!(wrap, glib::GTRUE, {
+        if wrap.stop() {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn parser_set_sink_caps(ptr: *mut gst_base::GstBaseParse,
+                                          caps: *mut gst::GstCaps)
+                                          -> glib::gboolean {
+    let parser = &*(ptr as *const RsParser);
+    let wrap: &ParserWrapper = &*parser.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.set_sink_caps(Caps::from_ptr(caps)) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn parser_handle_frame(ptr: *mut gst_base::GstBaseParse,
+                                         frame: *mut gst_base::GstBaseParseFrame,
+                                         skipsize: *mut i32)
+                                         -> gst::GstFlowReturn {
+    let parser = &*(ptr as *const RsParser);
+    let wrap: &ParserWrapper = &*parser.wrap;
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        wrap.handle_frame(ptr, frame, &mut *skipsize)
+    })
+}
+
+pub struct ParserInfo {
+    pub name: String,
+    pub long_name: String,
+    pub description: String,
+    pub classification: String,
+    pub author: String,
+    pub rank: u32,
+    pub create_instance: fn(Element) -> Box<Parser>,
+    pub input_caps: GstRc<Caps>,
+    pub output_caps: GstRc<Caps>,
+    pub min_frame_size: Option<u32>,
+}
+
+#[repr(C)]
+struct RsParser {
+    parent: gst_base::GstBaseParse,
+    wrap: *mut ParserWrapper,
+    parser_info: *const ParserInfo,
+}
+
+#[repr(C)]
+struct RsParserClass {
+    parent_class: gst_base::GstBaseParseClass,
+    parser_info: *const ParserInfo,
+    parent_vtable: glib::gconstpointer,
+}
+
+unsafe extern "C" fn parser_finalize(obj: *mut gobject::GObject) {
+    let parser = &mut *(obj as *mut RsParser);
+
+    drop(Box::from_raw(parser.wrap));
+
+    let parser_klass = &**(obj as *const *const RsParserClass);
+    let parent_klass = &*(parser_klass.parent_vtable as *const gobject::GObjectClass);
+    parent_klass.finalize.map(|f| f(obj));
+}
+
+unsafe extern "C" fn parser_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
+    let parser_klass = &mut *(klass as *mut RsParserClass);
+    let parser_info = &*(klass_data as *const ParserInfo);
+
+    {
+        let gobject_klass = &mut parser_klass
+                                     .parent_class
+                                     .parent_class
+                                     .parent_class
+                                     .parent_class;
+        gobject_klass.finalize = Some(parser_finalize);
+    }
+
+    {
+        let element_klass = &mut parser_klass.parent_class.parent_class;
+
+        let longname_cstr = CString::new(parser_info.long_name.clone()).unwrap();
+        let classification_cstr = CString::new(parser_info.classification.clone()).unwrap();
+        let description_cstr = CString::new(parser_info.description.clone()).unwrap();
+        let author_cstr = CString::new(parser_info.author.clone()).unwrap();
+
+        gst::gst_element_class_set_static_metadata(element_klass,
+                                                   longname_cstr.into_raw(),
+                                                   classification_cstr.into_raw(),
+                                                   description_cstr.into_raw(),
+                                                   author_cstr.into_raw());
+
+        let templ_name = CString::new("sink").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SINK,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     parser_info.input_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+
+        let templ_name = CString::new("src").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SRC,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     parser_info.output_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+    }
+
+    {
+        let baseparse_klass = &mut parser_klass.parent_class;
+        baseparse_klass.start = Some(parser_start);
+        baseparse_klass.stop = Some(parser_stop);
+        baseparse_klass.set_sink_caps = Some(parser_set_sink_caps);
+        baseparse_klass.handle_frame = Some(parser_handle_frame);
+    }
+
+    parser_klass.parser_info = parser_info;
+    parser_klass.parent_vtable = gobject::g_type_class_peek_parent(klass);
+}
+
+unsafe extern "C" fn parser_init(instance: *mut gobject::GTypeInstance, klass: glib::gpointer) {
+    let parser = &mut *(instance as *mut RsParser);
+    let parser_klass = &*(klass as *const RsParserClass);
+    let parser_info = &*parser_klass.parser_info;
+
+    parser.parser_info = parser_info;
+
+    let wrap = Box::new(ParserWrapper::new(&mut parser.parent.element,
+            (parser_info.create_instance)(Element::new(&mut parser.parent.element))));
+    parser.wrap = Box::into_raw(wrap);
+
+    if let Some(min_frame_size) = parser_info.min_frame_size {
+        gst_base::gst_base_parse_set_min_frame_size(&mut parser.parent, min_frame_size);
+    }
+}
+
+pub fn parser_register(plugin: &Plugin, parser_info: ParserInfo) {
+    unsafe {
+        let parent_type = gst_base::gst_base_parse_get_type();
+        let mut type_name = String::from("RsParser-");
+        type_name.push_str(&parser_info.name);
+        let type_name_cstr = CString::new(type_name.into_bytes()).unwrap();
+
+        let name_cstr = CString::new(parser_info.name.clone().into_bytes()).unwrap();
+        let rank = parser_info.rank;
+
+        let parser_info = Box::new(parser_info);
+        let parser_info_ptr = Box::into_raw(parser_info) as glib::gpointer;
+
+        let type_info = gobject::GTypeInfo {
+            class_size: mem::size_of::<RsParserClass>() as u16,
+            base_init: None,
+            base_finalize: None,
+            class_init: Some(parser_class_init),
+            class_finalize: None,
+            class_data: parser_info_ptr,
+            instance_size: mem::size_of::<RsParser>() as u16,
+            n_preallocs: 0,
+            instance_init: Some(parser_init),
+            value_table: ptr::null(),
+        };
+
+        let type_ = gobject::g_type_register_static(parent_type,
+                                                    type_name_cstr.as_ptr(),
+                                                    &type_info,
+                                                    gobject::GTypeFlags::empty());
+
+        gst::gst_element_register(plugin.as_ptr(), name_cstr.as_ptr(), rank, type_);
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::mem;
+use std::sync::Mutex;
+
+use value::*;
+use caps::Caps;
+
+use glib;
+use gobject;
+use gst;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyMutability {
+    Readable,
+    Writable,
+    ReadWrite,
+}
+
+impl PropertyMutability {
+    fn to_native(&self) -> gobject::GParamFlags {
+        match *self {
+            PropertyMutability::Readable => gobject::G_PARAM_READABLE,
+            PropertyMutability::Writable => gobject::G_PARAM_WRITABLE,
+            PropertyMutability::ReadWrite => gobject::G_PARAM_READWRITE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyType {
+    // Default value
+    Boolean(bool),
+    // Minimum and maximum, default value
+    Int((i32, i32), i32),
+    UInt64((u64, u64), u64),
+    String(Option<String>),
+    // Possible values as (value, name, nick), default value. The values are
+    // passed around as Value::Int
+    Enum(Vec<(i32, String, String)>, i32),
+    Caps,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    pub nick: String,
+    pub blurb: String,
+    pub property_type: PropertyType,
+    pub mutability: PropertyMutability,
+}
+
+unsafe fn register_enum(klass: *mut gobject::GObjectClass,
+                        property: &Property,
+                        values: &[(i32, String, String)])
+                        -> glib::GType {
+    let class_type = (*(klass as *const gobject::GTypeClass)).g_type;
+    let class_name = CStr::from_ptr(gobject::g_type_name(class_type)).to_string_lossy();
+    let type_name_cstr = CString::new(format!("{}-{}", class_name, property.name)).unwrap();
+
+    // The enum values have to stay around as long as the type exists
+    let mut enum_values = Vec::with_capacity(values.len() + 1);
+    for &(value, ref name, ref nick) in values {
+        enum_values.push(gobject::GEnumValue {
+                             value: value,
+                             value_name: CString::new(name.as_str()).unwrap().into_raw(),
+                             value_nick: CString::new(nick.as_str()).unwrap().into_raw(),
+                         });
+    }
+    enum_values.push(gobject::GEnumValue {
+                         value: 0,
+                         value_name: ptr::null(),
+                         value_nick: ptr::null(),
+                     });
+
+    let enum_values = Box::into_raw(enum_values.into_boxed_slice());
+    gobject::g_enum_register_static(type_name_cstr.as_ptr(),
+                                    (*enum_values).as_ptr())
+}
+
+// Installs the properties with consecutive ids starting at first_id
+pub unsafe fn install_properties(klass: *mut gobject::GObjectClass,
+                                 first_id: u32,
+                                 properties: &[Property]) {
+    for (i, property) in properties.iter().enumerate() {
+        let name_cstr = CString::new(property.name.as_str()).unwrap();
+        let nick_cstr = CString::new(property.nick.as_str()).unwrap();
+        let blurb_cstr = CString::new(property.blurb.as_str()).unwrap();
+        let flags = property.mutability.to_native();
+
+        let pspec = match property.property_type {
+            PropertyType::Boolean(default) => {
+                gobject::g_param_spec_boolean(name_cstr.as_ptr(),
+                                              nick_cstr.as_ptr(),
+                                              blurb_cstr.as_ptr(),
+                                              if default {
+                                                  glib::GTRUE
+                                              } else {
+                                                  glib::GFALSE
+                                              },
+                                              flags)
+            }
+            PropertyType::Int((min, max), default) => {
+                gobject::g_param_spec_int(name_cstr.as_ptr(),
+                                          nick_cstr.as_ptr(),
+                                          blurb_cstr.as_ptr(),
+                                          min,
+                                          max,
+                                          default,
+                                          flags)
+            }
+            PropertyType::UInt64((min, max), default) => {
+                gobject::g_param_spec_uint64(name_cstr.as_ptr(),
+                                             nick_cstr.as_ptr(),
+                                             blurb_cstr.as_ptr(),
+                                             min,
+                                             max,
+                                             default,
+                                             flags)
+            }
+            PropertyType::String(ref default) => {
+                let default_cstr = default.as_ref().map(|d| CString::new(d.as_str()).unwrap());
+                gobject::g_param_spec_string(name_cstr.as_ptr(),
+                                             nick_cstr.as_ptr(),
+                                             blurb_cstr.as_ptr(),
+                                             default_cstr
+                                                 .as_ref()
+                                                 .map(|d| d.as_ptr())
+                                                 .unwrap_or(ptr::null()),
+                                             flags)
+            }
+            PropertyType::Enum(ref values, default) => {
+                let enum_type = register_enum(klass, property, values);
+                gobject::g_param_spec_enum(name_cstr.as_ptr(),
+                                           nick_cstr.as_ptr(),
+                                           blurb_cstr.as_ptr(),
+                                           enum_type,
+                                           default,
+                                           flags)
+            }
+            PropertyType::Caps => {
+                gobject::g_param_spec_boxed(name_cstr.as_ptr(),
+                                            nick_cstr.as_ptr(),
+                                            blurb_cstr.as_ptr(),
+                                            gst::gst_caps_get_type(),
+                                            flags)
+            }
+        };
+
+        gobject::g_object_class_install_property(klass, first_id + i as u32, pspec);
+    }
+}
+
+pub unsafe fn value_from_property_gvalue(property: &Property,
+                                         value: *const gobject::GValue)
+                                         -> Value {
+    match property.property_type {
+        PropertyType::Enum(..) => Value::from(gobject::g_value_get_enum(value)),
+        // NULL strings reset to the default value, if any
+        PropertyType::String(Some(ref default)) if gobject::g_value_get_string(value)
+                                                       .is_null() => {
+            Value::from(default.as_str())
+        }
+        // NULL caps are passed as ANY caps, like capsfilter does
+        PropertyType::Caps if gobject::g_value_get_boxed(value).is_null() => {
+            Value::from(Caps::new_any())
+        }
+        _ => Value::from_ptr(value).unwrap(),
+    }
+}
+
+// Returns false if the value has the wrong type for the property
+pub unsafe fn value_to_property_gvalue(property: &Property,
+                                       v: &Value,
+                                       value: *mut gobject::GValue)
+                                       -> bool {
+    match property.property_type {
+        PropertyType::Enum(..) => {
+            match v.try_get::<i32>() {
+                Some(v) => {
+                    gobject::g_value_set_enum(value, v);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => {
+            if (*v.as_ptr()).g_type != (*value).g_type {
+                return false;
+            }
+
+            gobject::g_value_copy(v.as_ptr(), value);
+            true
+        }
+    }
+}
+
+// Property values of an element, kept outside the implementation so that
+// they can be read and changed while it is busy, e.g. in fill(). Changes
+// are queued until the wrapper passes them to the implementation
+pub struct PropertyStore {
+    values: Mutex<Vec<(String, Option<Value>)>>,
+    pending: Mutex<Vec<(String, Value)>>,
+}
+
+impl PropertyStore {
+    pub fn new<F>(properties: &[Property], get_property: F) -> PropertyStore
+        where F: Fn(&str) -> Option<Value>
+    {
+        PropertyStore {
+            values: Mutex::new(properties
+                                   .iter()
+                                   .map(|property| {
+                                            (property.name.clone(), get_property(&property.name))
+                                        })
+                                   .collect()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.values
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|&&(ref n, _)| n == name)
+            .and_then(|&(_, ref value)| value.clone())
+    }
+
+    pub fn set(&self, name: &str, value: &Value) {
+        let values = &mut self.values.lock().unwrap();
+        if let Some(&mut (_, ref mut v)) = values.iter_mut().find(|&&mut (ref n, _)| n == name) {
+            *v = Some(value.clone());
+        }
+
+        self.pending
+            .lock()
+            .unwrap()
+            .push((String::from(name), value.clone()));
+    }
+
+    // Changes that were not passed to the implementation yet, in order
+    pub fn take_pending(&self) -> Vec<(String, Value)> {
+        mem::replace(&mut *self.pending.lock().unwrap(), Vec::new())
+    }
+
+    // Reads the values from the implementation again, except for the ones
+    // with changes that it did not see yet
+    pub fn update<F>(&self, get_property: F)
+        where F: Fn(&str) -> Option<Value>
+    {
+        let values = &mut self.values.lock().unwrap();
+        let pending = &self.pending.lock().unwrap();
+
+        for &mut (ref name, ref mut value) in values.iter_mut() {
+            if !pending.iter().any(|&(ref n, _)| n == name) {
+                *value = get_property(name);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::slice;
+use std::panic::{self, AssertUnwindSafe};
+
+use value::*;
+use caps::Caps;
+use miniobject::*;
+use log::*;
+
+use slog::Logger;
+
+use glib;
+use gobject;
+
+lazy_static! {
+    static ref LOGGER: Logger = {
+        Logger::root(GstDebugDrain::new(None,
+                                        "rssignals",
+                                        0,
+                                        "Rust signals"),
+                    o!())
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalType {
+    Bool,
+    Int,
+    UInt,
+    Int64,
+    UInt64,
+    String,
+    Caps,
+}
+
+impl SignalType {
+    fn to_g_type(&self) -> glib::GType {
+        match *self {
+            SignalType::Bool => <bool as ValueType>::g_type(),
+            SignalType::Int => <i32 as ValueType>::g_type(),
+            SignalType::UInt => <u32 as ValueType>::g_type(),
+            SignalType::Int64 => <i64 as ValueType>::g_type(),
+            SignalType::UInt64 => <u64 as ValueType>::g_type(),
+            SignalType::String => <&str as ValueType>::g_type(),
+            SignalType::Caps => <GstRc<Caps> as ValueType>::g_type(),
+        }
+    }
+}
+
+// Returned by Element::emit_signal if the signal can't be emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitSignalError {
+    UnknownSignal,
+    // Expected number of arguments
+    WrongNumberOfArguments(usize),
+    // Index of the first argument with the wrong type
+    WrongArgumentType(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signal {
+    pub name: String,
+    pub arg_types: Vec<SignalType>,
+    pub return_type: Option<SignalType>,
+    // Action signals are emitted by the application and handled by the
+    // element, all others are emitted by the element via Element::emit_signal
+    pub action: bool,
+}
+
+pub type ActionSignalHandler = unsafe fn(*mut gobject::GObject, &Signal, &[Value])
+                                         -> Option<Value>;
+
+struct ActionSignalData {
+    handler: ActionSignalHandler,
+    signal: *const Signal,
+}
+
+unsafe extern "C" fn action_signal_callback() {}
+
+unsafe extern "C" fn action_signal_marshal(_closure: *mut gobject::GClosure,
+                                           return_value: *mut gobject::GValue,
+                                           n_param_values: u32,
+                                           param_values: *const gobject::GValue,
+                                           _invocation_hint: glib::gpointer,
+                                           marshal_data: glib::gpointer) {
+    // Nothing must unwind into the signal emission, and the handlers catch
+    // their own panics already
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        if param_values.is_null() || n_param_values == 0 {
+            return;
+        }
+
+        let data = &*(marshal_data as *const ActionSignalData);
+        let param_values = slice::from_raw_parts(param_values, n_param_values as usize);
+
+        let obj = gobject::g_value_get_object(&param_values[0]) as *mut gobject::GObject;
+        let mut args = Vec::with_capacity(param_values.len() - 1);
+        for (i, v) in param_values[1..].iter().enumerate() {
+            match Value::from_ptr(v) {
+                Some(arg) => args.push(arg),
+                None => {
+                    let type_name = CStr::from_ptr(gobject::g_type_name(v.g_type));
+                    warn!(LOGGER,
+                          "Not handling signal {}: Unsupported type {} of argument {}",
+                          (*data.signal).name,
+                          type_name.to_string_lossy(),
+                          i);
+                    return;
+                }
+            }
+        }
+
+        if let Some(res) = (data.handler)(obj, &*data.signal, &args) {
+            if !return_value.is_null() && (*res.as_ptr()).g_type == (*return_value).g_type {
+                gobject::g_value_copy(res.as_ptr(), return_value);
+            }
+        }
+    }));
+}
+
+// Registers the signals on the class, action signals are passed to handler
+pub unsafe fn install_signals(klass: glib::gpointer,
+                              signals: &[Signal],
+                              handler: ActionSignalHandler) {
+    let type_ = (*(klass as *const gobject::GTypeClass)).g_type;
+
+    for signal in signals {
+        let name_cstr = CString::new(signal.name.as_str()).unwrap();
+        let mut param_types = signal
+            .arg_types
+            .iter()
+            .map(|t| t.to_g_type())
+            .collect::<Vec<_>>();
+        let return_type = signal
+            .return_type
+            .map(|t| t.to_g_type())
+            .unwrap_or(gobject::G_TYPE_NONE);
+
+        let (flags, class_closure) = if signal.action {
+            // The closure and its data stay around as long as the class
+            let data = Box::new(ActionSignalData {
+                                    handler: handler,
+                                    signal: signal,
+                                });
+            let closure =
+                gobject::g_cclosure_new(Some(action_signal_callback), ptr::null_mut(), None);
+            gobject::g_closure_set_meta_marshal(closure,
+                                                Box::into_raw(data) as glib::gpointer,
+                                                Some(action_signal_marshal));
+
+            (gobject::G_SIGNAL_RUN_LAST | gobject::G_SIGNAL_ACTION, closure)
+        } else {
+            (gobject::G_SIGNAL_RUN_LAST, ptr::null_mut())
+        };
+
+        gobject::g_signal_newv(name_cstr.as_ptr(),
+                               type_,
+                               flags,
+                               class_closure,
+                               None,
+                               ptr::null_mut(),
+                               None,
+                               return_type,
+                               param_types.len() as u32,
+                               param_types.as_mut_ptr());
+    }
+}
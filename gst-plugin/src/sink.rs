@@ -11,10 +11,11 @@ use libc::c_char;
 use std::ffi::{CStr, CString};
 use std::ptr;
 use std::mem;
+use std::u64;
 
 use std::panic::{self, AssertUnwindSafe};
 
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use url::Url;
@@ -24,10 +25,16 @@ use slog::Logger;
 use utils::*;
 use error::*;
 use buffer::*;
+use bufferlist::BufferList;
 use miniobject::*;
 use log::*;
 use plugin::Plugin;
 use caps::*;
+use value::*;
+use properties::*;
+use signals::*;
+use tags::TagList;
+use structure::Structure;
 
 use glib;
 use gobject;
@@ -55,26 +62,166 @@ impl ToGError for SinkError {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentFormat {
+    Bytes,
+    Time,
+    Other,
+}
+
+// Serialized events, received in the streaming thread between buffers
+#[derive(Debug)]
+pub enum SinkEvent<'a> {
+    FlushStop,
+    Segment {
+        format: SegmentFormat,
+        start: u64,
+        stop: Option<u64>,
+        time: u64,
+        rate: f64,
+    },
+    Tag(&'a TagList),
+    Eos,
+    Custom(&'a Structure),
+}
+
+impl<'a> SinkEvent<'a> {
+    unsafe fn from_native(event: *mut gst::GstEvent) -> Option<SinkEvent<'a>> {
+        match (*event).type_ {
+            gst::GST_EVENT_FLUSH_STOP => Some(SinkEvent::FlushStop),
+            gst::GST_EVENT_SEGMENT => {
+                let mut segment = ptr::null();
+                gst::gst_event_parse_segment(event, &mut segment);
+                let segment = &*segment;
+
+                let format = match segment.format {
+                    gst::GST_FORMAT_BYTES => SegmentFormat::Bytes,
+                    gst::GST_FORMAT_TIME => SegmentFormat::Time,
+                    _ => SegmentFormat::Other,
+                };
+
+                Some(SinkEvent::Segment {
+                         format: format,
+                         start: segment.start,
+                         stop: if segment.stop == u64::MAX {
+                             None
+                         } else {
+                             Some(segment.stop)
+                         },
+                         time: segment.time,
+                         rate: segment.rate,
+                     })
+            }
+            gst::GST_EVENT_TAG => {
+                let mut tags = ptr::null_mut();
+                gst::gst_event_parse_tag(event, &mut tags);
+                Some(SinkEvent::Tag(TagList::from_ptr(tags)))
+            }
+            gst::GST_EVENT_EOS => Some(SinkEvent::Eos),
+            gst::GST_EVENT_CUSTOM_DOWNSTREAM |
+            gst::GST_EVENT_CUSTOM_BOTH => {
+                let structure = gst::gst_event_get_structure(event);
+                if structure.is_null() {
+                    None
+                } else {
+                    Some(SinkEvent::Custom(Structure::from_borrowed_ptr(structure)))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 pub struct SinkWrapper {
     raw: *mut gst::GstElement,
     logger: Logger,
     uri: Mutex<(Option<Url>, bool)>,
     uri_validator: Box<UriValidator>,
+    // Kept outside the sink so that it can be used while render() blocks
+    unlock: Option<Box<Unlock>>,
     sink: Mutex<Box<Sink>>,
+    // Kept outside the sink so that properties can be used while render()
+    // blocks
+    properties: PropertyStore,
     panicked: AtomicBool,
 }
 
 pub trait Sink {
     fn uri_validator(&self) -> Box<UriValidator>;
 
+    // Used to interrupt render() when flushing or shutting down. Sinks that
+    // can block in render() should return a handle here
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        None
+    }
+
+    // Property changes are passed on once the sink is not busy anymore,
+    // e.g. after render() returned. Values changed by the sink itself are
+    // only checked again after property changes and action signals
+    fn set_property(&mut self, _name: &str, _value: &Value) {}
+    fn get_property(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn handle_action_signal(&mut self, _name: &str, _args: &[Value]) -> Option<Value> {
+        None
+    }
+
     fn start(&mut self, uri: Url) -> Result<(), ErrorMessage>;
     fn stop(&mut self) -> Result<(), ErrorMessage>;
 
+    // Answered in SEEKING queries in BYTES format, e.g. for muxers that
+    // rewrite their headers at EOS
+    fn is_seekable(&self) -> bool {
+        false
+    }
+    // Called for BYTES segments if seekable, following buffers are written
+    // starting at offset
+    fn seek(&mut self, _offset: u64) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // Called with the negotiated caps before the first buffer and whenever
+    // they change
+    fn set_caps(&mut self, _caps: &Caps) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
     fn render(&mut self, buffer: &Buffer) -> Result<(), FlowError>;
+    // Lists are synchronised against the clock as a whole, based on the
+    // first buffer
+    fn render_list(&mut self, list: &BufferList) -> Result<(), FlowError> {
+        for buffer in list.iter() {
+            try!(self.render(buffer));
+        }
+
+        Ok(())
+    }
+
+    // Called with the first buffer when prerolling, the buffer is passed to
+    // render() again once playing
+    fn preroll(&mut self, _buffer: &Buffer) -> Result<(), FlowError> {
+        Ok(())
+    }
+
+    // Start and end time of the buffer for clock synchronisation. None uses
+    // the buffer timestamps, a start time of None disables synchronisation
+    // for this buffer
+    fn get_times(&self, _buffer: &Buffer) -> Option<(Option<u64>, Option<u64>)> {
+        None
+    }
+
+    // Default handling by the base class happens afterwards, e.g. for
+    // posting the EOS message
+    fn event(&mut self, _event: &SinkEvent) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
 }
 
 impl SinkWrapper {
-    fn new(raw: *mut gst::GstElement, sink: Box<Sink>) -> SinkWrapper {
+    fn new(raw: *mut gst::GstElement, properties: &[Property], sink: Box<Sink>) -> SinkWrapper {
+        let properties = PropertyStore::new(properties, |name| sink.get_property(name));
+
         SinkWrapper {
             raw: raw,
             logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
@@ -84,7 +231,9 @@ impl SinkWrapper {
                                  o!()),
             uri: Mutex::new((None, false)),
             uri_validator: sink.uri_validator(),
+            unlock: sink.unlock_handle(),
             sink: Mutex::new(sink),
+            properties: properties,
             panicked: AtomicBool::new(false),
         }
     }
@@ -141,7 +290,7 @@ impl SinkWrapper {
             }
         };
 
-        let sink = &mut self.sink.lock().unwrap();
+        let sink = &mut self.lock_sink();
         match sink.start(uri) {
             Ok(..) => {
                 trace!(self.logger, "Started successfully");
@@ -158,7 +307,7 @@ impl SinkWrapper {
     }
 
     fn stop(&self) -> bool {
-        let sink = &mut self.sink.lock().unwrap();
+        let sink = &mut self.lock_sink();
 
         debug!(self.logger, "Stopping");
 
@@ -177,15 +326,93 @@ impl SinkWrapper {
         }
     }
 
+    fn set_caps(&self, caps: &Caps) -> bool {
+        let sink = &mut self.lock_sink();
+
+        debug!(self.logger, "Setting caps {:?}", caps);
+
+        match sink.set_caps(caps) {
+            Ok(..) => true,
+            Err(ref msg) => {
+                error!(self.logger, "Failed to set caps: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
     fn render(&self, buffer: &Buffer) -> gst::GstFlowReturn {
-        let sink = &mut self.sink.lock().unwrap();
+        let sink = &mut self.lock_sink();
 
         trace!(self.logger, "Rendering buffer {:?}", buffer);
 
-        match sink.render(buffer) {
+        let res = sink.render(buffer);
+        self.handle_flow_result("render", res)
+    }
+
+    fn render_list(&self, list: &BufferList) -> gst::GstFlowReturn {
+        let sink = &mut self.lock_sink();
+
+        trace!(self.logger, "Rendering buffer list {:?}", list);
+
+        let res = sink.render_list(list);
+        self.handle_flow_result("render list", res)
+    }
+
+    fn preroll(&self, buffer: &Buffer) -> gst::GstFlowReturn {
+        let sink = &mut self.lock_sink();
+
+        trace!(self.logger, "Prerolling buffer {:?}", buffer);
+
+        let res = sink.preroll(buffer);
+        self.handle_flow_result("preroll", res)
+    }
+
+    fn get_times(&self, buffer: &Buffer) -> Option<(Option<u64>, Option<u64>)> {
+        let sink = &self.lock_sink();
+        sink.get_times(buffer)
+    }
+
+    fn event(&self, event: &SinkEvent) -> bool {
+        let sink = &mut self.lock_sink();
+
+        debug!(self.logger, "Handling event {:?}", event);
+
+        if let SinkEvent::Segment { format: SegmentFormat::Bytes, start, .. } = *event {
+            if sink.is_seekable() {
+                debug!(self.logger, "Seeking to {}", start);
+
+                if let Err(ref msg) = sink.seek(start) {
+                    error!(self.logger, "Failed to seek: {:?}", msg);
+
+                    self.post_message(msg);
+                    return false;
+                }
+            }
+        }
+
+        match sink.event(event) {
+            Ok(..) => true,
+            Err(ref msg) => {
+                error!(self.logger, "Failed to handle event: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn is_seekable(&self) -> bool {
+        let sink = &self.lock_sink();
+        sink.is_seekable()
+    }
+
+    fn handle_flow_result(&self, action: &str, res: Result<(), FlowError>) -> gst::GstFlowReturn {
+        match res {
             Ok(..) => gst::GST_FLOW_OK,
             Err(flow_error) => {
-                error!(self.logger, "Failed to render: {:?}", flow_error);
+                error!(self.logger, "Failed to {}: {:?}", action, flow_error);
                 match flow_error {
                     FlowError::NotNegotiated(ref msg) |
                     FlowError::Error(ref msg) => self.post_message(msg),
@@ -196,6 +423,68 @@ impl SinkWrapper {
         }
     }
 
+    fn unlock(&self) {
+        debug!(self.logger, "Unlocking");
+
+        if let Some(ref unlock) = self.unlock {
+            unlock.unlock();
+        }
+    }
+
+    fn unlock_stop(&self) {
+        debug!(self.logger, "Stopping unlock");
+
+        if let Some(ref unlock) = self.unlock {
+            unlock.unlock_stop();
+        }
+    }
+
+    fn set_property(&self, property: &Property, value: &Value) {
+        debug!(self.logger, "Setting property {} to {:?}", property.name, value);
+
+        self.properties.set(&property.name, value);
+
+        // Otherwise passed to the sink the next time it is locked
+        if let Ok(mut sink) = self.sink.try_lock() {
+            self.apply_properties(&mut sink);
+        }
+    }
+
+    fn get_property(&self, property: &Property) -> Option<Value> {
+        self.properties.get(&property.name)
+    }
+
+    // Locks the sink and passes it the property changes that happened
+    // while it was busy
+    fn lock_sink(&self) -> MutexGuard<Box<Sink>> {
+        let mut sink = self.sink.lock().unwrap();
+        self.apply_properties(&mut sink);
+        sink
+    }
+
+    fn apply_properties(&self, sink: &mut Box<Sink>) {
+        let pending = self.properties.take_pending();
+        if pending.is_empty() {
+            return;
+        }
+
+        for (name, value) in pending {
+            sink.set_property(&name, &value);
+        }
+        self.properties.update(|name| sink.get_property(name));
+    }
+
+    fn handle_action_signal(&self, signal: &Signal, args: &[Value]) -> Option<Value> {
+        let sink = &mut self.lock_sink();
+
+        debug!(self.logger, "Handling action signal {} with {:?}", signal.name, args);
+
+        let res = sink.handle_action_signal(&signal.name, args);
+        self.properties.update(|name| sink.get_property(name));
+
+        res
+    }
+
     fn post_message(&self, msg: &ErrorMessage) {
         unsafe {
             msg.post(self.raw);
@@ -266,6 +555,21 @@ unsafe extern "C" fn sink_stop(ptr: *mut gst_base::GstBaseSink) -> glib::gboolea
     })
 }
 
+unsafe extern "C" fn sink_set_caps(ptr: *mut gst_base::GstBaseSink,
+                                   caps: *mut gst::GstCaps)
+                                   -> glib::gboolean {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.set_caps(Caps::from_ptr(caps)) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
 unsafe extern "C" fn sink_render(ptr: *mut gst_base::GstBaseSink,
                                  buffer: *mut gst::GstBuffer)
                                  -> gst::GstFlowReturn {
@@ -278,6 +582,135 @@ unsafe extern "C" fn sink_render(ptr: *mut gst_base::GstBaseSink,
     })
 }
 
+unsafe extern "C" fn sink_render_list(ptr: *mut gst_base::GstBaseSink,
+                                      list: *mut gst::GstBufferList)
+                                      -> gst::GstFlowReturn {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+    let list: &BufferList = BufferList::from_ptr(list);
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        wrap.render_list(list)
+    })
+}
+
+unsafe extern "C" fn sink_preroll(ptr: *mut gst_base::GstBaseSink,
+                                  buffer: *mut gst::GstBuffer)
+                                  -> gst::GstFlowReturn {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+    let buffer: &Buffer = Buffer::from_ptr(buffer);
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        wrap.preroll(buffer)
+    })
+}
+
+unsafe extern "C" fn sink_get_times(ptr: *mut gst_base::GstBaseSink,
+                                    buffer: *mut gst::GstBuffer,
+                                    start: *mut gst::GstClockTime,
+                                    end: *mut gst::GstClockTime) {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, (), {
+        match wrap.get_times(Buffer::from_ptr(buffer)) {
+            Some((s, e)) => {
+                *start = s.unwrap_or(u64::MAX);
+                *end = e.unwrap_or(u64::MAX);
+            }
+            None => {
+                let sink_klass = &**(ptr as *const *const RsSinkClass);
+                let parent_klass = &*(sink_klass.parent_vtable as
+                                      *const gst_base::GstBaseSinkClass);
+                parent_klass.get_times.map(|f| f(ptr, buffer, start, end));
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn sink_event(ptr: *mut gst_base::GstBaseSink,
+                                event: *mut gst::GstEvent)
+                                -> glib::gboolean {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        let handled = match SinkEvent::from_native(event) {
+            Some(ref sink_event) => wrap.event(sink_event),
+            None => true,
+        };
+
+        if !handled {
+            gst::gst_mini_object_unref(event as *mut gst::GstMiniObject);
+            return glib::GFALSE;
+        }
+
+        let sink_klass = &**(ptr as *const *const RsSinkClass);
+        let parent_klass = &*(sink_klass.parent_vtable as *const gst_base::GstBaseSinkClass);
+        parent_klass
+            .event
+            .map(|f| f(ptr, event))
+            .unwrap_or(glib::GFALSE)
+    })
+}
+
+unsafe extern "C" fn sink_query(ptr: *mut gst_base::GstBaseSink,
+                                query: *mut gst::GstQuery)
+                                -> glib::gboolean {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if (*query).type_ == gst::GST_QUERY_SEEKING {
+            let mut format = gst::GST_FORMAT_UNDEFINED;
+            gst::gst_query_parse_seeking(query,
+                                         &mut format,
+                                         ptr::null_mut(),
+                                         ptr::null_mut(),
+                                         ptr::null_mut());
+
+            if format == gst::GST_FORMAT_BYTES {
+                let seekable = wrap.is_seekable();
+                debug!(wrap.logger, "Returning seekable {}", seekable);
+                gst::gst_query_set_seeking(query,
+                                           format,
+                                           if seekable { glib::GTRUE } else { glib::GFALSE },
+                                           0,
+                                           -1);
+                return glib::GTRUE;
+            }
+        }
+
+        let sink_klass = &**(ptr as *const *const RsSinkClass);
+        let parent_klass = &*(sink_klass.parent_vtable as *const gst_base::GstBaseSinkClass);
+        parent_klass
+            .query
+            .map(|f| f(ptr, query))
+            .unwrap_or(glib::GFALSE)
+    })
+}
+
+unsafe extern "C" fn sink_unlock(ptr: *mut gst_base::GstBaseSink) -> glib::gboolean {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        wrap.unlock();
+        glib::GTRUE
+    })
+}
+
+unsafe extern "C" fn sink_unlock_stop(ptr: *mut gst_base::GstBaseSink) -> glib::gboolean {
+    let sink = &*(ptr as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        wrap.unlock_stop();
+        glib::GTRUE
+    })
+}
+
 pub struct SinkInfo {
     pub name: String,
     pub long_name: String,
@@ -287,6 +720,14 @@ pub struct SinkInfo {
     pub rank: u32,
     pub create_instance: fn(Element) -> Box<Sink>,
     pub protocols: Vec<String>,
+    // Template caps of the sink pad
+    pub caps: GstRc<Caps>,
+    // Synchronise buffers against the clock
+    pub sync: bool,
+    // Wait for a buffer before completing the state change to PAUSED
+    pub async_enabled: bool,
+    pub properties: Vec<Property>,
+    pub signals: Vec<Signal>,
 }
 
 #[repr(C)]
@@ -325,14 +766,22 @@ unsafe extern "C" fn sink_set_property(obj: *mut gobject::GObject,
             let uri_ptr = gobject::g_value_get_string(value);
             sink_set_uri(sink, uri_ptr, ptr::null_mut());
         }
-        _ => unreachable!(),
+        id => {
+            let sink_info = &*sink.sink_info;
+            let property = &sink_info.properties[id as usize - 2];
+            let wrap: &SinkWrapper = &*sink.wrap;
+
+            panic_to_error!(wrap, (), {
+                wrap.set_property(property, &value_from_property_gvalue(property, value));
+            })
+        }
     }
 }
 
 unsafe extern "C" fn sink_get_property(obj: *mut gobject::GObject,
                                        id: u32,
                                        value: *mut gobject::GValue,
-                                       _pspec: *mut gobject::GParamSpec) {
+                                       pspec: *mut gobject::GParamSpec) {
     let sink = &*(obj as *const RsSink);
 
     match id {
@@ -340,10 +789,40 @@ unsafe extern "C" fn sink_get_property(obj: *mut gobject::GObject,
             let uri_ptr = sink_get_uri(sink);
             gobject::g_value_take_string(value, uri_ptr);
         }
-        _ => unreachable!(),
+        id => {
+            let sink_info = &*sink.sink_info;
+            let property = &sink_info.properties[id as usize - 2];
+            let wrap: &SinkWrapper = &*sink.wrap;
+
+            panic_to_error!(wrap, (), {
+                match wrap.get_property(property) {
+                    Some(ref v) if value_to_property_gvalue(property, v, value) => (),
+                    Some(ref v) => {
+                        error!(wrap.logger,
+                               "Invalid value {:?} for property {}",
+                               v,
+                               property.name);
+                        gobject::g_param_value_set_default(pspec, value);
+                    }
+                    None => gobject::g_param_value_set_default(pspec, value),
+                }
+            })
+        }
     }
 }
 
+unsafe fn sink_handle_action_signal(obj: *mut gobject::GObject,
+                                    signal: &Signal,
+                                    args: &[Value])
+                                    -> Option<Value> {
+    let sink = &*(obj as *const RsSink);
+    let wrap: &SinkWrapper = &*sink.wrap;
+
+    panic_to_error!(wrap, None, {
+        wrap.handle_action_signal(signal, args)
+    })
+}
+
 unsafe extern "C" fn sink_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
     let sink_klass = &mut *(klass as *mut RsSinkClass);
     let sink_info = &*(klass_data as *const SinkInfo);
@@ -368,6 +847,9 @@ unsafe extern "C" fn sink_class_init(klass: glib::gpointer, klass_data: glib::gp
                                          blurb_cstr.as_ptr(),
                                          ptr::null_mut(),
                                          gobject::G_PARAM_READWRITE));
+
+        install_properties(klass as *mut gobject::GObjectClass, 2, &sink_info.properties);
+        install_signals(klass, &sink_info.signals, sink_handle_action_signal);
     }
 
     {
@@ -384,12 +866,12 @@ unsafe extern "C" fn sink_class_init(klass: glib::gpointer, klass_data: glib::gp
                                                    description_cstr.into_raw(),
                                                    author_cstr.into_raw());
 
-        let caps = Caps::new_any();
         let templ_name = CString::new("sink").unwrap();
         let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
                                                      gst::GST_PAD_SINK,
                                                      gst::GST_PAD_ALWAYS,
-                                                     caps.as_ptr() as *mut gst::GstCaps);
+                                                     sink_info.caps.as_ptr() as
+                                                     *mut gst::GstCaps);
         gst::gst_element_class_add_pad_template(element_klass, pad_template);
     }
 
@@ -398,6 +880,14 @@ unsafe extern "C" fn sink_class_init(klass: glib::gpointer, klass_data: glib::gp
         basesink_klass.start = Some(sink_start);
         basesink_klass.stop = Some(sink_stop);
         basesink_klass.render = Some(sink_render);
+        basesink_klass.set_caps = Some(sink_set_caps);
+        basesink_klass.render_list = Some(sink_render_list);
+        basesink_klass.preroll = Some(sink_preroll);
+        basesink_klass.get_times = Some(sink_get_times);
+        basesink_klass.event = Some(sink_event);
+        basesink_klass.query = Some(sink_query);
+        basesink_klass.unlock = Some(sink_unlock);
+        basesink_klass.unlock_stop = Some(sink_unlock_stop);
     }
 
     sink_klass.sink_info = sink_info;
@@ -419,10 +909,22 @@ unsafe extern "C" fn sink_init(instance: *mut gobject::GTypeInstance, klass: gli
     sink.sink_info = sink_info;
 
     let wrap = Box::new(SinkWrapper::new(&mut sink.parent.element,
+            &sink_info.properties,
             (sink_info.create_instance)(Element::new(&mut sink.parent.element))));
     sink.wrap = Box::into_raw(wrap);
 
-    gst_base::gst_base_sink_set_sync(&mut sink.parent, glib::GFALSE);
+    gst_base::gst_base_sink_set_sync(&mut sink.parent,
+                                     if sink_info.sync {
+                                         glib::GTRUE
+                                     } else {
+                                         glib::GFALSE
+                                     });
+    gst_base::gst_base_sink_set_async_enabled(&mut sink.parent,
+                                              if sink_info.async_enabled {
+                                                  glib::GTRUE
+                                              } else {
+                                                  glib::GFALSE
+                                              });
 }
 
 unsafe extern "C" fn sink_uri_handler_get_type(_type: glib::GType) -> gst::GstURIType {
@@ -10,11 +10,13 @@ use libc::c_char;
 use std::ffi::{CStr, CString};
 use std::ptr;
 use std::mem;
+use std::cmp;
 use std::u64;
+use std::usize;
 
 use std::panic::{self, AssertUnwindSafe};
 
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use url::Url;
@@ -28,6 +30,9 @@ use buffer::*;
 use miniobject::*;
 use log::*;
 use caps::*;
+use value::*;
+use properties::*;
+use signals::*;
 
 use glib;
 use gobject;
@@ -60,24 +65,86 @@ pub struct SourceWrapper {
     logger: Logger,
     uri: Mutex<(Option<Url>, bool)>,
     uri_validator: Box<UriValidator>,
+    // Kept outside the source so that it can be used while fill() blocks
+    unlock: Option<Box<Unlock>>,
     source: Mutex<Box<Source>>,
+    // Kept outside the source so that LATENCY queries don't block on fill()
+    latency: Mutex<Option<(u64, Option<u64>)>>,
+    // Same for CAPS queries and properties
+    caps: Mutex<Option<GstRc<Caps>>>,
+    properties: PropertyStore,
     panicked: AtomicBool,
 }
 
 pub trait Source {
     fn uri_validator(&self) -> Box<UriValidator>;
 
+    // Used to interrupt fill() when flushing or shutting down. Sources that
+    // can block in fill() should return a handle here
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        None
+    }
+
+    // Property changes are passed on once the source is not busy anymore,
+    // e.g. after fill() returned. Values changed by the source itself are
+    // only checked again after start(), stop(), seek(), negotiate(),
+    // property changes and action signals
+    fn set_property(&mut self, _name: &str, _value: &Value) {}
+    fn get_property(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    fn handle_action_signal(&mut self, _name: &str, _args: &[Value]) -> Option<Value> {
+        None
+    }
+
     fn is_seekable(&self) -> bool;
     fn get_size(&self) -> Option<u64>;
 
+    // Caps that can currently be produced, None means the template caps.
+    // Checked at the same times as property values
+    fn get_caps(&self) -> Option<GstRc<Caps>> {
+        None
+    }
+    // Caps to set on the source pad, None means that they are negotiated
+    // with downstream from the result of get_caps()
+    fn negotiate(&mut self) -> Result<Option<GstRc<Caps>>, ErrorMessage> {
+        Ok(None)
+    }
+
     fn start(&mut self, uri: Url) -> Result<(), ErrorMessage>;
     fn stop(&mut self) -> Result<(), ErrorMessage>;
-    fn fill(&mut self, offset: u64, length: u32, buffer: &mut Buffer) -> Result<(), FlowError>;
+    // offset is u64::MAX for live sources, which operate in TIME format
+    fn fill(&mut self, _offset: u64, _length: u32, _buffer: &mut Buffer) -> Result<(), FlowError> {
+        Err(FlowError::Error(error_msg!(SourceError::Failure, ["Not implemented"])))
+    }
+    // Allows handing out buffers of any size without copying into a
+    // preallocated buffer. If None is returned, a buffer of length bytes
+    // is allocated and passed to fill() instead. If downstream provides the
+    // buffer, the result is copied into it
+    fn create(&mut self,
+              _offset: u64,
+              _length: u32)
+              -> Option<Result<GstRc<Buffer>, FlowError>> {
+        None
+    }
     fn seek(&mut self, start: u64, stop: Option<u64>) -> Result<(), ErrorMessage>;
+
+    // Minimum and maximum latency of a live source. Checked after start()
+    // and every fill()
+    fn get_latency(&self) -> Option<(u64, Option<u64>)> {
+        None
+    }
 }
 
 impl SourceWrapper {
-    fn new(raw: *mut gst::GstElement, source: Box<Source>) -> SourceWrapper {
+    fn new(raw: *mut gst::GstElement,
+           properties: &[Property],
+           source: Box<Source>)
+           -> SourceWrapper {
+        let caps = source.get_caps();
+        let properties = PropertyStore::new(properties, |name| source.get_property(name));
+
         SourceWrapper {
             raw: raw,
             logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
@@ -87,7 +154,11 @@ impl SourceWrapper {
                                  o!()),
             uri: Mutex::new((None, false)),
             uri_validator: source.uri_validator(),
+            unlock: source.unlock_handle(),
             source: Mutex::new(source),
+            latency: Mutex::new(None),
+            caps: Mutex::new(caps),
+            properties: properties,
             panicked: AtomicBool::new(false),
         }
     }
@@ -129,15 +200,40 @@ impl SourceWrapper {
     }
 
     fn is_seekable(&self) -> bool {
-        let source = &self.source.lock().unwrap();
+        let source = &self.lock_source();
         source.is_seekable()
     }
 
     fn get_size(&self) -> u64 {
-        let source = &self.source.lock().unwrap();
+        let source = &self.lock_source();
         source.get_size().unwrap_or(u64::MAX)
     }
 
+    fn get_caps(&self) -> Option<GstRc<Caps>> {
+        self.caps.lock().unwrap().clone()
+    }
+
+    fn negotiate(&self) -> Result<Option<GstRc<Caps>>, ()> {
+        let source = &mut self.lock_source();
+
+        debug!(self.logger, "Negotiating");
+
+        let res = source.negotiate();
+        self.update_state(source.as_ref());
+
+        match res {
+            Ok(caps) => {
+                debug!(self.logger, "Negotiated caps {:?}", caps);
+                Ok(caps)
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to negotiate: {:?}", msg);
+                self.post_message(msg);
+                Err(())
+            }
+        }
+    }
+
     fn start(&self) -> bool {
         debug!(self.logger, "Starting");
 
@@ -154,10 +250,12 @@ impl SourceWrapper {
             }
         };
 
-        let source = &mut self.source.lock().unwrap();
+        let source = &mut self.lock_source();
         match source.start(uri) {
             Ok(..) => {
                 trace!(self.logger, "Started successfully");
+                self.update_latency(source.as_ref());
+                self.update_state(source.as_ref());
                 true
             }
             Err(ref msg) => {
@@ -171,11 +269,14 @@ impl SourceWrapper {
     }
 
     fn stop(&self) -> bool {
-        let source = &mut self.source.lock().unwrap();
+        let source = &mut self.lock_source();
 
         debug!(self.logger, "Stopping");
 
-        match source.stop() {
+        let res = source.stop();
+        self.update_state(source.as_ref());
+
+        match res {
             Ok(..) => {
                 trace!(self.logger, "Stopped successfully");
                 self.uri.lock().unwrap().1 = false;
@@ -191,7 +292,7 @@ impl SourceWrapper {
     }
 
     fn fill(&self, offset: u64, length: u32, buffer: &mut Buffer) -> gst::GstFlowReturn {
-        let source = &mut self.source.lock().unwrap();
+        let source = &mut self.lock_source();
 
         trace!(self.logger,
                "Filling buffer {:?} with offset {} and length {}",
@@ -200,7 +301,10 @@ impl SourceWrapper {
                length);
 
         match source.fill(offset, length, buffer) {
-            Ok(()) => gst::GST_FLOW_OK,
+            Ok(()) => {
+                self.update_latency(source.as_ref());
+                gst::GST_FLOW_OK
+            }
             Err(flow_error) => {
                 error!(self.logger, "Failed to fill: {:?}", flow_error);
                 match flow_error {
@@ -213,12 +317,102 @@ impl SourceWrapper {
         }
     }
 
+    fn unlock(&self) {
+        debug!(self.logger, "Unlocking");
+
+        if let Some(ref unlock) = self.unlock {
+            unlock.unlock();
+        }
+    }
+
+    fn unlock_stop(&self) {
+        debug!(self.logger, "Stopping unlock");
+
+        if let Some(ref unlock) = self.unlock {
+            unlock.unlock_stop();
+        }
+    }
+
+    fn create(&self, offset: u64, length: u32) -> Option<Result<GstRc<Buffer>, gst::GstFlowReturn>> {
+        let source = &mut self.lock_source();
+
+        trace!(self.logger,
+               "Creating buffer with offset {} and length {}",
+               offset,
+               length);
+
+        match source.create(offset, length) {
+            None => None,
+            Some(Ok(buffer)) => {
+                self.update_latency(source.as_ref());
+                Some(Ok(buffer))
+            }
+            Some(Err(flow_error)) => {
+                error!(self.logger, "Failed to create: {:?}", flow_error);
+                match flow_error {
+                    FlowError::NotNegotiated(ref msg) |
+                    FlowError::Error(ref msg) => self.post_message(msg),
+                    _ => (),
+                }
+                Some(Err(flow_error.to_native()))
+            }
+        }
+    }
+
+    fn update_latency(&self, source: &Source) {
+        let latency = source.get_latency();
+        let old_latency = mem::replace(&mut *self.latency.lock().unwrap(), latency);
+
+        if old_latency.is_some() && old_latency != latency {
+            debug!(self.logger, "Latency changed to {:?}", latency);
+            unsafe {
+                gst::gst_element_post_message(self.raw,
+                                              gst::gst_message_new_latency(self.raw as
+                                                                           *mut gst::GstObject));
+            }
+        }
+    }
+
+    fn get_latency(&self) -> Option<(u64, Option<u64>)> {
+        *self.latency.lock().unwrap()
+    }
+
+    // Updates the caps and property values that are readable without
+    // locking the source
+    fn update_state(&self, source: &Source) {
+        *self.caps.lock().unwrap() = source.get_caps();
+        self.properties.update(|name| source.get_property(name));
+    }
+
+    // Locks the source and passes it the property changes that happened
+    // while it was busy
+    fn lock_source(&self) -> MutexGuard<Box<Source>> {
+        let mut source = self.source.lock().unwrap();
+        self.apply_properties(&mut source);
+        source
+    }
+
+    fn apply_properties(&self, source: &mut Box<Source>) {
+        let pending = self.properties.take_pending();
+        if pending.is_empty() {
+            return;
+        }
+
+        for (name, value) in pending {
+            source.set_property(&name, &value);
+        }
+        self.update_state(source.as_ref());
+    }
+
     fn seek(&self, start: u64, stop: Option<u64>) -> bool {
-        let source = &mut self.source.lock().unwrap();
+        let source = &mut self.lock_source();
 
         debug!(self.logger, "Seeking to {:?}-{:?}", start, stop);
 
-        match source.seek(start, stop) {
+        let res = source.seek(start, stop);
+        self.update_state(source.as_ref());
+
+        match res {
             Ok(..) => true,
             Err(ref msg) => {
                 error!(self.logger, "Failed to seek {:?}", msg);
@@ -228,6 +422,32 @@ impl SourceWrapper {
         }
     }
 
+    fn set_property(&self, property: &Property, value: &Value) {
+        debug!(self.logger, "Setting property {} to {:?}", property.name, value);
+
+        self.properties.set(&property.name, value);
+
+        // Otherwise passed to the source the next time it is locked
+        if let Ok(mut source) = self.source.try_lock() {
+            self.apply_properties(&mut source);
+        }
+    }
+
+    fn get_property(&self, property: &Property) -> Option<Value> {
+        self.properties.get(&property.name)
+    }
+
+    fn handle_action_signal(&self, signal: &Signal, args: &[Value]) -> Option<Value> {
+        let source = &mut self.lock_source();
+
+        debug!(self.logger, "Handling action signal {} with {:?}", signal.name, args);
+
+        let res = source.handle_action_signal(&signal.name, args);
+        self.update_state(source.as_ref());
+
+        res
+    }
+
     fn post_message(&self, msg: &ErrorMessage) {
         unsafe {
             msg.post(self.raw);
@@ -297,6 +517,59 @@ unsafe extern "C" fn source_get_size(ptr: *mut gst_base::GstBaseSrc,
     })
 }
 
+unsafe extern "C" fn source_get_caps(ptr: *mut gst_base::GstBaseSrc,
+                                     filter: *mut gst::GstCaps)
+                                     -> *mut gst::GstCaps {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, ptr::null_mut(), {
+        match wrap.get_caps() {
+            Some(caps) => {
+                if filter.is_null() {
+                    caps.into_ptr()
+                } else {
+                    gst::gst_caps_intersect_full(filter,
+                                                 caps.as_ptr() as *mut gst::GstCaps,
+                                                 gst::GST_CAPS_INTERSECT_FIRST)
+                }
+            }
+            None => {
+                let src_klass = &**(ptr as *const *const RsSrcClass);
+                let parent_klass = &*(src_klass.parent_vtable as
+                                      *const gst_base::GstBaseSrcClass);
+                parent_klass
+                    .get_caps
+                    .map(|f| f(ptr, filter))
+                    .unwrap_or(ptr::null_mut())
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn source_negotiate(ptr: *mut gst_base::GstBaseSrc) -> glib::gboolean {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        // The source is not locked anymore here as setting the caps sends
+        // them downstream
+        match wrap.negotiate() {
+            Ok(Some(caps)) => gst_base::gst_base_src_set_caps(ptr, caps.as_mut_ptr()),
+            Ok(None) => {
+                let src_klass = &**(ptr as *const *const RsSrcClass);
+                let parent_klass = &*(src_klass.parent_vtable as
+                                      *const gst_base::GstBaseSrcClass);
+                parent_klass
+                    .negotiate
+                    .map(|f| f(ptr))
+                    .unwrap_or(glib::GTRUE)
+            }
+            Err(_) => glib::GFALSE,
+        }
+    })
+}
+
 unsafe extern "C" fn source_start(ptr: *mut gst_base::GstBaseSrc) -> glib::gboolean {
     let src = &*(ptr as *const RsSrc);
     let wrap: &SourceWrapper = &*src.wrap;
@@ -337,6 +610,87 @@ unsafe extern "C" fn source_fill(ptr: *mut gst_base::GstBaseSrc,
     })
 }
 
+unsafe extern "C" fn source_unlock(ptr: *mut gst_base::GstBaseSrc) -> glib::gboolean {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        wrap.unlock();
+        glib::GTRUE
+    })
+}
+
+unsafe extern "C" fn source_unlock_stop(ptr: *mut gst_base::GstBaseSrc) -> glib::gboolean {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        wrap.unlock_stop();
+        glib::GTRUE
+    })
+}
+
+unsafe extern "C" fn source_create(ptr: *mut gst_base::GstBaseSrc,
+                                   offset: u64,
+                                   length: u32,
+                                   buffer: *mut *mut gst::GstBuffer)
+                                   -> gst::GstFlowReturn {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        // If the source has no create() implementation, let the parent class
+        // allocate and fill
+        match wrap.create(offset, length) {
+            Some(Ok(b)) => {
+                if (*buffer).is_null() {
+                    *buffer = b.into_ptr();
+                    return gst::GST_FLOW_OK;
+                }
+
+                // Downstream provided a buffer to fill, copy as much as fits
+                // into it like GstBaseSrc does
+                let map = match b.map_read() {
+                    Some(map) => map,
+                    None => {
+                        error!(wrap.logger, "Failed to map buffer");
+                        wrap.post_message(&error_msg!(SourceError::Failure,
+                                                      ["Failed to map buffer"]));
+                        return gst::GST_FLOW_ERROR;
+                    }
+                };
+
+                let dest = Buffer::from_mut_ptr(*buffer);
+                let size = cmp::min(dest.get_maxsize(), map.get_size());
+                dest.set_size(size);
+                if dest.copy_from_slice(0, &map.as_slice()[..size]).is_err() {
+                    error!(wrap.logger, "Failed to copy into downstream buffer");
+                    wrap.post_message(&error_msg!(SourceError::Failure,
+                                                  ["Failed to copy into downstream buffer"]));
+                    return gst::GST_FLOW_ERROR;
+                }
+                gst::gst_buffer_copy_into(*buffer,
+                                          b.as_mut_ptr(),
+                                          gst::GST_BUFFER_COPY_METADATA,
+                                          0,
+                                          usize::MAX);
+
+                gst::GST_FLOW_OK
+            }
+            Some(Err(ret)) => ret,
+            None => {
+                let src_klass = &**(ptr as *const *const RsSrcClass);
+                let parent_klass = &*(src_klass.parent_vtable as
+                                      *const gst_base::GstBaseSrcClass);
+                parent_klass
+                    .create
+                    .map(|f| f(ptr, offset, length, buffer))
+                    .unwrap_or(gst::GST_FLOW_ERROR)
+            }
+        }
+    })
+}
+
 unsafe extern "C" fn source_seek(ptr: *mut gst_base::GstBaseSrc,
                                  segment: *mut gst::GstSegment)
                                  -> glib::gboolean {
@@ -355,6 +709,33 @@ unsafe extern "C" fn source_seek(ptr: *mut gst_base::GstBaseSrc,
     })
 }
 
+unsafe extern "C" fn source_query(ptr: *mut gst_base::GstBaseSrc,
+                                  query: *mut gst::GstQuery)
+                                  -> glib::gboolean {
+    let src = &*(ptr as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if (*query).type_ == gst::GST_QUERY_LATENCY {
+            if let Some((min, max)) = wrap.get_latency() {
+                debug!(wrap.logger, "Returning latency {}-{:?}", min, max);
+                gst::gst_query_set_latency(query,
+                                           gst_base::gst_base_src_is_live(ptr),
+                                           min,
+                                           max.unwrap_or(u64::MAX));
+                return glib::GTRUE;
+            }
+        }
+
+        let src_klass = &**(ptr as *const *const RsSrcClass);
+        let parent_klass = &*(src_klass.parent_vtable as *const gst_base::GstBaseSrcClass);
+        parent_klass
+            .query
+            .map(|f| f(ptr, query))
+            .unwrap_or(glib::GFALSE)
+    })
+}
+
 pub struct SourceInfo {
     pub name: String,
     pub long_name: String,
@@ -364,7 +745,17 @@ pub struct SourceInfo {
     pub rank: u32,
     pub create_instance: fn(Element) -> Box<Source>,
     pub protocols: Vec<String>,
+    // Template caps of the source pad
+    pub caps: GstRc<Caps>,
     pub push_only: bool,
+    // Default size of the buffers passed to fill(), also available as the
+    // "blocksize" property. 4096 if None
+    pub blocksize: Option<u32>,
+    // Live sources operate in TIME format and timestamp their output
+    // against the pipeline clock
+    pub live: bool,
+    pub properties: Vec<Property>,
+    pub signals: Vec<Signal>,
 }
 
 #[repr(C)]
@@ -403,14 +794,22 @@ unsafe extern "C" fn source_set_property(obj: *mut gobject::GObject,
             let uri_ptr = gobject::g_value_get_string(value);
             source_set_uri(src, uri_ptr, ptr::null_mut());
         }
-        _ => unreachable!(),
+        id => {
+            let source_info = &*src.source_info;
+            let property = &source_info.properties[id as usize - 2];
+            let wrap: &SourceWrapper = &*src.wrap;
+
+            panic_to_error!(wrap, (), {
+                wrap.set_property(property, &value_from_property_gvalue(property, value));
+            })
+        }
     }
 }
 
 unsafe extern "C" fn source_get_property(obj: *mut gobject::GObject,
                                          id: u32,
                                          value: *mut gobject::GValue,
-                                         _pspec: *mut gobject::GParamSpec) {
+                                         pspec: *mut gobject::GParamSpec) {
     let src = &*(obj as *const RsSrc);
 
     match id {
@@ -418,10 +817,40 @@ unsafe extern "C" fn source_get_property(obj: *mut gobject::GObject,
             let uri_ptr = source_get_uri(src);
             gobject::g_value_take_string(value, uri_ptr);
         }
-        _ => unreachable!(),
+        id => {
+            let source_info = &*src.source_info;
+            let property = &source_info.properties[id as usize - 2];
+            let wrap: &SourceWrapper = &*src.wrap;
+
+            panic_to_error!(wrap, (), {
+                match wrap.get_property(property) {
+                    Some(ref v) if value_to_property_gvalue(property, v, value) => (),
+                    Some(ref v) => {
+                        error!(wrap.logger,
+                               "Invalid value {:?} for property {}",
+                               v,
+                               property.name);
+                        gobject::g_param_value_set_default(pspec, value);
+                    }
+                    None => gobject::g_param_value_set_default(pspec, value),
+                }
+            })
+        }
     }
 }
 
+unsafe fn source_handle_action_signal(obj: *mut gobject::GObject,
+                                      signal: &Signal,
+                                      args: &[Value])
+                                      -> Option<Value> {
+    let src = &*(obj as *const RsSrc);
+    let wrap: &SourceWrapper = &*src.wrap;
+
+    panic_to_error!(wrap, None, {
+        wrap.handle_action_signal(signal, args)
+    })
+}
+
 unsafe extern "C" fn source_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
     let src_klass = &mut *(klass as *mut RsSrcClass);
     let source_info = &*(klass_data as *const SourceInfo);
@@ -447,6 +876,9 @@ unsafe extern "C" fn source_class_init(klass: glib::gpointer, klass_data: glib::
                                          blurb_cstr.as_ptr(),
                                          ptr::null_mut(),
                                          gobject::G_PARAM_READWRITE));
+
+        install_properties(klass as *mut gobject::GObjectClass, 2, &source_info.properties);
+        install_signals(klass, &source_info.signals, source_handle_action_signal);
     }
 
     {
@@ -463,23 +895,29 @@ unsafe extern "C" fn source_class_init(klass: glib::gpointer, klass_data: glib::
                                                    description_cstr.into_raw(),
                                                    author_cstr.into_raw());
 
-        let caps = Caps::new_any();
         let templ_name = CString::new("src").unwrap();
         let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
                                                      gst::GST_PAD_SRC,
                                                      gst::GST_PAD_ALWAYS,
-                                                     caps.as_ptr() as *mut gst::GstCaps);
+                                                     source_info.caps.as_ptr() as
+                                                     *mut gst::GstCaps);
         gst::gst_element_class_add_pad_template(element_klass, pad_template);
     }
 
     {
         let basesrc_klass = &mut src_klass.parent_class.parent_class;
         basesrc_klass.start = Some(source_start);
+        basesrc_klass.get_caps = Some(source_get_caps);
+        basesrc_klass.negotiate = Some(source_negotiate);
         basesrc_klass.stop = Some(source_stop);
         basesrc_klass.is_seekable = Some(source_is_seekable);
         basesrc_klass.get_size = Some(source_get_size);
         basesrc_klass.fill = Some(source_fill);
+        basesrc_klass.create = Some(source_create);
         basesrc_klass.do_seek = Some(source_seek);
+        basesrc_klass.query = Some(source_query);
+        basesrc_klass.unlock = Some(source_unlock);
+        basesrc_klass.unlock_stop = Some(source_unlock_stop);
     }
 
     src_klass.source_info = source_info;
@@ -501,10 +939,18 @@ unsafe extern "C" fn source_init(instance: *mut gobject::GTypeInstance, klass: g
     src.source_info = source_info;
 
     let wrap = Box::new(SourceWrapper::new(&mut src.parent.parent.element,
+            &source_info.properties,
             (source_info.create_instance)(Element::new(&mut src.parent.parent.element))));
     src.wrap = Box::into_raw(wrap);
 
-    gst_base::gst_base_src_set_blocksize(&mut src.parent.parent, 4096);
+    gst_base::gst_base_src_set_blocksize(&mut src.parent.parent,
+                                         source_info.blocksize.unwrap_or(4096));
+
+    if source_info.live {
+        gst_base::gst_base_src_set_live(&mut src.parent.parent, glib::GTRUE);
+        gst_base::gst_base_src_set_format(&mut src.parent.parent, gst::GST_FORMAT_TIME);
+        gst_base::gst_base_src_set_do_timestamp(&mut src.parent.parent, glib::GTRUE);
+    }
 }
 
 unsafe extern "C" fn source_uri_handler_get_type(_type: glib::GType) -> gst::GstURIType {
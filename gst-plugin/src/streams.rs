@@ -8,6 +8,7 @@
 
 use std::ptr;
 use std::mem;
+use std::fmt;
 use std::ffi::{CStr, CString};
 use caps::Caps;
 use miniobject::*;
@@ -108,7 +109,7 @@ impl Stream {
         unsafe { gst::gst_stream_set_stream_type(self.0, mem::transmute(t.bits())) }
     }
 
-    pub fn set_tags(&self, tags: Option<TagList>) {
+    pub fn set_tags(&self, tags: Option<&TagList>) {
         let ptr = tags.map(|tags| unsafe { tags.as_mut_ptr() })
             .unwrap_or(ptr::null_mut());
 
@@ -116,6 +117,17 @@ impl Stream {
     }
 }
 
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("stream_id", &self.get_stream_id())
+            .field("stream_type", &self.get_stream_type())
+            .field("stream_flags", &self.get_stream_flags())
+            .field("caps", &self.get_caps())
+            .finish()
+    }
+}
+
 impl Clone for Stream {
     fn clone(&self) -> Self {
         unsafe { Stream(gst::gst_object_ref(self.0 as *mut gst::GstObject) as *mut gst::GstStream) }
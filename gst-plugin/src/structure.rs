@@ -9,6 +9,8 @@
 use std::fmt;
 use std::ptr;
 use std::mem;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 use std::ffi::{CStr, CString};
 use std::ops::{Deref, DerefMut};
 use std::borrow::{Borrow, ToOwned, BorrowMut};
@@ -17,6 +19,7 @@ use std::marker::PhantomData;
 use value::*;
 
 use glib;
+use gobject;
 use gst;
 
 pub struct OwnedStructure(*mut Structure, PhantomData<Structure>);
@@ -246,7 +249,220 @@ impl Structure {
         unsafe { gst::gst_structure_n_fields(&self.0) as u32 }
     }
 
-    // TODO: Various operations
+    pub fn has_name(&self, name: &str) -> bool {
+        self.get_name() == name
+    }
+
+    pub fn intersect(&self, other: &Structure) -> Option<OwnedStructure> {
+        unsafe {
+            let structure = gst::gst_structure_intersect(&self.0, &other.0);
+            if structure.is_null() {
+                None
+            } else {
+                Some(OwnedStructure(structure as *mut Structure, PhantomData))
+            }
+        }
+    }
+
+    pub fn can_intersect(&self, other: &Structure) -> bool {
+        unsafe { gst::gst_structure_can_intersect(&self.0, &other.0) == glib::GTRUE }
+    }
+
+    pub fn is_subset(&self, superset: &Structure) -> bool {
+        unsafe { gst::gst_structure_is_subset(&self.0, &superset.0) == glib::GTRUE }
+    }
+
+    // Fixates all fields to their nearest fixed value
+    pub fn fixate(&mut self) {
+        unsafe {
+            gst::gst_structure_fixate(&mut self.0);
+        }
+    }
+
+    // All fixate_field functions return false if the field does not exist
+    // or could not be fixated
+    pub fn fixate_field(&mut self, name: &str) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            gst::gst_structure_fixate_field(&mut self.0, cstr.as_ptr()) == glib::GTRUE
+        }
+    }
+
+    pub fn fixate_field_bool(&mut self, name: &str, target: bool) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            gst::gst_structure_fixate_field_boolean(&mut self.0,
+                                                    cstr.as_ptr(),
+                                                    if target {
+                                                        glib::GTRUE
+                                                    } else {
+                                                        glib::GFALSE
+                                                    }) == glib::GTRUE
+        }
+    }
+
+    pub fn fixate_field_str(&mut self, name: &str, target: &str) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            let target_cstr = CString::new(target).unwrap();
+            gst::gst_structure_fixate_field_string(&mut self.0,
+                                                   cstr.as_ptr(),
+                                                   target_cstr.as_ptr()) ==
+            glib::GTRUE
+        }
+    }
+
+    pub fn fixate_field_nearest_int(&mut self, name: &str, target: i32) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            gst::gst_structure_fixate_field_nearest_int(&mut self.0, cstr.as_ptr(), target) ==
+            glib::GTRUE
+        }
+    }
+
+    pub fn fixate_field_nearest_double(&mut self, name: &str, target: f64) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            gst::gst_structure_fixate_field_nearest_double(&mut self.0, cstr.as_ptr(), target) ==
+            glib::GTRUE
+        }
+    }
+
+    pub fn fixate_field_nearest_fraction(&mut self, name: &str, target: Rational32) -> bool {
+        unsafe {
+            let cstr = CString::new(name).unwrap();
+            gst::gst_structure_fixate_field_nearest_fraction(&mut self.0,
+                                                             cstr.as_ptr(),
+                                                             *target.numer(),
+                                                             *target.denom()) ==
+            glib::GTRUE
+        }
+    }
+
+    // Calls func for every field with a supported value type, stopping once
+    // it returns false. Returns false if iteration was stopped
+    pub fn map_in_place<F>(&mut self, func: F) -> bool
+        where F: FnMut(&str, &mut Value) -> bool
+    {
+        let mut data = TrampolineData {
+            func: func,
+            panic: None,
+        };
+
+        let res = unsafe {
+            gst::gst_structure_map_in_place(&mut self.0,
+                                            Some(map_in_place_trampoline::<F>),
+                                            &mut data as *mut TrampolineData<F> as
+                                            glib::gpointer) == glib::GTRUE
+        };
+
+        if let Some(err) = data.panic {
+            panic::resume_unwind(err);
+        }
+
+        res
+    }
+
+    // Calls func for every field with a supported value type. The field is
+    // replaced by the returned value, or removed if None is returned
+    pub fn filter_map_in_place<F>(&mut self, func: F)
+        where F: FnMut(&str, Value) -> Option<Value>
+    {
+        let mut data = TrampolineData {
+            func: func,
+            panic: None,
+        };
+
+        unsafe {
+            gst::gst_structure_filter_and_map_in_place(&mut self.0,
+                                                       Some(filter_map_in_place_trampoline::<F>),
+                                                       &mut data as *mut TrampolineData<F> as
+                                                       glib::gpointer);
+        }
+
+        if let Some(err) = data.panic {
+            panic::resume_unwind(err);
+        }
+    }
+}
+
+// Panics of the closure are caught in the trampolines and resumed once
+// GStreamer returned, the remaining fields are left alone
+struct TrampolineData<F> {
+    func: F,
+    panic: Option<Box<Any + Send>>,
+}
+
+unsafe fn quark_to_str<'a>(quark: glib::GQuark) -> &'a str {
+    CStr::from_ptr(glib::g_quark_to_string(quark))
+        .to_str()
+        .unwrap()
+}
+
+unsafe extern "C" fn map_in_place_trampoline<F>(field_id: glib::GQuark,
+                                                value: *mut gobject::GValue,
+                                                user_data: glib::gpointer)
+                                                -> glib::gboolean
+    where F: FnMut(&str, &mut Value) -> bool
+{
+    let data = &mut *(user_data as *mut TrampolineData<F>);
+    if data.panic.is_some() {
+        return glib::GFALSE;
+    }
+
+    let value = match Value::from_mut_ptr(value) {
+        Some(value) => value,
+        None => return glib::GTRUE,
+    };
+
+    let res = {
+        let func = &mut data.func;
+        panic::catch_unwind(AssertUnwindSafe(|| func(quark_to_str(field_id), value)))
+    };
+
+    match res {
+        Ok(true) => glib::GTRUE,
+        Ok(false) => glib::GFALSE,
+        Err(err) => {
+            data.panic = Some(err);
+            glib::GFALSE
+        }
+    }
+}
+
+unsafe extern "C" fn filter_map_in_place_trampoline<F>(field_id: glib::GQuark,
+                                                       value: *mut gobject::GValue,
+                                                       user_data: glib::gpointer)
+                                                       -> glib::gboolean
+    where F: FnMut(&str, Value) -> Option<Value>
+{
+    let data = &mut *(user_data as *mut TrampolineData<F>);
+    if data.panic.is_some() {
+        return glib::GTRUE;
+    }
+
+    let old_value = match Value::from_ptr(value) {
+        Some(old_value) => old_value,
+        None => return glib::GTRUE,
+    };
+
+    let res = {
+        let func = &mut data.func;
+        panic::catch_unwind(AssertUnwindSafe(|| func(quark_to_str(field_id), old_value)))
+    };
+
+    match res {
+        Ok(Some(new_value)) => {
+            gobject::g_value_unset(value);
+            ptr::write(value, new_value.into_raw());
+            glib::GTRUE
+        }
+        Ok(None) => glib::GFALSE,
+        Err(err) => {
+            data.panic = Some(err);
+            glib::GTRUE
+        }
+    }
 }
 
 impl fmt::Debug for Structure {
@@ -398,4 +614,94 @@ mod tests {
                                        ("f3", 123i32.into())]);
         assert_eq!(s, s2);
     }
+
+    #[test]
+    fn intersect_fixate() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let s1 = OwnedStructure::from_string("test, f1=(int)[1, 10], f2=(string){a, b}")
+            .unwrap();
+        let s2 = OwnedStructure::from_string("test, f1=(int)[5, 20]").unwrap();
+        let s3 = OwnedStructure::from_string("other, f1=(int)5").unwrap();
+
+        assert!(s1.can_intersect(&s2));
+        assert!(!s1.can_intersect(&s3));
+        assert_eq!(s1.intersect(&s3), None);
+
+        let mut s = s1.intersect(&s2).unwrap();
+        assert_eq!(s,
+                   OwnedStructure::from_string("test, f1=(int)[5, 10], f2=(string){a, b}")
+                       .unwrap());
+        assert!(s.is_subset(&s1));
+        assert!(!s1.is_subset(&s));
+
+        assert!(s.fixate_field_nearest_int("f1", 8));
+        assert!(s.fixate_field_str("f2", "b"));
+        assert!(!s.fixate_field_nearest_int("f3", 8));
+        assert_eq!(s.get::<i32>("f1").unwrap().get(), 8);
+        assert_eq!(s.get::<&str>("f2").unwrap().get(), "b");
+
+        let mut s = s1.clone();
+        s.fixate();
+        assert_eq!(s, OwnedStructure::from_string("test, f1=(int)1, f2=(string)a").unwrap());
+    }
+
+    #[test]
+    fn map_filter() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let mut s = OwnedStructure::new("test",
+                                        &[("f1", 1i32.into()),
+                                          ("f2", "abc".into()),
+                                          ("f3", 3i32.into())]);
+
+        s.map_in_place(|_, v| {
+                           if let Some(i) = v.try_get::<i32>() {
+                               *v = Value::new(i * 2);
+                           }
+                           true
+                       });
+        assert_eq!(s.get::<i32>("f1").unwrap().get(), 2);
+        assert_eq!(s.get::<i32>("f3").unwrap().get(), 6);
+
+        s.filter_map_in_place(|f, v| if f == "f2" {
+                                  None
+                              } else {
+                                  v.try_get::<i32>().map(|i| Value::new(i + 1))
+                              });
+        assert_eq!(s,
+                   OwnedStructure::new("test", &[("f1", 3i32.into()), ("f3", 7i32.into())]));
+    }
+
+    #[test]
+    fn map_panic() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let mut s = OwnedStructure::new("test", &[("f1", 1i32.into()), ("f2", 2i32.into())]);
+
+        // The panic reaches the caller, the remaining fields are untouched
+        let mut called = 0;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            s.filter_map_in_place(|_, _| {
+                                      called += 1;
+                                      panic!("Oops")
+                                  })
+        }));
+        assert!(res.is_err());
+        assert_eq!(called, 1);
+        assert_eq!(s,
+                   OwnedStructure::new("test", &[("f1", 1i32.into()), ("f2", 2i32.into())]));
+    }
+
+    #[test]
+    fn nested() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let inner = OwnedStructure::new("inner", &[("f1", 1i32.into())]);
+        let mut s = OwnedStructure::new_empty("test");
+        s.set("inner", inner.clone());
+
+        assert_eq!(s.get::<&Structure>("inner").unwrap().get(), inner.as_ref());
+        assert_eq!(OwnedStructure::from_string(&s.to_string()).unwrap(), s);
+    }
 }
@@ -0,0 +1,552 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Conversion between structures and Rust types implementing the serde traits.
+//
+// Structs become structures named after the struct, which can be changed with
+// #[serde(rename = "...")]. Their fields become structure fields, with None
+// fields being left out. Sequences and tuples become arrays, nested structs
+// become nested structures and unit enum variants become strings.
+//
+// Fractions are deserialized as (numerator, denominator) tuples, buffers as
+// byte sequences, caps and date times as their string representation and
+// enums by their nick. Ranges and flagsets are not supported.
+//
+// The other direction does not produce buffers: serializing bytes fails and
+// byte sequences like Vec<u8> become arrays of uints.
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+
+use serde::ser::{self, Serialize, Impossible};
+use serde::de::{self, Deserialize, Deserializer, Visitor, IntoDeserializer, Unexpected};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+
+use structure::*;
+use value::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error(msg.to_string())
+    }
+}
+
+pub fn to_structure<T: Serialize + ?Sized>(value: &T) -> Result<OwnedStructure, Error> {
+    let value = try!(value.serialize(ValueSerializer));
+
+    value
+        .as_ref()
+        .and_then(|v| v.try_get::<&Structure>())
+        .map(|s| s.to_owned())
+        .ok_or_else(|| Error(String::from("Only structs can be converted to structures")))
+}
+
+// The structure name is not checked against the name of the struct
+pub fn from_structure<'de, T: Deserialize<'de>>(structure: &'de Structure) -> Result<T, Error> {
+    T::deserialize(StructureDeserializer(structure))
+}
+
+fn unsupported(what: &str) -> Error {
+    Error(format!("Unsupported type: {}", what))
+}
+
+// Returns None for values that should be left out, i.e. None
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<Option<Value>, Error>;
+    type SerializeMap = Impossible<Option<Value>, Error>;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = Impossible<Option<Value>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v as i32)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v as i32)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v as u32)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v as u32)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(v)))
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Option<Value>, Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Option<Value>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<Value>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Value>, Error> {
+        Err(unsupported("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Option<Value>, Error> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_unit_variant(self,
+                              _name: &'static str,
+                              _variant_index: u32,
+                              variant: &'static str)
+                              -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self,
+                                                        _name: &'static str,
+                                                        value: &T)
+                                                        -> Result<Option<Value>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self,
+                                                         name: &'static str,
+                                                         _variant_index: u32,
+                                                         _variant: &'static str,
+                                                         _value: &T)
+                                                         -> Result<Option<Value>, Error> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_struct(self,
+                              _name: &'static str,
+                              len: usize)
+                              -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_variant(self,
+                               name: &'static str,
+                               _variant_index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Impossible<Option<Value>, Error>, Error> {
+        Err(unsupported(name))
+    }
+
+    fn serialize_map(self,
+                     _len: Option<usize>)
+                     -> Result<Impossible<Option<Value>, Error>, Error> {
+        Err(unsupported("map"))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer(OwnedStructure::new_empty(name)))
+    }
+
+    fn serialize_struct_variant(self,
+                                name: &'static str,
+                                _variant_index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Impossible<Option<Value>, Error>, Error> {
+        Err(unsupported(name))
+    }
+}
+
+struct SeqSerializer(Vec<Value>);
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match try!(value.serialize(ValueSerializer)) {
+            Some(value) => {
+                self.0.push(value);
+                Ok(())
+            }
+            None => Err(Error(String::from("None is not allowed in sequences"))),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(self.0)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(self.0)))
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(self.0)))
+    }
+}
+
+struct StructSerializer(OwnedStructure);
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self,
+                                               key: &'static str,
+                                               value: &T)
+                                               -> Result<(), Error> {
+        if let Some(value) = try!(value.serialize(ValueSerializer)) {
+            self.0.set(key, value);
+        }
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::new(self.0)))
+    }
+}
+
+struct StructureDeserializer<'de>(&'de Structure);
+
+impl<'de> Deserializer<'de> for StructureDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let fields = self.0.iter().map(|(f, v)| (f, ValueDeserializer(v)));
+        let mut map: MapDeserializer<_, Error> = MapDeserializer::new(fields);
+        let value = try!(visitor.visit_map(&mut map));
+        try!(map.end());
+
+        Ok(value)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ValueDeserializer<'de>(ValueRef<'de>);
+
+fn visit_values<'de, V: Visitor<'de>>(values: &'de [Value],
+                                      visitor: V)
+                                      -> Result<V::Value, Error> {
+    let values = values
+        .iter()
+        .map(|v| ValueDeserializer(ValueRef::from_value(v)));
+    let mut seq: SeqDeserializer<_, Error> = SeqDeserializer::new(values);
+    let value = try!(visitor.visit_seq(&mut seq));
+    try!(seq.end());
+
+    Ok(value)
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.get() {
+            ValueView::Bool(v) => visitor.visit_bool(v),
+            ValueView::Int(v) => visitor.visit_i32(v),
+            ValueView::UInt(v) => visitor.visit_u32(v),
+            ValueView::Int64(v) => visitor.visit_i64(v),
+            ValueView::UInt64(v) => visitor.visit_u64(v),
+            ValueView::Float(v) => visitor.visit_f32(v),
+            ValueView::Double(v) => visitor.visit_f64(v),
+            ValueView::Bitmask(v) => visitor.visit_u64(v.0),
+            ValueView::Enum(v) => visitor.visit_i32(v.get_value()),
+            ValueView::Flags(v) => visitor.visit_u32(v.get_value()),
+            ValueView::IntRange(_) |
+            ValueView::Int64Range(_) |
+            ValueView::DoubleRange(_) |
+            ValueView::FractionRange(_) => {
+                Err(de::Error::invalid_type(Unexpected::Other("range"), &visitor))
+            }
+            ValueView::Flagset(_) => {
+                Err(de::Error::invalid_type(Unexpected::Other("flagset"), &visitor))
+            }
+            ValueView::String(_) => {
+                visitor.visit_borrowed_str(self.0.try_get::<&'de str>().unwrap())
+            }
+            ValueView::Fraction(v) => {
+                let mut seq: SeqDeserializer<_, Error> =
+                    SeqDeserializer::new(vec![*v.numer(), *v.denom()].into_iter());
+                let value = try!(visitor.visit_seq(&mut seq));
+                try!(seq.end());
+
+                Ok(value)
+            }
+            ValueView::Buffer(buffer) => {
+                let map = try!(buffer
+                                   .map_read()
+                                   .ok_or_else(|| Error(String::from("Failed to map buffer"))));
+                let mut seq: SeqDeserializer<_, Error> =
+                    SeqDeserializer::new(map.as_slice().iter().cloned());
+                let value = try!(visitor.visit_seq(&mut seq));
+                try!(seq.end());
+
+                Ok(value)
+            }
+            ValueView::Caps(caps) => visitor.visit_string(caps.to_string()),
+            ValueView::DateTime(datetime) => visitor.visit_string(datetime.to_iso8601_string()),
+            ValueView::Structure(_) => {
+                StructureDeserializer(self.0.try_get::<&'de Structure>().unwrap())
+                    .deserialize_any(visitor)
+            }
+            ValueView::Array(_) => visit_values(self.0.try_get::<&'de [Value]>().unwrap(), visitor),
+            ValueView::List(_) => visit_values(self.0.try_get::<List<'de>>().unwrap().0, visitor),
+        }
+    }
+
+    // Missing fields are handled as None by serde already
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self,
+                                                   _name: &'static str,
+                                                   visitor: V)
+                                                   -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self,
+                                         name: &'static str,
+                                         _variants: &'static [&'static str],
+                                         visitor: V)
+                                         -> Result<V::Value, Error> {
+        if let Some(variant) = self.0.try_get::<&'de str>() {
+            return visitor.visit_enum(variant.into_deserializer());
+        }
+
+        match self.0.try_get::<EnumValue>().and_then(|v| v.get_nick()) {
+            Some(nick) => visitor.visit_enum(nick.as_str().into_deserializer()),
+            None => Err(Error(format!("Expected string or enum for enum {}", name))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+        byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+    use gst;
+
+    fn init() {
+        unsafe {
+            gst::gst_init(ptr::null_mut(), ptr::null_mut());
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "inner")]
+    struct Inner {
+        level: u32,
+        mode: Mode,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename = "test-message")]
+    struct Message {
+        name: String,
+        count: i32,
+        position: u64,
+        enabled: bool,
+        extra: Option<i64>,
+        values: Vec<i32>,
+        inner: Inner,
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        init();
+
+        let msg = Message {
+            name: String::from("foo"),
+            count: -2,
+            position: 123,
+            enabled: true,
+            extra: None,
+            values: vec![1, 2, 3],
+            inner: Inner {
+                level: 5,
+                mode: Mode::Slow,
+            },
+        };
+
+        let s = to_structure(&msg).unwrap();
+        assert_eq!(s.get_name(), "test-message");
+        assert!(!s.has_field("extra"));
+        assert_eq!(s.get::<&str>("name").unwrap().get(), "foo");
+        assert_eq!(s.get::<u64>("position").unwrap().get(), 123);
+        let inner = s.get::<&Structure>("inner").unwrap();
+        assert_eq!(inner.get().get_name(), "inner");
+        assert_eq!(inner.get().get::<&str>("mode").unwrap().get(), "slow");
+
+        let msg2: Message = from_structure(&s).unwrap();
+        assert_eq!(msg2, msg);
+    }
+
+    #[test]
+    fn test_from_string() {
+        init();
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Format {
+            Bytes,
+            Time,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Stats {
+            bitrate: u32,
+            framerate: (i32, i32),
+            codec: Option<String>,
+            ratio: f64,
+            format: Format,
+            channels: Vec<u32>,
+        }
+
+        let s = OwnedStructure::from_string("stats, bitrate=(uint)128000, \
+                                             framerate=(fraction)30/1, other=(int)1, \
+                                             ratio=(double)0.5, format=(GstFormat)time, \
+                                             channels=(uint){ 1, 2 }")
+                .unwrap();
+        let stats: Stats = from_structure(&s).unwrap();
+        assert_eq!(stats,
+                   Stats {
+                       bitrate: 128000,
+                       framerate: (30, 1),
+                       codec: None,
+                       ratio: 0.5,
+                       format: Format::Time,
+                       channels: vec![1, 2],
+                   });
+
+        let s = OwnedStructure::from_string("stats, bitrate=(string)abc").unwrap();
+        assert!(from_structure::<Stats>(&s).is_err());
+    }
+}
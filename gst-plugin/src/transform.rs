@@ -0,0 +1,612 @@
+// Copyright (C) 2017 Sebastian Dröge <sebastian@centricular.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ffi::CString;
+use std::ptr;
+use std::mem;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use slog::Logger;
+
+use utils::*;
+use error::*;
+use buffer::*;
+use miniobject::*;
+use log::*;
+use plugin::Plugin;
+use caps::*;
+
+use glib;
+use gobject;
+use gst;
+use gst_base;
+
+#[derive(Debug)]
+pub enum TransformError {
+    Failure,
+    NotImplemented,
+}
+
+impl ToGError for TransformError {
+    fn to_gerror(&self) -> (u32, i32) {
+        match *self {
+            TransformError::Failure => (gst_library_error_domain(), 1),
+            TransformError::NotImplemented => (gst_stream_error_domain(), 3),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadDirection {
+    Src,
+    Sink,
+}
+
+impl PadDirection {
+    fn from_native(direction: gst::GstPadDirection) -> PadDirection {
+        match direction {
+            gst::GST_PAD_SRC => PadDirection::Src,
+            gst::GST_PAD_SINK => PadDirection::Sink,
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub struct TransformWrapper {
+    raw: *mut gst::GstElement,
+    logger: Logger,
+    transform: Mutex<Box<Transform>>,
+    panicked: AtomicBool,
+}
+
+pub trait Transform {
+    fn start(&mut self) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // Caps on the pad with the given direction are transformed into the caps
+    // that are possible on the opposite pad. None means the same caps.
+    // The filter is only a hint, the result is intersected with it afterwards
+    fn transform_caps(&self,
+                      _direction: PadDirection,
+                      _caps: &Caps,
+                      _filter: Option<&Caps>)
+                      -> Option<GstRc<Caps>> {
+        None
+    }
+
+    fn set_caps(&mut self, _incaps: &Caps, _outcaps: &Caps) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // Size of the buffer on the opposite pad for a buffer of the given size.
+    // None means the same size.
+    fn transform_size(&self,
+                      _direction: PadDirection,
+                      _caps: &Caps,
+                      _size: usize,
+                      _othercaps: &Caps)
+                      -> Option<usize> {
+        None
+    }
+
+    // Called after set_caps, Some(..) switches passthrough mode on or off
+    fn passthrough(&self) -> Option<bool> {
+        None
+    }
+
+    fn transform(&mut self, _inbuf: &Buffer, _outbuf: &mut Buffer) -> Result<(), FlowError> {
+        Err(FlowError::Error(error_msg!(TransformError::NotImplemented,
+                                        ["Copy transform not implemented"])))
+    }
+
+    fn transform_ip(&mut self, _buffer: &mut Buffer) -> Result<(), FlowError> {
+        Err(FlowError::Error(error_msg!(TransformError::NotImplemented,
+                                        ["In-place transform not implemented"])))
+    }
+
+    // Only called in passthrough mode if transform_ip_on_passthrough is set.
+    // The buffer is not writable in that case
+    fn transform_ip_passthrough(&mut self, _buffer: &Buffer) -> Result<(), FlowError> {
+        Ok(())
+    }
+}
+
+impl TransformWrapper {
+    fn new(raw: *mut gst::GstElement, transform: Box<Transform>) -> TransformWrapper {
+        TransformWrapper {
+            raw: raw,
+            logger: Logger::root(GstDebugDrain::new(Some(unsafe { &Element::new(raw) }),
+                                                    "rstransform",
+                                                    0,
+                                                    "Rust transform base class"),
+                                 o!()),
+            transform: Mutex::new(transform),
+            panicked: AtomicBool::new(false),
+        }
+    }
+
+    fn start(&self) -> bool {
+        let transform = &mut self.transform.lock().unwrap();
+
+        debug!(self.logger, "Starting");
+
+        match transform.start() {
+            Ok(..) => {
+                trace!(self.logger, "Started successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to start: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn stop(&self) -> bool {
+        let transform = &mut self.transform.lock().unwrap();
+
+        debug!(self.logger, "Stopping");
+
+        match transform.stop() {
+            Ok(..) => {
+                trace!(self.logger, "Stopped successfully");
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to stop: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn transform_caps(&self,
+                      direction: PadDirection,
+                      caps: &Caps,
+                      filter: Option<&Caps>)
+                      -> Option<GstRc<Caps>> {
+        let transform = &self.transform.lock().unwrap();
+
+        let res = transform.transform_caps(direction, caps, filter);
+        trace!(self.logger,
+               "Transformed caps {:?} in direction {:?} with filter {:?}: {:?}",
+               caps,
+               direction,
+               filter,
+               res);
+
+        res
+    }
+
+    fn set_caps(&self, incaps: &Caps, outcaps: &Caps) -> bool {
+        let transform = &mut self.transform.lock().unwrap();
+
+        debug!(self.logger,
+               "Setting caps: input {:?}, output {:?}",
+               incaps,
+               outcaps);
+
+        match transform.set_caps(incaps, outcaps) {
+            Ok(..) => {
+                if let Some(passthrough) = transform.passthrough() {
+                    debug!(self.logger, "Setting passthrough {}", passthrough);
+                    unsafe {
+                        gst_base::gst_base_transform_set_passthrough(self.raw as
+                                                                     *mut gst_base::GstBaseTransform,
+                                                                     if passthrough {
+                                                                         glib::GTRUE
+                                                                     } else {
+                                                                         glib::GFALSE
+                                                                     });
+                    }
+                }
+                true
+            }
+            Err(ref msg) => {
+                error!(self.logger, "Failed to set caps: {:?}", msg);
+
+                self.post_message(msg);
+                false
+            }
+        }
+    }
+
+    fn transform_size(&self,
+                      direction: PadDirection,
+                      caps: &Caps,
+                      size: usize,
+                      othercaps: &Caps)
+                      -> Option<usize> {
+        let transform = &self.transform.lock().unwrap();
+
+        let res = transform.transform_size(direction, caps, size, othercaps);
+        trace!(self.logger,
+               "Transformed size {} in direction {:?}: {:?}",
+               size,
+               direction,
+               res);
+
+        res
+    }
+
+    fn transform(&self, inbuf: &Buffer, outbuf: &mut Buffer) -> gst::GstFlowReturn {
+        let transform = &mut self.transform.lock().unwrap();
+
+        trace!(self.logger, "Transforming buffer {:?} into {:?}", inbuf, outbuf);
+
+        let res = transform.transform(inbuf, outbuf);
+        self.handle_flow_result(res)
+    }
+
+    fn transform_ip(&self, buffer: &mut Buffer) -> gst::GstFlowReturn {
+        let transform = &mut self.transform.lock().unwrap();
+
+        trace!(self.logger, "Transforming buffer {:?} in-place", buffer);
+
+        let res = transform.transform_ip(buffer);
+        self.handle_flow_result(res)
+    }
+
+    fn transform_ip_passthrough(&self, buffer: &Buffer) -> gst::GstFlowReturn {
+        let transform = &mut self.transform.lock().unwrap();
+
+        trace!(self.logger, "Handling passthrough buffer {:?}", buffer);
+
+        let res = transform.transform_ip_passthrough(buffer);
+        self.handle_flow_result(res)
+    }
+
+    fn handle_flow_result(&self, res: Result<(), FlowError>) -> gst::GstFlowReturn {
+        match res {
+            Ok(()) => gst::GST_FLOW_OK,
+            Err(flow_error) => {
+                error!(self.logger, "Failed to transform: {:?}", flow_error);
+                match flow_error {
+                    FlowError::NotNegotiated(ref msg) |
+                    FlowError::Error(ref msg) => self.post_message(msg),
+                    _ => (),
+                }
+                flow_error.to_native()
+            }
+        }
+    }
+
+    fn post_message(&self, msg: &ErrorMessage) {
+        unsafe {
+            msg.post(self.raw);
+        }
+    }
+}
+
+unsafe extern "C" fn transform_start(ptr: *mut gst_base::GstBaseTransform) -> glib::gboolean {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.start() {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn transform_stop(ptr: *mut gst_base::GstBaseTransform) -> glib::gboolean {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, glib::GTRUE, {
+        if wrap.stop() {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn transform_transform_caps(ptr: *mut gst_base::GstBaseTransform,
+                                              direction: gst::GstPadDirection,
+                                              caps: *mut gst::GstCaps,
+                                              filter: *mut gst::GstCaps)
+                                              -> *mut gst::GstCaps {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, ptr::null_mut(), {
+        let filter_ref = if filter.is_null() {
+            None
+        } else {
+            Some(Caps::from_ptr(filter))
+        };
+
+        match wrap.transform_caps(PadDirection::from_native(direction),
+                                  Caps::from_ptr(caps),
+                                  filter_ref) {
+            Some(caps) => {
+                if filter.is_null() {
+                    caps.into_ptr()
+                } else {
+                    gst::gst_caps_intersect_full(filter,
+                                                 caps.as_ptr() as *mut gst::GstCaps,
+                                                 gst::GST_CAPS_INTERSECT_FIRST)
+                }
+            }
+            None => {
+                let transform_klass = &**(ptr as *const *const RsTransformClass);
+                let parent_klass =
+                    &*(transform_klass.parent_vtable as *const gst_base::GstBaseTransformClass);
+                parent_klass
+                    .transform_caps
+                    .map(|f| f(ptr, direction, caps, filter))
+                    .unwrap_or(ptr::null_mut())
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn transform_set_caps(ptr: *mut gst_base::GstBaseTransform,
+                                        incaps: *mut gst::GstCaps,
+                                        outcaps: *mut gst::GstCaps)
+                                        -> glib::gboolean {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        if wrap.set_caps(Caps::from_ptr(incaps), Caps::from_ptr(outcaps)) {
+            glib::GTRUE
+        } else {
+            glib::GFALSE
+        }
+    })
+}
+
+unsafe extern "C" fn transform_transform_size(ptr: *mut gst_base::GstBaseTransform,
+                                              direction: gst::GstPadDirection,
+                                              caps: *mut gst::GstCaps,
+                                              size: usize,
+                                              othercaps: *mut gst::GstCaps,
+                                              othersize: *mut usize)
+                                              -> glib::gboolean {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, glib::GFALSE, {
+        match wrap.transform_size(PadDirection::from_native(direction),
+                                  Caps::from_ptr(caps),
+                                  size,
+                                  Caps::from_ptr(othercaps)) {
+            Some(res) => {
+                *othersize = res;
+                glib::GTRUE
+            }
+            None => {
+                let transform_klass = &**(ptr as *const *const RsTransformClass);
+                let parent_klass =
+                    &*(transform_klass.parent_vtable as *const gst_base::GstBaseTransformClass);
+                parent_klass
+                    .transform_size
+                    .map(|f| f(ptr, direction, caps, size, othercaps, othersize))
+                    .unwrap_or(glib::GFALSE)
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn transform_transform(ptr: *mut gst_base::GstBaseTransform,
+                                         inbuf: *mut gst::GstBuffer,
+                                         outbuf: *mut gst::GstBuffer)
+                                         -> gst::GstFlowReturn {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+    let inbuf: &Buffer = Buffer::from_ptr(inbuf);
+    let outbuf: &mut Buffer = <Buffer as MiniObject>::from_mut_ptr(outbuf);
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        wrap.transform(inbuf, outbuf)
+    })
+}
+
+unsafe extern "C" fn transform_transform_ip(ptr: *mut gst_base::GstBaseTransform,
+                                            buffer: *mut gst::GstBuffer)
+                                            -> gst::GstFlowReturn {
+    let transform = &*(ptr as *const RsTransform);
+    let wrap: &TransformWrapper = &*transform.wrap;
+
+    panic_to_error!(wrap, gst::GST_FLOW_ERROR, {
+        // In passthrough mode the buffer is the one that is pushed downstream
+        // unchanged and is not necessarily writable
+        if gst_base::gst_base_transform_is_passthrough(ptr) == glib::GTRUE {
+            wrap.transform_ip_passthrough(Buffer::from_ptr(buffer))
+        } else {
+            wrap.transform_ip(<Buffer as MiniObject>::from_mut_ptr(buffer))
+        }
+    })
+}
+
+pub struct TransformInfo {
+    pub name: String,
+    pub long_name: String,
+    pub description: String,
+    pub classification: String,
+    pub author: String,
+    pub rank: u32,
+    pub create_instance: fn(Element) -> Box<Transform>,
+    pub input_caps: GstRc<Caps>,
+    pub output_caps: GstRc<Caps>,
+    pub in_place: bool,
+    pub passthrough: bool,
+    pub passthrough_on_same_caps: bool,
+    pub transform_ip_on_passthrough: bool,
+}
+
+#[repr(C)]
+struct RsTransform {
+    parent: gst_base::GstBaseTransform,
+    wrap: *mut TransformWrapper,
+    transform_info: *const TransformInfo,
+}
+
+#[repr(C)]
+struct RsTransformClass {
+    parent_class: gst_base::GstBaseTransformClass,
+    transform_info: *const TransformInfo,
+    parent_vtable: glib::gconstpointer,
+}
+
+unsafe extern "C" fn transform_finalize(obj: *mut gobject::GObject) {
+    let transform = &mut *(obj as *mut RsTransform);
+
+    drop(Box::from_raw(transform.wrap));
+
+    let transform_klass = &**(obj as *const *const RsTransformClass);
+    let parent_klass = &*(transform_klass.parent_vtable as *const gobject::GObjectClass);
+    parent_klass.finalize.map(|f| f(obj));
+}
+
+unsafe extern "C" fn transform_class_init(klass: glib::gpointer, klass_data: glib::gpointer) {
+    let transform_klass = &mut *(klass as *mut RsTransformClass);
+    let transform_info = &*(klass_data as *const TransformInfo);
+
+    {
+        let gobject_klass = &mut transform_klass
+                                     .parent_class
+                                     .parent_class
+                                     .parent_class
+                                     .parent_class;
+        gobject_klass.finalize = Some(transform_finalize);
+    }
+
+    {
+        let element_klass = &mut transform_klass.parent_class.parent_class;
+
+        let longname_cstr = CString::new(transform_info.long_name.clone()).unwrap();
+        let classification_cstr = CString::new(transform_info.classification.clone()).unwrap();
+        let description_cstr = CString::new(transform_info.description.clone()).unwrap();
+        let author_cstr = CString::new(transform_info.author.clone()).unwrap();
+
+        gst::gst_element_class_set_static_metadata(element_klass,
+                                                   longname_cstr.into_raw(),
+                                                   classification_cstr.into_raw(),
+                                                   description_cstr.into_raw(),
+                                                   author_cstr.into_raw());
+
+        let templ_name = CString::new("sink").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SINK,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     transform_info.input_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+
+        let templ_name = CString::new("src").unwrap();
+        let pad_template = gst::gst_pad_template_new(templ_name.into_raw(),
+                                                     gst::GST_PAD_SRC,
+                                                     gst::GST_PAD_ALWAYS,
+                                                     transform_info.output_caps.as_ptr() as
+                                                     *mut gst::GstCaps);
+        gst::gst_element_class_add_pad_template(element_klass, pad_template);
+    }
+
+    {
+        let basetransform_klass = &mut transform_klass.parent_class;
+        basetransform_klass.start = Some(transform_start);
+        basetransform_klass.stop = Some(transform_stop);
+        basetransform_klass.transform_caps = Some(transform_transform_caps);
+        basetransform_klass.set_caps = Some(transform_set_caps);
+        basetransform_klass.transform_size = Some(transform_transform_size);
+        basetransform_klass.transform = Some(transform_transform);
+        basetransform_klass.transform_ip = Some(transform_transform_ip);
+        basetransform_klass.passthrough_on_same_caps =
+            if transform_info.passthrough_on_same_caps {
+                glib::GTRUE
+            } else {
+                glib::GFALSE
+            };
+        basetransform_klass.transform_ip_on_passthrough =
+            if transform_info.transform_ip_on_passthrough {
+                glib::GTRUE
+            } else {
+                glib::GFALSE
+            };
+    }
+
+    transform_klass.transform_info = transform_info;
+    transform_klass.parent_vtable = gobject::g_type_class_peek_parent(klass);
+}
+
+unsafe extern "C" fn transform_init(instance: *mut gobject::GTypeInstance, klass: glib::gpointer) {
+    let transform = &mut *(instance as *mut RsTransform);
+    let transform_klass = &*(klass as *const RsTransformClass);
+    let transform_info = &*transform_klass.transform_info;
+
+    transform.transform_info = transform_info;
+
+    let wrap = Box::new(TransformWrapper::new(&mut transform.parent.element,
+            (transform_info.create_instance)(Element::new(&mut transform.parent.element))));
+    transform.wrap = Box::into_raw(wrap);
+
+    gst_base::gst_base_transform_set_in_place(&mut transform.parent,
+                                              if transform_info.in_place {
+                                                  glib::GTRUE
+                                              } else {
+                                                  glib::GFALSE
+                                              });
+    gst_base::gst_base_transform_set_passthrough(&mut transform.parent,
+                                                 if transform_info.passthrough {
+                                                     glib::GTRUE
+                                                 } else {
+                                                     glib::GFALSE
+                                                 });
+}
+
+pub fn transform_register(plugin: &Plugin, transform_info: TransformInfo) {
+    unsafe {
+        let parent_type = gst_base::gst_base_transform_get_type();
+        let mut type_name = String::from("RsTransform-");
+        type_name.push_str(&transform_info.name);
+        let type_name_cstr = CString::new(type_name.into_bytes()).unwrap();
+
+        let name_cstr = CString::new(transform_info.name.clone().into_bytes()).unwrap();
+        let rank = transform_info.rank;
+
+        let transform_info = Box::new(transform_info);
+        let transform_info_ptr = Box::into_raw(transform_info) as glib::gpointer;
+
+        let type_info = gobject::GTypeInfo {
+            class_size: mem::size_of::<RsTransformClass>() as u16,
+            base_init: None,
+            base_finalize: None,
+            class_init: Some(transform_class_init),
+            class_finalize: None,
+            class_data: transform_info_ptr,
+            instance_size: mem::size_of::<RsTransform>() as u16,
+            n_preallocs: 0,
+            instance_init: Some(transform_init),
+            value_table: ptr::null(),
+        };
+
+        let type_ = gobject::g_type_register_static(parent_type,
+                                                    type_name_cstr.as_ptr(),
+                                                    &type_info,
+                                                    gobject::GTypeFlags::empty());
+
+        gst::gst_element_register(plugin.as_ptr(), name_cstr.as_ptr(), rank, type_);
+    }
+}
@@ -6,11 +6,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use libc::c_char;
-use std::ffi::CString;
 use std::i32;
+use std::ffi::CString;
+use std::mem;
+use std::slice;
 use num_rational::Rational32;
 
+use value::Value;
+use signals::EmitSignalError;
+
+use glib;
+use gobject;
 use gst;
 
 pub struct Element(*mut gst::GstElement);
@@ -29,6 +35,70 @@ impl Element {
     pub unsafe fn as_ptr(&self) -> *mut gst::GstElement {
         self.0
     }
+
+    // Signal handlers are called synchronously from here. Returns the return
+    // value of the signal, if it has one
+    pub fn emit_signal(&self,
+                       name: &str,
+                       args: &[Value])
+                       -> Result<Option<Value>, EmitSignalError> {
+        unsafe {
+            let type_ = (*(*(self.0 as *const gobject::GTypeInstance)).g_class).g_type;
+
+            let name_cstr = match CString::new(name) {
+                Ok(name_cstr) => name_cstr,
+                Err(..) => return Err(EmitSignalError::UnknownSignal),
+            };
+            let signal_id = gobject::g_signal_lookup(name_cstr.as_ptr(), type_);
+            if signal_id == 0 {
+                return Err(EmitSignalError::UnknownSignal);
+            }
+
+            let mut query = mem::zeroed();
+            gobject::g_signal_query(signal_id, &mut query);
+            if query.n_params as usize != args.len() {
+                return Err(EmitSignalError::WrongNumberOfArguments(query.n_params as usize));
+            }
+
+            if !args.is_empty() {
+                // The lowest bit is G_SIGNAL_TYPE_STATIC_SCOPE, not part of the type
+                let param_types = slice::from_raw_parts(query.param_types, args.len());
+                for (i, (arg, param_type)) in args.iter().zip(param_types).enumerate() {
+                    let param_type = *param_type & !gobject::G_TYPE_FLAG_RESERVED_ID_BIT;
+                    if gobject::g_type_is_a((*arg.as_ptr()).g_type, param_type) ==
+                       glib::GFALSE {
+                        return Err(EmitSignalError::WrongArgumentType(i));
+                    }
+                }
+            }
+
+            let mut values = Vec::with_capacity(args.len() + 1);
+            let mut instance = mem::zeroed();
+            gobject::g_value_init(&mut instance, type_);
+            gobject::g_value_set_object(&mut instance, self.0 as glib::gpointer);
+            values.push(instance);
+            for arg in args {
+                values.push(arg.clone().into_raw());
+            }
+
+            let mut return_value: gobject::GValue = mem::zeroed();
+            if query.return_type != gobject::G_TYPE_NONE {
+                gobject::g_value_init(&mut return_value, query.return_type);
+            }
+
+            gobject::g_signal_emitv(values.as_ptr(), signal_id, 0, &mut return_value);
+
+            for value in &mut values {
+                gobject::g_value_unset(value);
+            }
+
+            if query.return_type == gobject::G_TYPE_NONE {
+                Ok(None)
+            } else {
+                Ok(Value::from_raw(return_value))
+            }
+        }
+    }
 }
 
 impl Drop for Element {
@@ -45,9 +115,14 @@ impl Clone for Element {
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn cstring_drop(ptr: *mut c_char) {
-    let _ = CString::from_raw(ptr);
+// Interrupts blocking operations of a source or sink from another thread.
+// Called without holding the lock that fill()/render() run under.
+pub trait Unlock: Send + Sync {
+    // Make any currently blocking and all following operations return
+    // FlowError::Flushing as soon as possible
+    fn unlock(&self);
+    // Clear the state set by unlock() again
+    fn unlock_stop(&self);
 }
 
 pub fn f64_to_fraction(val: f64) -> Option<Rational32> {
@@ -6,10 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::marker::PhantomData;
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt;
 use std::slice;
 use libc::c_char;
@@ -17,32 +18,121 @@ use libc::c_char;
 pub use num_rational::Rational32;
 
 use buffer::*;
+use caps::Caps;
+use datetime::DateTime;
 use miniobject::*;
+use structure::{Structure, OwnedStructure};
 
 use glib;
 use gobject;
 use gst;
 
+// Value, ValueRef and ValueView are only PartialEq and not Eq anymore: they
+// can hold doubles, and values that gst_value_compare() considers unordered.
+// TypedValue and TypedValueRef are Eq if the contained type is
 #[repr(C)]
 pub struct Value(gobject::GValue);
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+// Equality of views is the equality of the corresponding values, e.g. the
+// order of list items does not matter
+#[derive(Clone, Debug)]
 pub enum ValueView<'a> {
     Bool(bool),
     Int(i32),
     UInt(u32),
     Int64(i64),
     UInt64(u64),
+    Float(f32),
+    Double(f64),
     String(Cow<'a, str>),
     Fraction(Rational32),
+    IntRange(IntRange<i32>),
+    Int64Range(IntRange<i64>),
+    DoubleRange(DoubleRange),
+    FractionRange(FractionRange),
+    Bitmask(Bitmask),
+    Flagset(Flagset),
+    Enum(EnumValue),
+    Flags(FlagsValue),
     Buffer(GstRc<Buffer>),
+    Caps(GstRc<Caps>),
+    Structure(Cow<'a, Structure>),
+    DateTime(DateTime),
     Array(Cow<'a, [Value]>),
+    List(Cow<'a, [Value]>),
 }
 
 impl<'a> ValueView<'a> {
     pub fn try_get<T: ValueType<'a>>(&'a self) -> Option<T> {
         T::from_value_view(self)
     }
+
+    fn from_gvalue(value: &'a gobject::GValue) -> ValueView<'a> {
+        match value.g_type {
+            gobject::G_TYPE_BOOLEAN => ValueView::Bool(bool::from_value(value).unwrap()),
+            gobject::G_TYPE_INT => ValueView::Int(i32::from_value(value).unwrap()),
+            gobject::G_TYPE_UINT => ValueView::UInt(u32::from_value(value).unwrap()),
+            gobject::G_TYPE_INT64 => ValueView::Int64(i64::from_value(value).unwrap()),
+            gobject::G_TYPE_UINT64 => ValueView::UInt64(u64::from_value(value).unwrap()),
+            gobject::G_TYPE_FLOAT => ValueView::Float(f32::from_value(value).unwrap()),
+            gobject::G_TYPE_DOUBLE => ValueView::Double(f64::from_value(value).unwrap()),
+            typ if typ == *TYPE_FRACTION => {
+                ValueView::Fraction(Rational32::from_value(value).unwrap())
+            }
+            gobject::G_TYPE_STRING => {
+                ValueView::String(Cow::Borrowed(<&str as ValueType>::from_value(value).unwrap()))
+            }
+            typ if typ == *TYPE_INT_RANGE => {
+                ValueView::IntRange(<IntRange<i32> as ValueType>::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_INT64_RANGE => {
+                ValueView::Int64Range(<IntRange<i64> as ValueType>::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_DOUBLE_RANGE => {
+                ValueView::DoubleRange(DoubleRange::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_FRACTION_RANGE => {
+                ValueView::FractionRange(FractionRange::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_BITMASK => ValueView::Bitmask(Bitmask::from_value(value).unwrap()),
+            typ if typ == *TYPE_GST_VALUE_ARRAY => {
+                ValueView::Array(Cow::Borrowed(<&[Value] as ValueType>::from_value(value)
+                                                   .unwrap()))
+            }
+            typ if typ == *TYPE_GST_VALUE_LIST => {
+                ValueView::List(Cow::Borrowed(List::from_value(value).unwrap().0))
+            }
+            typ if typ == *TYPE_BUFFER => {
+                ValueView::Buffer(<GstRc<Buffer> as ValueType>::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_CAPS => {
+                ValueView::Caps(<GstRc<Caps> as ValueType>::from_value(value).unwrap())
+            }
+            typ if typ == *TYPE_STRUCTURE => {
+                ValueView::Structure(Cow::Borrowed(<&Structure as ValueType>::from_value(value)
+                                                       .unwrap()))
+            }
+            typ if typ == *TYPE_DATE_TIME => {
+                ValueView::DateTime(DateTime::from_value(value).unwrap())
+            }
+            typ if Flagset::is_value_type(typ) => {
+                ValueView::Flagset(Flagset::from_value(value).unwrap())
+            }
+            typ if EnumValue::is_value_type(typ) => {
+                ValueView::Enum(EnumValue::from_value(value).unwrap())
+            }
+            typ if FlagsValue::is_value_type(typ) => {
+                ValueView::Flags(FlagsValue::from_value(value).unwrap())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'a> PartialEq for ValueView<'a> {
+    fn eq(&self, other: &ValueView<'a>) -> bool {
+        Value::from(self.clone()) == Value::from(other.clone())
+    }
 }
 
 pub trait ValueType<'a>
@@ -50,14 +140,230 @@ pub trait ValueType<'a>
 {
     fn g_type() -> glib::GType;
 
+    // Whether values of the given type can be retrieved as Self. Types
+    // covering whole type hierarchies like enums override this
+    fn is_value_type(typ: glib::GType) -> bool {
+        typ == Self::g_type()
+    }
+
     fn from_value(v: &'a gobject::GValue) -> Option<Self>;
     fn from_value_view(v: &'a ValueView<'a>) -> Option<Self>;
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IntRange<T> {
+    pub min: T,
+    pub max: T,
+    pub step: T,
+}
+
+impl<T: From<u8>> IntRange<T> {
+    pub fn new(min: T, max: T) -> IntRange<T> {
+        IntRange::new_with_step(min, max, T::from(1))
+    }
+
+    pub fn new_with_step(min: T, max: T, step: T) -> IntRange<T> {
+        IntRange {
+            min: min,
+            max: max,
+            step: step,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DoubleRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DoubleRange {
+    pub fn new(min: f64, max: f64) -> DoubleRange {
+        DoubleRange { min: min, max: max }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FractionRange {
+    pub min: Rational32,
+    pub max: Rational32,
+}
+
+impl FractionRange {
+    pub fn new(min: Rational32, max: Rational32) -> FractionRange {
+        FractionRange { min: min, max: max }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bitmask(pub u64);
+
+// Flags together with the mask of flags that are actually set or unset
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Flagset {
+    g_type: glib::GType,
+    flags: u32,
+    mask: u32,
+}
+
+impl Flagset {
+    pub fn new(flags: u32, mask: u32) -> Flagset {
+        Flagset {
+            g_type: *TYPE_FLAGSET,
+            flags: flags,
+            mask: mask,
+        }
+    }
+
+    // For flagset subtypes, e.g. registered for a specific flags type
+    pub fn new_with_type(g_type: glib::GType, flags: u32, mask: u32) -> Flagset {
+        assert!(Flagset::is_value_type(g_type));
+
+        Flagset {
+            g_type: g_type,
+            flags: flags,
+            mask: mask,
+        }
+    }
+
+    pub fn get_type(&self) -> glib::GType {
+        self.g_type
+    }
+
+    pub fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    pub fn get_mask(&self) -> u32 {
+        self.mask
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EnumValue {
+    g_type: glib::GType,
+    value: i32,
+}
+
+impl EnumValue {
+    pub fn new(g_type: glib::GType, value: i32) -> EnumValue {
+        assert!(EnumValue::is_value_type(g_type));
+
+        EnumValue {
+            g_type: g_type,
+            value: value,
+        }
+    }
+
+    pub fn from_nick(g_type: glib::GType, nick: &str) -> Option<EnumValue> {
+        if !EnumValue::is_value_type(g_type) {
+            return None;
+        }
+
+        unsafe {
+            let klass = gobject::g_type_class_ref(g_type) as *mut gobject::GEnumClass;
+            let nick_cstr = CString::new(nick).unwrap();
+            let enum_value = gobject::g_enum_get_value_by_nick(klass, nick_cstr.as_ptr());
+
+            let res = if enum_value.is_null() {
+                None
+            } else {
+                Some(EnumValue::new(g_type, (*enum_value).value))
+            };
+            gobject::g_type_class_unref(klass as glib::gpointer);
+
+            res
+        }
+    }
+
+    pub fn get_type(&self) -> glib::GType {
+        self.g_type
+    }
+
+    pub fn get_value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn get_nick(&self) -> Option<String> {
+        unsafe {
+            let klass = gobject::g_type_class_ref(self.g_type) as *mut gobject::GEnumClass;
+            let enum_value = gobject::g_enum_get_value(klass, self.value);
+
+            let res = if enum_value.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr((*enum_value).value_nick)
+                         .to_str()
+                         .unwrap()
+                         .into())
+            };
+            gobject::g_type_class_unref(klass as glib::gpointer);
+
+            res
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FlagsValue {
+    g_type: glib::GType,
+    value: u32,
+}
+
+impl FlagsValue {
+    pub fn new(g_type: glib::GType, value: u32) -> FlagsValue {
+        assert!(FlagsValue::is_value_type(g_type));
+
+        FlagsValue {
+            g_type: g_type,
+            value: value,
+        }
+    }
+
+    pub fn get_type(&self) -> glib::GType {
+        self.g_type
+    }
+
+    pub fn get_value(&self) -> u32 {
+        self.value
+    }
+}
+
+// Unordered list of values, unlike arrays
+#[derive(Clone, Copy, Debug)]
+pub struct List<'a>(pub &'a [Value]);
+
+impl<'a> PartialEq for List<'a> {
+    fn eq(&self, other: &List<'a>) -> bool {
+        Value::from(*self) == Value::from(*other)
+    }
+}
+
 lazy_static! {
     static ref TYPE_BUFFER: glib::GType = unsafe { gst::gst_buffer_get_type() };
+    static ref TYPE_CAPS: glib::GType = unsafe { gst::gst_caps_get_type() };
+    static ref TYPE_STRUCTURE: glib::GType = unsafe { gst::gst_structure_get_type() };
+    static ref TYPE_DATE_TIME: glib::GType = unsafe { gst::gst_date_time_get_type() };
     static ref TYPE_FRACTION: glib::GType = unsafe { gst::gst_fraction_get_type() };
+    static ref TYPE_INT_RANGE: glib::GType = unsafe { gst::gst_int_range_get_type() };
+    static ref TYPE_INT64_RANGE: glib::GType = unsafe { gst::gst_int64_range_get_type() };
+    static ref TYPE_DOUBLE_RANGE: glib::GType = unsafe { gst::gst_double_range_get_type() };
+    static ref TYPE_FRACTION_RANGE: glib::GType = unsafe { gst::gst_fraction_range_get_type() };
+    static ref TYPE_BITMASK: glib::GType = unsafe { gst::gst_bitmask_get_type() };
+    static ref TYPE_FLAGSET: glib::GType = unsafe { gst::gst_flagset_get_type() };
     static ref TYPE_GST_VALUE_ARRAY: glib::GType = unsafe { gst::gst_value_array_get_type() };
+    static ref TYPE_GST_VALUE_LIST: glib::GType = unsafe { gst::gst_value_list_get_type() };
+}
+
+// Compares according to the GStreamer value semantics, None if the values
+// can't be ordered
+fn compare_gvalues(v1: &gobject::GValue, v2: &gobject::GValue) -> Option<Ordering> {
+    match unsafe { gst::gst_value_compare(v1, v2) } {
+        gst::GST_VALUE_LESS_THAN => Some(Ordering::Less),
+        gst::GST_VALUE_EQUAL => Some(Ordering::Equal),
+        gst::GST_VALUE_GREATER_THAN => Some(Ordering::Greater),
+        _ => None,
+    }
 }
 
 impl Value {
@@ -77,6 +383,14 @@ impl Value {
         Some(value)
     }
 
+    pub unsafe fn from_mut_ptr<'a>(ptr: *mut gobject::GValue) -> Option<&'a mut Value> {
+        if ptr.is_null() || !Value::is_supported_type((*ptr).g_type) {
+            return None;
+        }
+
+        Some(&mut *(ptr as *mut Value))
+    }
+
     pub fn from_value_ref<'a>(v: &ValueRef<'a>) -> Value {
         unsafe { Value::from_ptr(v.0) }.unwrap()
     }
@@ -95,6 +409,15 @@ impl Value {
         v
     }
 
+    // Like from_raw, but unsets unsupported values instead of leaking them
+    unsafe fn from_raw_or_unset(mut value: gobject::GValue) -> Option<Value> {
+        if !Value::is_supported_type(value.g_type) {
+            gobject::g_value_unset(&mut value);
+            return None;
+        }
+        Some(Value(value))
+    }
+
     fn is_supported_type(typ: glib::GType) -> bool {
         match typ {
             gobject::G_TYPE_BOOLEAN |
@@ -102,10 +425,24 @@ impl Value {
             gobject::G_TYPE_UINT |
             gobject::G_TYPE_INT64 |
             gobject::G_TYPE_UINT64 |
+            gobject::G_TYPE_FLOAT |
+            gobject::G_TYPE_DOUBLE |
             gobject::G_TYPE_STRING => true,
             typ if typ == *TYPE_FRACTION => true,
+            typ if typ == *TYPE_INT_RANGE => true,
+            typ if typ == *TYPE_INT64_RANGE => true,
+            typ if typ == *TYPE_DOUBLE_RANGE => true,
+            typ if typ == *TYPE_FRACTION_RANGE => true,
+            typ if typ == *TYPE_BITMASK => true,
             //typ if typ == *TYPE_BUFFER  => true
             typ if typ == *TYPE_GST_VALUE_ARRAY => true,
+            typ if typ == *TYPE_GST_VALUE_LIST => true,
+            typ if typ == *TYPE_CAPS => true,
+            typ if typ == *TYPE_STRUCTURE => true,
+            typ if typ == *TYPE_DATE_TIME => true,
+            typ if Flagset::is_value_type(typ) => true,
+            typ if EnumValue::is_value_type(typ) => true,
+            typ if FlagsValue::is_value_type(typ) => true,
             _ => false,
         }
     }
@@ -121,40 +458,69 @@ impl Value {
             ValueView::UInt(v) => Value::from(v),
             ValueView::Int64(v) => Value::from(v),
             ValueView::UInt64(v) => Value::from(v),
+            ValueView::Float(v) => Value::from(v),
+            ValueView::Double(v) => Value::from(v),
             ValueView::Fraction(v) => Value::from(v),
+            ValueView::IntRange(v) => Value::from(v),
+            ValueView::Int64Range(v) => Value::from(v),
+            ValueView::DoubleRange(v) => Value::from(v),
+            ValueView::FractionRange(v) => Value::from(v),
+            ValueView::Bitmask(v) => Value::from(v),
+            ValueView::Flagset(v) => Value::from(v),
+            ValueView::Enum(v) => Value::from(v),
+            ValueView::Flags(v) => Value::from(v),
             ValueView::String(v) => Value::from(v),
             ValueView::Array(v) => Value::from(v),
+            ValueView::List(v) => Value::from(List(v.as_ref())),
             ValueView::Buffer(v) => Value::from(v),
+            ValueView::Caps(v) => Value::from(v),
+            ValueView::Structure(v) => Value::from(v),
+            ValueView::DateTime(v) => Value::from(v),
         }
     }
 
     pub fn get(&self) -> ValueView {
-        match self.0.g_type {
-            gobject::G_TYPE_BOOLEAN => ValueView::Bool(bool::from_value(&self.0).unwrap()),
-            gobject::G_TYPE_INT => ValueView::Int(i32::from_value(&self.0).unwrap()),
-            gobject::G_TYPE_UINT => ValueView::UInt(u32::from_value(&self.0).unwrap()),
-            gobject::G_TYPE_INT64 => ValueView::Int64(i64::from_value(&self.0).unwrap()),
-            gobject::G_TYPE_UINT64 => ValueView::UInt64(u64::from_value(&self.0).unwrap()),
-            typ if typ == *TYPE_FRACTION => {
-                ValueView::Fraction(Rational32::from_value(&self.0).unwrap())
-            }
-            gobject::G_TYPE_STRING => {
-                ValueView::String(Cow::Borrowed(<&str as ValueType>::from_value(&self.0).unwrap()))
-            }
-            typ if typ == *TYPE_GST_VALUE_ARRAY => {
-                ValueView::Array(Cow::Borrowed(<&[Value] as ValueType>::from_value(&self.0)
-                                                   .unwrap()))
-            }
-            typ if typ == *TYPE_BUFFER => {
-                ValueView::Buffer(<GstRc<Buffer> as ValueType>::from_value(&self.0).unwrap())
-            }
-            _ => unreachable!(),
-        }
+        ValueView::from_gvalue(&self.0)
     }
 
     pub fn try_get<'a, T: ValueType<'a>>(&'a self) -> Option<T> {
         T::from_value(&self.0)
     }
+
+    // Fixed values are neither ranges nor lists
+    pub fn is_fixed(&self) -> bool {
+        unsafe { gst::gst_value_is_fixed(&self.0) == glib::GTRUE }
+    }
+
+    pub fn is_subset(&self, superset: &Value) -> bool {
+        unsafe { gst::gst_value_is_subset(&self.0, &superset.0) == glib::GTRUE }
+    }
+
+    pub fn can_intersect(&self, other: &Value) -> bool {
+        unsafe { gst::gst_value_can_intersect(&self.0, &other.0) == glib::GTRUE }
+    }
+
+    pub fn intersect(&self, other: &Value) -> Option<Value> {
+        unsafe {
+            let mut value = mem::zeroed();
+            if gst::gst_value_intersect(&mut value, &self.0, &other.0) == glib::GTRUE {
+                Value::from_raw_or_unset(value)
+            } else {
+                None
+            }
+        }
+    }
+
+    pub fn union(&self, other: &Value) -> Option<Value> {
+        unsafe {
+            let mut value = mem::zeroed();
+            if gst::gst_value_union(&mut value, &self.0, &other.0) == glib::GTRUE {
+                Value::from_raw_or_unset(value)
+            } else {
+                None
+            }
+        }
+    }
 }
 
 impl Clone for Value {
@@ -171,14 +537,19 @@ impl Clone for Value {
 
 impl PartialEq for Value {
     fn eq(&self, other: &Value) -> bool {
-        self.get().eq(&other.get())
+        compare_gvalues(&self.0, &other.0) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        compare_gvalues(&self.0, &other.0)
     }
 }
-impl Eq for Value {}
 
 impl<'a> PartialEq<ValueRef<'a>> for Value {
     fn eq(&self, other: &ValueRef<'a>) -> bool {
-        self.get().eq(&other.get())
+        compare_gvalues(&self.0, other.0) == Some(Ordering::Equal)
     }
 }
 
@@ -219,27 +590,7 @@ impl<'a> ValueRef<'a> {
     }
 
     pub fn get(&self) -> ValueView {
-        match self.0.g_type {
-            gobject::G_TYPE_BOOLEAN => ValueView::Bool(bool::from_value(self.0).unwrap()),
-            gobject::G_TYPE_INT => ValueView::Int(i32::from_value(self.0).unwrap()),
-            gobject::G_TYPE_UINT => ValueView::UInt(u32::from_value(self.0).unwrap()),
-            gobject::G_TYPE_INT64 => ValueView::Int64(i64::from_value(self.0).unwrap()),
-            gobject::G_TYPE_UINT64 => ValueView::UInt64(u64::from_value(self.0).unwrap()),
-            typ if typ == *TYPE_FRACTION => {
-                ValueView::Fraction(Rational32::from_value(self.0).unwrap())
-            }
-            gobject::G_TYPE_STRING => {
-                ValueView::String(Cow::Borrowed(<&str as ValueType>::from_value(self.0).unwrap()))
-            }
-            typ if typ == *TYPE_GST_VALUE_ARRAY => {
-                ValueView::Array(Cow::Borrowed(<&[Value] as ValueType>::from_value(self.0)
-                                                   .unwrap()))
-            }
-            typ if typ == *TYPE_BUFFER => {
-                ValueView::Buffer(<GstRc<Buffer> as ValueType>::from_value(self.0).unwrap())
-            }
-            _ => unreachable!(),
-        }
+        ValueView::from_gvalue(self.0)
     }
 
     pub fn try_get<T: ValueType<'a>>(&self) -> Option<T> {
@@ -249,14 +600,19 @@ impl<'a> ValueRef<'a> {
 
 impl<'a> PartialEq for ValueRef<'a> {
     fn eq(&self, other: &ValueRef<'a>) -> bool {
-        self.get().eq(&other.get())
+        compare_gvalues(self.0, other.0) == Some(Ordering::Equal)
+    }
+}
+
+impl<'a> PartialOrd for ValueRef<'a> {
+    fn partial_cmp(&self, other: &ValueRef<'a>) -> Option<Ordering> {
+        compare_gvalues(self.0, other.0)
     }
 }
-impl<'a> Eq for ValueRef<'a> {}
 
 impl<'a> PartialEq<Value> for ValueRef<'a> {
     fn eq(&self, other: &Value) -> bool {
-        self.get().eq(&other.get())
+        compare_gvalues(self.0, &other.0) == Some(Ordering::Equal)
     }
 }
 
@@ -346,6 +702,162 @@ impl_value_type_simple!(Rational32,
                             gst::gst_value_set_fraction(value, *v.numer(), *v.denom())
                         });
 
+impl_value_type_simple!(f32,
+                        Float,
+                        gobject::G_TYPE_FLOAT,
+                        |value: &gobject::GValue| gobject::g_value_get_float(value),
+                        |value: &mut gobject::GValue, v| gobject::g_value_set_float(value, v));
+impl_value_type_simple!(f64,
+                        Double,
+                        gobject::G_TYPE_DOUBLE,
+                        |value: &gobject::GValue| gobject::g_value_get_double(value),
+                        |value: &mut gobject::GValue, v| gobject::g_value_set_double(value, v));
+impl_value_type_simple!(IntRange<i32>,
+                        IntRange,
+                        *TYPE_INT_RANGE,
+                        |value: &gobject::GValue| {
+                            IntRange::new_with_step(gst::gst_value_get_int_range_min(value),
+                                                    gst::gst_value_get_int_range_max(value),
+                                                    gst::gst_value_get_int_range_step(value))
+                        },
+                        |value: &mut gobject::GValue, v: IntRange<i32>| {
+                            gst::gst_value_set_int_range_step(value, v.min, v.max, v.step)
+                        });
+impl_value_type_simple!(IntRange<i64>,
+                        Int64Range,
+                        *TYPE_INT64_RANGE,
+                        |value: &gobject::GValue| {
+                            IntRange::new_with_step(gst::gst_value_get_int64_range_min(value),
+                                                    gst::gst_value_get_int64_range_max(value),
+                                                    gst::gst_value_get_int64_range_step(value))
+                        },
+                        |value: &mut gobject::GValue, v: IntRange<i64>| {
+                            gst::gst_value_set_int64_range_step(value, v.min, v.max, v.step)
+                        });
+impl_value_type_simple!(DoubleRange,
+                        DoubleRange,
+                        *TYPE_DOUBLE_RANGE,
+                        |value: &gobject::GValue| {
+                            DoubleRange::new(gst::gst_value_get_double_range_min(value),
+                                             gst::gst_value_get_double_range_max(value))
+                        },
+                        |value: &mut gobject::GValue, v: DoubleRange| {
+                            gst::gst_value_set_double_range(value, v.min, v.max)
+                        });
+impl_value_type_simple!(FractionRange,
+                        FractionRange,
+                        *TYPE_FRACTION_RANGE,
+                        |value: &gobject::GValue| {
+                            let min = &*gst::gst_value_get_fraction_range_min(value);
+                            let max = &*gst::gst_value_get_fraction_range_max(value);
+                            FractionRange::new(Rational32::from_value(min).unwrap(),
+                                               Rational32::from_value(max).unwrap())
+                        },
+                        |value: &mut gobject::GValue, v: FractionRange| {
+                            gst::gst_value_set_fraction_range_full(value,
+                                                                   *v.min.numer(),
+                                                                   *v.min.denom(),
+                                                                   *v.max.numer(),
+                                                                   *v.max.denom())
+                        });
+impl_value_type_simple!(Bitmask,
+                        Bitmask,
+                        *TYPE_BITMASK,
+                        |value: &gobject::GValue| Bitmask(gst::gst_value_get_bitmask(value)),
+                        |value: &mut gobject::GValue, v: Bitmask| {
+                            gst::gst_value_set_bitmask(value, v.0)
+                        });
+
+// Like impl_value_type_simple, but for types covering all subtypes of a base
+// type and remembering the actual type
+macro_rules! impl_value_type_subtypes(
+    ($typ:ty, $variant:ident, $g_type:expr, $is_value_type:expr, $getter:expr, $setter:expr) => {
+        impl<'a> ValueType<'a> for $typ {
+            fn g_type() -> glib::GType {
+                $g_type
+            }
+
+            fn is_value_type(typ: glib::GType) -> bool {
+                unsafe {
+                    $is_value_type(typ)
+                }
+            }
+
+            fn from_value(value: &'a gobject::GValue) -> Option<Self> {
+                if !Self::is_value_type(value.g_type) {
+                    return None;
+                }
+
+                unsafe {
+                    Some($getter(&value))
+                }
+            }
+
+            fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
+                if let ValueView::$variant(ref v) = *value_view {
+                    Some(*v)
+                } else {
+                    None
+                }
+            }
+        }
+
+        impl From<$typ> for Value {
+            fn from(v: $typ) -> Value {
+                unsafe {
+                    let mut value = Value(mem::zeroed());
+
+                    gobject::g_value_init(&mut value.0, v.get_type());
+                    $setter(&mut value.0, v);
+
+                    value
+                }
+            }
+        }
+    };
+);
+
+impl_value_type_subtypes!(Flagset,
+                          Flagset,
+                          *TYPE_FLAGSET,
+                          |typ| gobject::g_type_is_a(typ, *TYPE_FLAGSET) == glib::GTRUE,
+                          |value: &gobject::GValue| {
+                              Flagset {
+                                  g_type: value.g_type,
+                                  flags: gst::gst_value_get_flagset_flags(value),
+                                  mask: gst::gst_value_get_flagset_mask(value),
+                              }
+                          },
+                          |value: &mut gobject::GValue, v: Flagset| {
+                              gst::gst_value_set_flagset(value, v.flags, v.mask)
+                          });
+impl_value_type_subtypes!(EnumValue,
+                          Enum,
+                          gobject::G_TYPE_ENUM,
+                          |typ| gobject::g_type_fundamental(typ) == gobject::G_TYPE_ENUM,
+                          |value: &gobject::GValue| {
+                              EnumValue {
+                                  g_type: value.g_type,
+                                  value: gobject::g_value_get_enum(value),
+                              }
+                          },
+                          |value: &mut gobject::GValue, v: EnumValue| {
+                              gobject::g_value_set_enum(value, v.value)
+                          });
+impl_value_type_subtypes!(FlagsValue,
+                          Flags,
+                          gobject::G_TYPE_FLAGS,
+                          |typ| gobject::g_type_fundamental(typ) == gobject::G_TYPE_FLAGS,
+                          |value: &gobject::GValue| {
+                              FlagsValue {
+                                  g_type: value.g_type,
+                                  value: gobject::g_value_get_flags(value),
+                              }
+                          },
+                          |value: &mut gobject::GValue, v: FlagsValue| {
+                              gobject::g_value_set_flags(value, v.value)
+                          });
+
 impl<'a> ValueType<'a> for &'a str {
     fn g_type() -> glib::GType {
         gobject::G_TYPE_STRING
@@ -414,6 +926,10 @@ impl<'a> ValueType<'a> for GstRc<Buffer> {
 
         unsafe {
             let buffer = gobject::g_value_get_boxed(value) as *mut gst::GstBuffer;
+            if buffer.is_null() {
+                return None;
+            }
+
             Some(GstRc::from_unowned_ptr(buffer))
         }
     }
@@ -452,9 +968,9 @@ impl<'a> From<&'a Buffer> for Value {
     }
 }
 
-impl<'a> ValueType<'a> for &'a [Value] {
+impl<'a> ValueType<'a> for GstRc<Caps> {
     fn g_type() -> glib::GType {
-        *TYPE_GST_VALUE_ARRAY
+        *TYPE_CAPS
     }
 
     fn from_value(value: &'a gobject::GValue) -> Option<Self> {
@@ -463,17 +979,227 @@ impl<'a> ValueType<'a> for &'a [Value] {
         }
 
         unsafe {
-            let arr = value.data[0] as *const glib::GArray;
+            let caps = gobject::g_value_get_boxed(value) as *mut gst::GstCaps;
+            if caps.is_null() {
+                return None;
+            }
 
-            if arr.is_null() {
-                Some(&[])
-            } else {
-                let arr = &*arr;
-                Some(slice::from_raw_parts(arr.data as *const Value, arr.len as usize))
+            Some(GstRc::from_unowned_ptr(caps))
+        }
+    }
+
+    fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
+        if let ValueView::Caps(ref v) = *value_view {
+            Some(v.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl From<GstRc<Caps>> for Value {
+    fn from(v: GstRc<Caps>) -> Value {
+        Value::from(v.as_ref())
+    }
+}
+
+impl<'a> From<&'a GstRc<Caps>> for Value {
+    fn from(v: &'a GstRc<Caps>) -> Value {
+        Value::from(v.as_ref())
+    }
+}
+
+impl<'a> From<&'a Caps> for Value {
+    fn from(v: &'a Caps) -> Value {
+        unsafe {
+            let mut value = Value(mem::zeroed());
+
+            gobject::g_value_init(&mut value.0, <GstRc<Caps> as ValueType>::g_type());
+            gobject::g_value_set_boxed(&mut value.0, v.as_ptr() as glib::gpointer);
+
+            value
+        }
+    }
+}
+
+impl<'a> ValueType<'a> for &'a Structure {
+    fn g_type() -> glib::GType {
+        *TYPE_STRUCTURE
+    }
+
+    fn from_value(value: &'a gobject::GValue) -> Option<Self> {
+        if value.g_type != Self::g_type() {
+            return None;
+        }
+
+        unsafe {
+            let structure = gobject::g_value_get_boxed(value) as *const gst::GstStructure;
+            if structure.is_null() {
+                return None;
+            }
+
+            Some(Structure::from_borrowed_ptr(structure))
+        }
+    }
+
+    fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
+        if let ValueView::Structure(ref v) = *value_view {
+            Some(v.as_ref())
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, Structure>> for Value {
+    fn from(v: Cow<'a, Structure>) -> Value {
+        unsafe {
+            let mut value = Value(mem::zeroed());
+
+            gobject::g_value_init(&mut value.0, <&Structure as ValueType>::g_type());
+
+            match v {
+                Cow::Borrowed(structure) => {
+                    gobject::g_value_set_boxed(&mut value.0,
+                                               structure as *const Structure as glib::gpointer);
+                }
+                Cow::Owned(structure) => {
+                    gobject::g_value_take_boxed(&mut value.0,
+                                                structure.into_ptr() as glib::gpointer);
+                }
             }
+
+            value
+        }
+    }
+}
+
+impl From<OwnedStructure> for Value {
+    fn from(v: OwnedStructure) -> Value {
+        Value::from(Cow::Owned::<Structure>(v))
+    }
+}
+
+impl<'a> From<&'a Structure> for Value {
+    fn from(v: &'a Structure) -> Value {
+        Value::from(Cow::Borrowed::<Structure>(v))
+    }
+}
+
+impl<'a> ValueType<'a> for DateTime {
+    fn g_type() -> glib::GType {
+        *TYPE_DATE_TIME
+    }
+
+    fn from_value(value: &'a gobject::GValue) -> Option<Self> {
+        if value.g_type != Self::g_type() {
+            return None;
+        }
+
+        unsafe {
+            let datetime = gobject::g_value_get_boxed(value) as *mut gst::GstDateTime;
+            DateTime::from_unowned_ptr(datetime)
         }
     }
 
+    fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
+        if let ValueView::DateTime(ref v) = *value_view {
+            Some(v.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl From<DateTime> for Value {
+    fn from(v: DateTime) -> Value {
+        unsafe {
+            let mut value = Value(mem::zeroed());
+
+            gobject::g_value_init(&mut value.0, <DateTime as ValueType>::g_type());
+            gobject::g_value_take_boxed(&mut value.0, v.into_ptr() as glib::gpointer);
+
+            value
+        }
+    }
+}
+
+impl<'a> From<&'a DateTime> for Value {
+    fn from(v: &'a DateTime) -> Value {
+        unsafe {
+            let mut value = Value(mem::zeroed());
+
+            gobject::g_value_init(&mut value.0, <DateTime as ValueType>::g_type());
+            gobject::g_value_set_boxed(&mut value.0, v.as_ptr() as glib::gpointer);
+
+            value
+        }
+    }
+}
+
+// Arrays and lists both store their values in a GArray
+unsafe fn garray_values<'a>(value: &'a gobject::GValue) -> &'a [Value] {
+    let arr = value.data[0] as *const glib::GArray;
+
+    if arr.is_null() {
+        &[]
+    } else {
+        let arr = &*arr;
+        slice::from_raw_parts(arr.data as *const Value, arr.len as usize)
+    }
+}
+
+impl<'a> ValueType<'a> for List<'a> {
+    fn g_type() -> glib::GType {
+        *TYPE_GST_VALUE_LIST
+    }
+
+    fn from_value(value: &'a gobject::GValue) -> Option<Self> {
+        if value.g_type != Self::g_type() {
+            return None;
+        }
+
+        unsafe { Some(List(garray_values(value))) }
+    }
+
+    fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
+        if let ValueView::List(ref v) = *value_view {
+            Some(List(v.as_ref()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> From<List<'a>> for Value {
+    fn from(v: List<'a>) -> Value {
+        unsafe {
+            let mut value = Value(mem::zeroed());
+
+            gobject::g_value_init(&mut value.0, <List as ValueType>::g_type());
+
+            for e in v.0 {
+                gst::gst_value_list_append_value(&mut value.0, e.as_ptr() as *mut gobject::GValue);
+            }
+
+            value
+        }
+    }
+}
+
+impl<'a> ValueType<'a> for &'a [Value] {
+    fn g_type() -> glib::GType {
+        *TYPE_GST_VALUE_ARRAY
+    }
+
+    fn from_value(value: &'a gobject::GValue) -> Option<Self> {
+        if value.g_type != Self::g_type() {
+            return None;
+        }
+
+        unsafe { Some(garray_values(value)) }
+    }
+
     fn from_value_view(value_view: &'a ValueView<'a>) -> Option<Self> {
         if let ValueView::Array(ref v) = *value_view {
             Some(v.as_ref())
@@ -548,12 +1274,14 @@ impl From<(i32, i32)> for Value {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct TypedValue<T> {
     value: Value,
     phantom: PhantomData<T>,
 }
 
+impl<T: Eq> Eq for TypedValue<T> {}
+
 impl<'a, T> TypedValue<T>
     where T: ValueType<'a>
 {
@@ -562,7 +1290,7 @@ impl<'a, T> TypedValue<T>
     }
 
     pub fn from_value(value: Value) -> Option<TypedValue<T>> {
-        if value.0.g_type != T::g_type() {
+        if !T::is_value_type(value.0.g_type) {
             return None;
         }
 
@@ -660,12 +1388,44 @@ impl<'a> From<&'a Buffer> for TypedValue<GstRc<Buffer>> {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+impl<'a> From<&'a GstRc<Caps>> for TypedValue<GstRc<Caps>> {
+    fn from(v: &'a GstRc<Caps>) -> Self {
+        TypedValue::from_value(Value::new(v)).unwrap()
+    }
+}
+
+impl<'a> From<&'a DateTime> for TypedValue<DateTime> {
+    fn from(v: &'a DateTime) -> Self {
+        TypedValue::from_value(Value::new(v)).unwrap()
+    }
+}
+
+impl<'a> From<&'a Caps> for TypedValue<GstRc<Caps>> {
+    fn from(v: &'a Caps) -> Self {
+        TypedValue::from_value(Value::new(v)).unwrap()
+    }
+}
+
+impl<'a> From<OwnedStructure> for TypedValue<&'a Structure> {
+    fn from(v: OwnedStructure) -> Self {
+        TypedValue::from_value(Value::new(v)).unwrap()
+    }
+}
+
+impl<'a> From<Cow<'a, Structure>> for TypedValue<&'a Structure> {
+    fn from(v: Cow<'a, Structure>) -> Self {
+        TypedValue::from_value(Value::new(v)).unwrap()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct TypedValueRef<'a, T> {
     value: ValueRef<'a>,
     phantom: PhantomData<T>,
 }
 
+impl<'a, T: Eq> Eq for TypedValueRef<'a, T> {}
+
 impl<'a, T> TypedValueRef<'a, T>
     where T: ValueType<'a>
 {
@@ -677,7 +1437,7 @@ impl<'a, T> TypedValueRef<'a, T>
     }
 
     pub fn from_value_ref(value: ValueRef<'a>) -> Option<TypedValueRef<'a, T>> {
-        if value.0.g_type != T::g_type() {
+        if !T::is_value_type(value.0.g_type) {
             return None;
         }
 
@@ -759,6 +1519,20 @@ mod tests {
     gen_test_value!(uint64, u64, 12u64, UInt64);
     gen_test_value!(boolean, bool, true, Bool);
     gen_test_value!(fraction, Rational32, Rational32::new(1, 2), Fraction);
+    gen_test_value!(float, f32, 1.5f32, Float);
+    gen_test_value!(double, f64, 1.5f64, Double);
+    gen_test_value!(int_range, IntRange<i32>, IntRange::new(1i32, 10), IntRange);
+    gen_test_value!(int64_range,
+                    IntRange<i64>,
+                    IntRange::new_with_step(0i64, 100, 10),
+                    Int64Range);
+    gen_test_value!(double_range, DoubleRange, DoubleRange::new(0.5, 1.5), DoubleRange);
+    gen_test_value!(fraction_range,
+                    FractionRange,
+                    FractionRange::new(Rational32::new(1, 2), Rational32::new(30, 1)),
+                    FractionRange);
+    gen_test_value!(bitmask, Bitmask, Bitmask(0x3), Bitmask);
+    gen_test_value!(flagset, Flagset, Flagset::new(0x1, 0x3), Flagset);
 
     #[test]
     fn string_owned() {
@@ -940,4 +1714,180 @@ mod tests {
             unreachable!();
         }
     }
+    #[test]
+    fn caps() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let orig_v = Caps::new_simple("foo/bar", &[("int", 12.into())]);
+
+        let value = Value::new(orig_v.clone());
+        if let ValueView::Caps(caps) = value.get() {
+            assert_eq!(caps, orig_v);
+        } else {
+            unreachable!();
+        }
+
+        if let Some(caps) = value.get().try_get::<GstRc<Caps>>() {
+            assert_eq!(caps, orig_v);
+        } else {
+            unreachable!();
+        }
+
+        let value2 = Value::from_value_view(value.get());
+        assert_eq!(value2, value);
+
+        let value3 = TypedValue::new(&orig_v);
+        assert_eq!(value3.get(), orig_v);
+
+        if let Some(value3) = TypedValue::<GstRc<Caps>>::from_value(value) {
+            assert_eq!(value3.get(), orig_v);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn structure() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let orig_v = OwnedStructure::new("foo", &[("int", 12.into())]);
+
+        let value = Value::new(orig_v.clone());
+        if let ValueView::Structure(s) = value.get() {
+            assert_eq!(s.as_ref(), orig_v.as_ref());
+        } else {
+            unreachable!();
+        }
+
+        if let Some(s) = value.get().try_get::<&Structure>() {
+            assert_eq!(s, orig_v.as_ref());
+        } else {
+            unreachable!();
+        }
+
+        let value2 = Value::from_value_view(value.get());
+        assert_eq!(value2, value);
+
+        let value3 = TypedValue::new(orig_v.as_ref());
+        assert_eq!(value3.get(), orig_v.as_ref());
+
+        if let Some(value3) = TypedValue::<&Structure>::from_value(value) {
+            assert_eq!(value3.get(), orig_v.as_ref());
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn list() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let orig_v = vec![Value::new("a"), Value::new("b")];
+
+        let value = Value::new(List(&orig_v));
+        if let ValueView::List(list) = value.get() {
+            assert_eq!(list, orig_v.as_slice());
+        } else {
+            unreachable!();
+        }
+
+        if let Some(list) = value.get().try_get::<List>() {
+            assert_eq!(list.0, orig_v.as_slice());
+        } else {
+            unreachable!();
+        }
+
+        let value2 = Value::from_value_view(value.get());
+        assert_eq!(value2, value);
+
+        // Lists are unordered, arrays are not
+        let reversed = vec![Value::new("b"), Value::new("a")];
+        assert_eq!(Value::new(List(&reversed)), value);
+        assert!(Value::new(&reversed) != Value::new(&orig_v));
+        assert!(Value::new(List(&reversed)) != Value::new(&orig_v));
+    }
+
+    #[test]
+    fn datetime() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let orig_v = DateTime::new_ymd(2017, 5, 1).unwrap();
+        assert_eq!(orig_v.get_year(), 2017);
+        assert_eq!(orig_v.get_month(), Some(5));
+        assert_eq!(orig_v.get_hour(), None);
+        assert_eq!(orig_v.to_iso8601_string(), "2017-05-01");
+
+        let value = Value::new(&orig_v);
+        if let ValueView::DateTime(datetime) = value.get() {
+            assert_eq!(datetime, orig_v);
+        } else {
+            unreachable!();
+        }
+
+        let value2 = Value::from_value_view(value.get());
+        assert_eq!(value2, value);
+
+        if let Some(value3) = TypedValue::<DateTime>::from_value(value) {
+            assert_eq!(value3.get(), orig_v);
+        } else {
+            unreachable!();
+        }
+
+        assert_eq!(DateTime::from_iso8601_string("2017-05-01").unwrap(), orig_v);
+    }
+
+    #[test]
+    fn enum_flags() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        let format_type = unsafe { gst::gst_format_get_type() };
+        let orig_v = EnumValue::from_nick(format_type, "time").unwrap();
+        assert_eq!(orig_v.get_value(), gst::GST_FORMAT_TIME as i32);
+        assert_eq!(orig_v.get_nick(), Some(String::from("time")));
+
+        let value = Value::new(orig_v);
+        if let ValueView::Enum(v) = value.get() {
+            assert_eq!(v, orig_v);
+        } else {
+            unreachable!();
+        }
+        assert_eq!(Value::from_value_view(value.get()), value);
+        assert_eq!(TypedValue::<EnumValue>::from_value(value).unwrap().get(),
+                   orig_v);
+
+        let flags_type = unsafe { gst::gst_seek_flags_get_type() };
+        let orig_v = FlagsValue::new(flags_type, gst::GST_SEEK_FLAG_FLUSH.bits());
+        let value = Value::new(orig_v);
+        if let ValueView::Flags(v) = value.get() {
+            assert_eq!(v, orig_v);
+        } else {
+            unreachable!();
+        }
+        assert_eq!(TypedValue::<FlagsValue>::from_value(value).unwrap().get(),
+                   orig_v);
+    }
+
+    #[test]
+    fn compare() {
+        unsafe { gst::gst_init(ptr::null_mut(), ptr::null_mut()) };
+
+        assert!(Value::new(1i32) < Value::new(2i32));
+        assert!(Value::new(1.5f64) > Value::new(0.5f64));
+        assert_eq!(Value::new(1i32).partial_cmp(&Value::new("a")), None);
+        assert!(Value::new(::std::f64::NAN) != Value::new(::std::f64::NAN));
+
+        let range = Value::new(IntRange::new(1i32, 10));
+        assert_eq!(range, Value::new(IntRange::new(1i32, 10)));
+        assert_eq!(range.partial_cmp(&Value::new(IntRange::new(1i32, 20))), None);
+        assert!(!range.is_fixed());
+        assert!(Value::new(5i32).is_fixed());
+        assert!(Value::new(5i32).is_subset(&range));
+        assert!(!Value::new(20i32).is_subset(&range));
+        assert!(range.can_intersect(&Value::new(5i32)));
+        assert_eq!(range.intersect(&Value::new(IntRange::new(5i32, 20))),
+                   Some(Value::new(IntRange::new(5i32, 10))));
+        assert_eq!(range.intersect(&Value::new(20i32)), None);
+        let range2 = Value::new(IntRange::new(3i32, 20));
+        assert_eq!(range.union(&range2), Some(Value::new(IntRange::new(1i32, 20))));
+    }
 }